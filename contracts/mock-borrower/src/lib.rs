@@ -0,0 +1,127 @@
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env, ext_contract,
+    json_types::U128,
+    near_bindgen, require,
+    serde::{Deserialize, Serialize},
+    AccountId, Gas, PanicOnDefault, Promise, PromiseOrValue, ONE_YOCTO,
+};
+
+const TGAS: u64 = Gas::ONE_TERA.0;
+const GAS_FLASH_LOAN: Gas = Gas(250 * TGAS);
+const GAS_REPAY: Gas = Gas(60 * TGAS);
+const GAS_REPAY_CALLBACK: Gas = Gas(10 * TGAS);
+
+/// How the borrower deals with a flash loan
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// repay principal and fee
+    Repay,
+    /// repay principal only
+    RepayPrincipal,
+    /// keep all borrowed LiNEAR
+    Keep,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct FlashLoanMessage {
+    amount: U128,
+    fee: U128,
+}
+
+// only the generated `ext_phoenix` module is used
+#[allow(dead_code)]
+#[ext_contract(ext_phoenix)]
+trait PhoenixBonds {
+    fn flash_loan(&mut self, amount: U128, msg: String) -> U128;
+}
+
+#[allow(dead_code)]
+#[ext_contract(ext_fungible_token)]
+trait FungibleToken {
+    fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> U128;
+}
+
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+pub struct MockBorrower {
+    linear_address: AccountId,
+    phoenix_address: AccountId,
+    mode: Mode,
+}
+
+#[near_bindgen]
+impl MockBorrower {
+    #[init]
+    pub fn new(linear_address: AccountId, phoenix_address: AccountId) -> Self {
+        Self {
+            linear_address,
+            phoenix_address,
+            mode: Mode::Repay,
+        }
+    }
+
+    pub fn borrow(&mut self, amount: U128) -> Promise {
+        ext_phoenix::ext(self.phoenix_address.clone())
+            .with_static_gas(GAS_FLASH_LOAN)
+            .flash_loan(amount, "".to_string())
+    }
+
+    // -- mock contract methods
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    #[private]
+    pub fn on_repaid(&mut self) -> U128 {
+        // all borrowed LiNEAR is used
+        U128(0)
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for MockBorrower {
+    #[allow(unused_variables)]
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        require!(env::predecessor_account_id() == self.linear_address);
+        let loan: FlashLoanMessage = near_sdk::serde_json::from_str(&msg).unwrap();
+
+        let repay_amount = match self.mode {
+            Mode::Repay => loan.amount.0 + loan.fee.0,
+            Mode::RepayPrincipal => loan.amount.0,
+            Mode::Keep => return PromiseOrValue::Value(U128(0)),
+        };
+
+        ext_fungible_token::ext(self.linear_address.clone())
+            .with_static_gas(GAS_REPAY)
+            .with_attached_deposit(ONE_YOCTO)
+            .ft_transfer_call(
+                self.phoenix_address.clone(),
+                repay_amount.into(),
+                None,
+                "\"FlashLoanRepay\"".to_string(),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_REPAY_CALLBACK)
+                    .on_repaid(),
+            )
+            .into()
+    }
+}
@@ -1,10 +1,70 @@
 use crate::*;
-use near_contract_standards::fungible_token::events::FtMint;
-use near_sdk::{json_types::U128, AccountId, Balance, PromiseOrValue};
+use near_contract_standards::fungible_token::{
+    core::FungibleTokenCore, events::FtMint, resolver::FungibleTokenResolver,
+};
+use near_sdk::{json_types::U128, AccountId, Balance, Gas, Promise, PromiseOrValue};
+
+const GAS_FAIL_TRANSFER: Gas = Gas(5_000_000_000_000);
 
-near_contract_standards::impl_fungible_token_core!(MockLinear, tokens);
 near_contract_standards::impl_fungible_token_storage!(MockLinear, tokens);
 
+#[near_bindgen]
+impl MockLinear {
+    #[payable]
+    pub fn ft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) -> PromiseOrValue<()> {
+        require!(!self.ft_transfer_panic, "LiNEAR ft_transfer Panic");
+        if let Some(failure) = self.take_unregistered_transfer(&receiver_id) {
+            return PromiseOrValue::Promise(failure);
+        }
+        self.tokens.ft_transfer(receiver_id, amount, memo);
+        PromiseOrValue::Value(())
+    }
+
+    #[payable]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        require!(!self.ft_transfer_panic, "LiNEAR ft_transfer Panic");
+        if let Some(failure) = self.take_unregistered_transfer(&receiver_id) {
+            return PromiseOrValue::Promise(failure);
+        }
+        self.tokens.ft_transfer_call(receiver_id, amount, memo, msg)
+    }
+
+    pub fn ft_total_supply(&self) -> U128 {
+        self.tokens.ft_total_supply()
+    }
+
+    pub fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.tokens.ft_balance_of(account_id)
+    }
+
+    #[private]
+    pub fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        self.tokens
+            .ft_resolve_transfer(sender_id, receiver_id, amount)
+    }
+
+    #[private]
+    pub fn fail_unregistered_transfer(&mut self, receiver_id: AccountId) {
+        env::panic_str(format!("The account {} is not registered", receiver_id).as_str());
+    }
+}
+
 impl MockLinear {
     pub(crate) fn mint_linear(
         &mut self,
@@ -23,4 +83,18 @@ impl MockLinear {
         }
         .emit();
     }
+
+    /// State changes of a failed receipt are reverted, so the transfer fails
+    /// in a following receipt to keep the count of unregistered transfers.
+    fn take_unregistered_transfer(&mut self, receiver_id: &AccountId) -> Option<Promise> {
+        if self.unregistered_transfers == 0 {
+            return None;
+        }
+        self.unregistered_transfers -= 1;
+        Some(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FAIL_TRANSFER)
+                .fail_unregistered_transfer(receiver_id.clone()),
+        )
+    }
 }
@@ -2,12 +2,26 @@ use near_bigdecimal::*;
 use near_contract_standards::fungible_token::FungibleToken;
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
+    collections::LookupMap,
     env,
     json_types::U128,
-    near_bindgen, require, PanicOnDefault, ONE_NEAR,
+    near_bindgen, require, AccountId, Balance, EpochHeight, PanicOnDefault, ONE_NEAR,
 };
 
 mod ft;
+mod stake;
+
+/// Same as LiNEAR, unstaked NEAR can be withdrawn after 4 epochs
+const NUM_EPOCHS_TO_UNLOCK: EpochHeight = 4;
+/// Precision of the per-epoch reward rate
+const FULL_REWARD_RATE: u128 = 1_000_000;
+const FULL_BASIS_POINT: u32 = 10_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct UnstakedBalance {
+    amount: Balance,
+    available_epoch_height: EpochHeight,
+}
 
 #[near_bindgen]
 #[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
@@ -16,6 +30,21 @@ pub struct MockLinear {
     tokens: FungibleToken,
     panic: bool,
     small_change: bool,
+    /// panic on `ft_transfer` and `ft_transfer_call`
+    ft_transfer_panic: bool,
+    /// number of upcoming transfers that fail as if the receiver is not registered
+    unregistered_transfers: u32,
+    /// panic on `ft_price`
+    ft_price_panic: bool,
+    /// return zero on `ft_price`
+    zero_price: bool,
+    /// mocked epoch height, only moves forward with `advance_epochs`
+    epoch_height: EpochHeight,
+    /// LiNEAR price growth per epoch, in parts per million
+    epoch_reward_rate: u32,
+    unstaked: LookupMap<AccountId, UnstakedBalance>,
+    /// fee of instant unstake via the liquidity pool, in basis points
+    instant_unstake_fee: u32,
 }
 
 #[near_bindgen]
@@ -27,6 +56,14 @@ impl MockLinear {
             tokens: FungibleToken::new(b't'),
             panic: false,
             small_change: false,
+            ft_transfer_panic: false,
+            unregistered_transfers: 0,
+            ft_price_panic: false,
+            zero_price: false,
+            epoch_height: env::epoch_height(),
+            epoch_reward_rate: 0,
+            unstaked: LookupMap::new(b'u'),
+            instant_unstake_fee: 30,
         }
     }
 
@@ -37,8 +74,7 @@ impl MockLinear {
         require!(!self.panic, "LiNEAR Panic");
         let account_id = env::predecessor_account_id();
         let amount = env::attached_deposit();
-        let mut shares =
-            (BigDecimal::from(amount) * ONE_NEAR.into() / self.linear_price.into()).round_u128();
+        let mut shares = self.shares_from_amount(amount);
         if self.small_change {
             shares -= 10;
         }
@@ -49,6 +85,10 @@ impl MockLinear {
     }
 
     pub fn ft_price(&self) -> U128 {
+        require!(!self.ft_price_panic, "LiNEAR ft_price Panic");
+        if self.zero_price {
+            return 0.into();
+        }
         self.linear_price.into()
     }
 
@@ -65,4 +105,45 @@ impl MockLinear {
     pub fn set_small_change(&mut self, small_change: bool) {
         self.small_change = small_change;
     }
+
+    pub fn set_ft_transfer_panic(&mut self, panic: bool) {
+        self.ft_transfer_panic = panic;
+    }
+
+    /// Make the next `count` transfers fail as if the receiver is not registered
+    pub fn set_unregistered_transfers(&mut self, count: u32) {
+        self.unregistered_transfers = count;
+    }
+
+    pub fn set_ft_price_panic(&mut self, panic: bool) {
+        self.ft_price_panic = panic;
+    }
+
+    pub fn set_zero_price(&mut self, zero_price: bool) {
+        self.zero_price = zero_price;
+    }
+
+    /// Set LiNEAR price growth per epoch in parts per million,
+    /// e.g. 137 is about 10% APR given 730 epochs a year
+    pub fn set_epoch_reward_rate(&mut self, rate: u32) {
+        self.epoch_reward_rate = rate;
+    }
+
+    /// Move to a following epoch, LiNEAR price grows by the reward rate in each epoch
+    pub fn advance_epochs(&mut self, count: EpochHeight) {
+        for _ in 0..count {
+            self.linear_price = self.linear_price
+                * (FULL_REWARD_RATE + self.epoch_reward_rate as u128)
+                / FULL_REWARD_RATE;
+        }
+        self.epoch_height += count;
+    }
+
+    pub fn get_epoch_height(&self) -> EpochHeight {
+        self.epoch_height
+    }
+
+    pub fn set_instant_unstake_fee(&mut self, fee: u32) {
+        self.instant_unstake_fee = fee;
+    }
 }
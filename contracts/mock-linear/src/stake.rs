@@ -0,0 +1,128 @@
+use crate::*;
+use near_contract_standards::fungible_token::events::FtBurn;
+use near_sdk::{
+    serde::{Deserialize, Serialize},
+    Promise,
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountDetailsView {
+    pub account_id: AccountId,
+    pub unstaked_balance: U128,
+    pub staked_balance: U128,
+    pub unstaked_available_epoch_height: EpochHeight,
+    pub can_withdraw: bool,
+}
+
+#[near_bindgen]
+impl MockLinear {
+    // -- public LiNEAR methods
+
+    /// Unstake `amount` NEAR by burning LiNEAR
+    pub fn unstake(&mut self, amount: U128) {
+        let shares = self.shares_from_amount(amount.0);
+        self.internal_unstake(amount.0, shares);
+    }
+
+    pub fn unstake_all(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let shares = self.ft_balance_of(account_id).0;
+        self.internal_unstake(self.amount_from_shares(shares), shares);
+    }
+
+    /// Withdraw `amount` unstaked NEAR once it's unlocked
+    pub fn withdraw(&mut self, amount: U128) {
+        self.internal_withdraw(Some(amount.0));
+    }
+
+    pub fn withdraw_all(&mut self) {
+        self.internal_withdraw(None);
+    }
+
+    /// Swap LiNEAR for NEAR in the liquidity pool, which charges a fee.
+    /// Returns the amount of NEAR received.
+    pub fn instant_unstake(&mut self, stake_shares_in: U128, min_amount_out: U128) -> U128 {
+        require!(!self.panic, "LiNEAR Panic");
+        let account_id = env::predecessor_account_id();
+        let shares = stake_shares_in.0;
+        let amount = self.amount_from_shares(shares);
+        let received =
+            amount - amount * self.instant_unstake_fee as u128 / FULL_BASIS_POINT as u128;
+        require!(
+            received >= min_amount_out.0,
+            "Received NEAR is less than min_amount_out"
+        );
+
+        self.burn_linear(&account_id, shares, "instant unstake");
+        Promise::new(account_id).transfer(received);
+        received.into()
+    }
+
+    pub fn get_account_details(&self, account_id: AccountId) -> AccountDetailsView {
+        let unstaked = self.unstaked.get(&account_id).unwrap_or_default();
+        let shares = self.ft_balance_of(account_id.clone()).0;
+        AccountDetailsView {
+            account_id,
+            unstaked_balance: unstaked.amount.into(),
+            staked_balance: self.amount_from_shares(shares).into(),
+            unstaked_available_epoch_height: unstaked.available_epoch_height,
+            can_withdraw: unstaked.available_epoch_height <= self.epoch_height,
+        }
+    }
+}
+
+impl MockLinear {
+    pub(crate) fn shares_from_amount(&self, amount: Balance) -> Balance {
+        (BigDecimal::from(amount) * ONE_NEAR.into() / self.linear_price.into()).round_u128()
+    }
+
+    fn amount_from_shares(&self, shares: Balance) -> Balance {
+        (BigDecimal::from(shares) * self.linear_price.into() / ONE_NEAR.into()).round_u128()
+    }
+
+    fn burn_linear(&mut self, account_id: &AccountId, shares: Balance, memo: &str) {
+        self.tokens.internal_withdraw(account_id, shares);
+        FtBurn {
+            owner_id: account_id,
+            amount: &U128(shares),
+            memo: Some(memo),
+        }
+        .emit();
+    }
+
+    fn internal_unstake(&mut self, amount: Balance, shares: Balance) {
+        require!(!self.panic, "LiNEAR Panic");
+        require!(amount > 0, "Unstaking amount should be positive");
+        let account_id = env::predecessor_account_id();
+
+        self.burn_linear(&account_id, shares, "unstake");
+
+        // like LiNEAR, each unstake postpones the unlock of all unstaked NEAR
+        let mut unstaked = self.unstaked.get(&account_id).unwrap_or_default();
+        unstaked.amount += amount;
+        unstaked.available_epoch_height = self.epoch_height + NUM_EPOCHS_TO_UNLOCK;
+        self.unstaked.insert(&account_id, &unstaked);
+    }
+
+    /// Withdraw given amount or all of unstaked NEAR
+    fn internal_withdraw(&mut self, amount: Option<Balance>) {
+        require!(!self.panic, "LiNEAR Panic");
+        let account_id = env::predecessor_account_id();
+        let mut unstaked = self.unstaked.get(&account_id).unwrap_or_default();
+        let amount = amount.unwrap_or(unstaked.amount);
+        require!(amount > 0, "Withdrawal amount should be positive");
+        require!(
+            unstaked.amount >= amount,
+            "Not enough unstaked balance to withdraw"
+        );
+        require!(
+            unstaked.available_epoch_height <= self.epoch_height,
+            "The unstaked balance is not yet available due to unstaking delay"
+        );
+
+        unstaked.amount -= amount;
+        self.unstaked.insert(&account_id, &unstaked);
+        Promise::new(account_id).transfer(amount);
+    }
+}
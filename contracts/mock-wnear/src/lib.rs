@@ -0,0 +1,117 @@
+use near_contract_standards::fungible_token::{
+    core::FungibleTokenCore,
+    events::{FtBurn, FtMint},
+    resolver::FungibleTokenResolver,
+    FungibleToken,
+};
+use near_sdk::{
+    assert_one_yocto,
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    env,
+    json_types::U128,
+    near_bindgen, require, AccountId, PanicOnDefault, Promise, PromiseOrValue,
+};
+
+/// Mock of wrap.near, which wraps NEAR 1:1 into a fungible token
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
+pub struct MockWnear {
+    tokens: FungibleToken,
+    /// panic on `near_withdraw`
+    withdraw_panic: bool,
+    /// panic on `near_deposit`
+    deposit_panic: bool,
+}
+
+near_contract_standards::impl_fungible_token_storage!(MockWnear, tokens);
+
+#[near_bindgen]
+impl MockWnear {
+    #[init]
+    pub fn new() -> Self {
+        Self {
+            tokens: FungibleToken::new(b't'),
+            withdraw_panic: false,
+            deposit_panic: false,
+        }
+    }
+
+    // -- public wNEAR methods
+
+    #[payable]
+    pub fn near_deposit(&mut self) {
+        require!(!self.deposit_panic, "wNEAR near_deposit Panic");
+        let account_id = env::predecessor_account_id();
+        let amount = env::attached_deposit();
+        if !self.tokens.accounts.contains_key(&account_id) {
+            self.tokens.internal_register_account(&account_id);
+        }
+        self.tokens.internal_deposit(&account_id, amount);
+        FtMint {
+            owner_id: &account_id,
+            amount: &U128(amount),
+            memo: Some("Deposit"),
+        }
+        .emit();
+    }
+
+    #[payable]
+    pub fn near_withdraw(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+        require!(!self.withdraw_panic, "wNEAR near_withdraw Panic");
+        let account_id = env::predecessor_account_id();
+        self.tokens.internal_withdraw(&account_id, amount.0);
+        FtBurn {
+            owner_id: &account_id,
+            amount: &amount,
+            memo: Some("Withdraw"),
+        }
+        .emit();
+        Promise::new(account_id).transfer(amount.0)
+    }
+
+    #[payable]
+    pub fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        self.tokens.ft_transfer(receiver_id, amount, memo)
+    }
+
+    #[payable]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        self.tokens.ft_transfer_call(receiver_id, amount, memo, msg)
+    }
+
+    pub fn ft_total_supply(&self) -> U128 {
+        self.tokens.ft_total_supply()
+    }
+
+    pub fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.tokens.ft_balance_of(account_id)
+    }
+
+    #[private]
+    pub fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        self.tokens
+            .ft_resolve_transfer(sender_id, receiver_id, amount)
+    }
+
+    // -- mock contract methods
+
+    pub fn set_withdraw_panic(&mut self, panic: bool) {
+        self.withdraw_panic = panic;
+    }
+
+    pub fn set_deposit_panic(&mut self, panic: bool) {
+        self.deposit_panic = panic;
+    }
+}
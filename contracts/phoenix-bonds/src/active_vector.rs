@@ -1,6 +1,6 @@
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
-    store::{UnorderedSet, Vector},
+    store::{LookupMap, UnorderedSet},
     IntoStorageKey,
 };
 use std::cmp::min;
@@ -9,14 +9,134 @@ pub trait Active {
     fn is_active(&self) -> bool;
 }
 
-/// Maintains a list of items as well as a set
-/// of active items.
+/// Number of bits of a word in `ActiveIndex`
+const WORD_BITS: u32 = u64::BITS;
+/// Words of the top level cover 64^4 indices each
+const TOP_LEVEL: u8 = 3;
+
+/// Hierarchical bitmap of active item indices. Bit `i` of word `w` is set if
+/// index `w * 64 + i` is active at level 0, or if word `w * 64 + i` of the
+/// level below is not empty at higher levels. The next active index can be
+/// found by reading a few words no matter how far away it is.
 #[derive(BorshSerialize, BorshDeserialize)]
+struct ActiveIndex {
+    /// non-empty words by level and position
+    words: LookupMap<(u8, u32), u64>,
+}
+
+impl ActiveIndex {
+    fn new<S>(prefix: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        Self {
+            words: LookupMap::new(prefix),
+        }
+    }
+
+    fn word(&self, level: u8, position: u32) -> u64 {
+        self.words.get(&(level, position)).copied().unwrap_or(0)
+    }
+
+    fn insert(&mut self, index: u32) {
+        let mut position = index;
+        for level in 0..=TOP_LEVEL {
+            let word = self.word(level, position / WORD_BITS);
+            self.words.insert(
+                (level, position / WORD_BITS),
+                word | 1 << (position % WORD_BITS),
+            );
+            // upper levels already mark this word
+            if word != 0 {
+                return;
+            }
+            position /= WORD_BITS;
+        }
+    }
+
+    fn remove(&mut self, index: u32) {
+        let mut position = index;
+        for level in 0..=TOP_LEVEL {
+            let word = self.word(level, position / WORD_BITS) & !(1 << (position % WORD_BITS));
+            if word != 0 {
+                self.words.insert((level, position / WORD_BITS), word);
+                return;
+            }
+            self.words.remove(&(level, position / WORD_BITS));
+            position /= WORD_BITS;
+        }
+    }
+
+    /// The smallest active index in `[from_index, len)`
+    fn next(&self, from_index: u32, len: u32) -> Option<u32> {
+        let mut level = 0;
+        let mut position = from_index;
+        // go up until a word with a set bit at or after `position` is found
+        loop {
+            let word = self.word(level, position / WORD_BITS) & u64::MAX << (position % WORD_BITS);
+            if word != 0 {
+                position = position / WORD_BITS * WORD_BITS + word.trailing_zeros();
+                break;
+            }
+            let next_word = position / WORD_BITS + 1;
+            if level < TOP_LEVEL {
+                level += 1;
+                position = next_word;
+            } else {
+                // top level words are not indexed, scan them until `len` is covered
+                let first_index =
+                    u64::from(next_word) * u64::from(WORD_BITS).pow(u32::from(TOP_LEVEL) + 1);
+                if first_index >= u64::from(len) {
+                    return None;
+                }
+                position = next_word * WORD_BITS;
+            }
+        }
+        // go down along the first set bits
+        while level > 0 {
+            level -= 1;
+            position = position * WORD_BITS + self.word(level, position).trailing_zeros();
+        }
+        Some(position).filter(|index| *index < len)
+    }
+}
+
+/// Maintains a list of items as well as a set
+/// of active items. Inactive items could be removed,
+/// while indices of the other items remain unchanged.
+///
+/// NOTE: `len` and `items` share the same storage layout with
+/// `store::Vector`, which was used to store items before removal is supported.
+#[derive(BorshSerialize)]
 pub struct ActiveVector<T: Active + BorshSerialize + BorshDeserialize> {
-    /// all items
-    items: Vector<T>,
+    /// number of items ever appended, which is also the index of next item
+    len: u32,
+    /// all items that are not removed
+    items: LookupMap<u32, T>,
     /// active item indexes
     active_items: UnorderedSet<u32>,
+    /// ordered index of `active_items`, which is missing in vectors
+    /// stored before it was added until `build_index()` is called
+    active_index: Option<ActiveIndex>,
+}
+
+impl<T: Active + BorshSerialize + BorshDeserialize> BorshDeserialize for ActiveVector<T> {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self {
+            len: BorshDeserialize::deserialize(buf)?,
+            items: BorshDeserialize::deserialize(buf)?,
+            active_items: BorshDeserialize::deserialize(buf)?,
+            active_index: if buf.is_empty() {
+                None
+            } else {
+                BorshDeserialize::deserialize(buf)?
+            },
+        })
+    }
+}
+
+fn active_index_prefix(items_prefix: &[u8]) -> Vec<u8> {
+    [items_prefix, "_index".as_bytes()].concat()
 }
 
 impl<T: Active + BorshSerialize + BorshDeserialize> ActiveVector<T> {
@@ -28,24 +148,47 @@ impl<T: Active + BorshSerialize + BorshDeserialize> ActiveVector<T> {
         let items_prefix = item_prefix.as_slice();
         let active_prefix = [items_prefix, "_active".as_bytes()].concat();
         Self {
-            items: Vector::new(items_prefix),
+            len: 0,
+            items: LookupMap::new(items_prefix),
             active_items: UnorderedSet::new(active_prefix),
+            active_index: Some(ActiveIndex::new(active_index_prefix(items_prefix))),
+        }
+    }
+
+    /// Index active items of a vector stored before the index was added,
+    /// which reads all active items once. `items_key` must be the key the
+    /// vector was created with.
+    pub fn build_index<S>(&mut self, items_key: S)
+    where
+        S: IntoStorageKey,
+    {
+        if self.active_index.is_some() {
+            return;
         }
+        let mut active_index = ActiveIndex::new(active_index_prefix(&items_key.into_storage_key()));
+        for index in self.active_items.iter() {
+            active_index.insert(*index);
+        }
+        self.active_index = Some(active_index);
     }
 
     pub fn append(&mut self, item: T) -> u32 {
-        let index = self.items.len();
+        let index = self.len;
         if item.is_active() {
             self.active_items.insert(index);
+            if let Some(active_index) = self.active_index.as_mut() {
+                active_index.insert(index);
+            }
         }
 
-        self.items.push(item);
+        self.items.insert(index, item);
+        self.len += 1;
 
         index
     }
 
     pub fn len(&self) -> u32 {
-        self.items.len()
+        self.len
     }
 
     pub fn active_len(&self) -> u32 {
@@ -53,32 +196,267 @@ impl<T: Active + BorshSerialize + BorshDeserialize> ActiveVector<T> {
     }
 
     pub fn get(&self, index: u32) -> Option<&T> {
-        self.items.get(index)
+        self.items.get(&index)
     }
 
     pub fn get_items(&self, offset: u32, limit: u32) -> Vec<&T> {
-        (offset..min(self.items.len(), offset + limit))
-            .map(|index| self.items.get(index).unwrap())
+        (offset..min(self.len, offset.saturating_add(limit)))
+            .filter_map(|index| self.items.get(&index))
             .collect()
     }
 
-    pub fn get_active_item_indices(&self) -> Vec<u32> {
-        self.active_items.iter().copied().collect()
-    }
+    /// Indices of active items in ascending order, starting from `from_index`.
+    /// Vectors without the ordered index read all active indices instead.
+    pub fn get_active_item_indices(&self, from_index: u32, limit: u32) -> Vec<u32> {
+        if from_index >= self.len || limit == 0 || self.active_items.is_empty() {
+            return vec![];
+        }
 
-    pub fn get_item_indices(&self) -> Vec<u32> {
-        (0..self.items.len()).collect()
+        match self.active_index.as_ref() {
+            Some(active_index) => {
+                let mut indices = vec![];
+                let mut next_index = active_index.next(from_index, self.len);
+                while let Some(index) = next_index {
+                    indices.push(index);
+                    if indices.len() >= limit as usize {
+                        break;
+                    }
+                    next_index = active_index.next(index + 1, self.len);
+                }
+                indices
+            }
+            None => {
+                let mut indices: Vec<u32> = self
+                    .active_items
+                    .iter()
+                    .copied()
+                    .filter(|index| *index >= from_index)
+                    .collect();
+                indices.sort_unstable();
+                indices.truncate(limit as usize);
+                indices
+            }
+        }
     }
 
     pub fn update(&mut self, index: u32, item: T) {
         let active = item.is_active();
         if active && !self.active_items.contains(&index) {
             self.active_items.insert(index);
+            if let Some(active_index) = self.active_index.as_mut() {
+                active_index.insert(index);
+            }
         }
         if !active && self.active_items.contains(&index) {
             self.active_items.remove(&index);
+            if let Some(active_index) = self.active_index.as_mut() {
+                active_index.remove(index);
+            }
+        }
+
+        self.items.insert(index, item);
+    }
+
+    /// Remove an inactive item. Returns `None` if the item
+    /// doesn't exist or is still active.
+    pub fn remove(&mut self, index: u32) -> Option<T> {
+        if self.active_items.contains(&index) {
+            return None;
         }
+        self.items.remove(&index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{
+        env, store::Vector, test_utils::VMContextBuilder, testing_env, Gas, RuntimeFeesConfig,
+        VMConfig,
+    };
+
+    use super::*;
+
+    #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+    struct Item {
+        value: u32,
+        active: bool,
+    }
+
+    impl Active for Item {
+        fn is_active(&self) -> bool {
+            self.active
+        }
+    }
+
+    fn item(value: u32, active: bool) -> Item {
+        Item { value, active }
+    }
+
+    /// Layout of `ActiveVector` before removal was supported
+    #[derive(BorshSerialize, BorshDeserialize)]
+    struct LegacyActiveVector {
+        items: Vector<Item>,
+        active_items: UnorderedSet<u32>,
+    }
+
+    #[test]
+    fn test_read_legacy_layout() {
+        let mut legacy = LegacyActiveVector {
+            items: Vector::new(b"v".to_vec()),
+            active_items: UnorderedSet::new(b"v_active".to_vec()),
+        };
+        legacy.items.push(item(0, false));
+        legacy.items.push(item(1, true));
+        legacy.active_items.insert(1);
+        legacy.items.flush();
+        legacy.active_items.flush();
+
+        let bytes = legacy.try_to_vec().unwrap();
+        let vector = ActiveVector::<Item>::try_from_slice(&bytes).unwrap();
+
+        assert_eq!(vector.len(), 2);
+        assert_eq!(vector.get(0), Some(&item(0, false)));
+        assert_eq!(vector.get(1), Some(&item(1, true)));
+        assert_eq!(vector.get_active_item_indices(0, 10), vec![1]);
+
+        // legacy vectors keep working after being indexed
+        let mut vector = vector;
+        vector.build_index(b"v".to_vec());
+        vector.append(item(2, true));
+        vector.update(1, item(1, false));
+        assert_eq!(vector.get_active_item_indices(0, 10), vec![2]);
+
+        let bytes = vector.try_to_vec().unwrap();
+        drop(vector);
+        let vector = ActiveVector::<Item>::try_from_slice(&bytes).unwrap();
+        assert!(vector.active_index.is_some());
+        assert_eq!(vector.get_active_item_indices(0, 10), vec![2]);
+    }
+
+    #[test]
+    fn test_remove_inactive_item() {
+        let mut vector = ActiveVector::new(b"v".to_vec());
+        vector.append(item(0, false));
+        vector.append(item(1, true));
+        vector.append(item(2, false));
+
+        // active item cannot be removed
+        assert_eq!(vector.remove(1), None);
+        assert_eq!(vector.remove(0), Some(item(0, false)));
+        assert_eq!(vector.remove(0), None);
+
+        // indices of other items are kept
+        assert_eq!(vector.len(), 3);
+        assert_eq!(vector.get_items(0, 2), vec![&item(1, true)]);
+        assert_eq!(
+            vector.get_items(0, 3),
+            vec![&item(1, true), &item(2, false)]
+        );
+        assert_eq!(vector.append(item(3, true)), 3);
+    }
+
+    /// Building large sets costs more gas than a single call could burn
+    fn setup_free_gas() {
+        testing_env!(
+            VMContextBuilder::new().build(),
+            VMConfig::free(),
+            RuntimeFeesConfig::free()
+        );
+    }
+
+    fn collect_pages(vector: &ActiveVector<Item>, page_size: u32) -> Vec<u32> {
+        let mut all = vec![];
+        let mut from_index = 0;
+        loop {
+            let page = vector.get_active_item_indices(from_index, page_size);
+            match page.last() {
+                Some(last) => {
+                    from_index = last + 1;
+                    all.extend(page);
+                }
+                None => return all,
+            }
+        }
+    }
+
+    #[test]
+    fn test_paginate_large_active_set() {
+        setup_free_gas();
+        let mut vector = ActiveVector::new(b"v".to_vec());
+        for i in 0..1000 {
+            vector.append(item(i, i % 3 != 0));
+        }
+        let expected: Vec<u32> = (0..1000).filter(|i| i % 3 != 0).collect();
+
+        assert_eq!(collect_pages(&vector, 1), expected);
+        assert_eq!(collect_pages(&vector, 7), expected);
+        assert_eq!(collect_pages(&vector, 1000), expected);
+        assert_eq!(vector.get_active_item_indices(998, 10), vec![998]);
+        assert_eq!(vector.get_active_item_indices(1000, 10), Vec::<u32>::new());
+        assert_eq!(vector.get_active_item_indices(0, 0), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_paginate_sparse_active_set() {
+        setup_free_gas();
+        let mut vector = ActiveVector::new(b"v".to_vec());
+        for i in 0..1000 {
+            vector.append(item(i, i % 100 == 0));
+        }
+        // deactivating and activating items changes the order inside
+        // the underlying set, but pages are always sorted by index
+        vector.update(0, item(0, false));
+        vector.update(500, item(500, false));
+        vector.update(1, item(1, true));
+        vector.update(999, item(999, true));
+
+        let expected = vec![1, 100, 200, 300, 400, 600, 700, 800, 900, 999];
+        assert_eq!(collect_pages(&vector, 3), expected);
+        assert_eq!(vector.get_active_item_indices(150, 2), vec![200, 300]);
+    }
+
+    #[test]
+    fn test_active_index_across_levels() {
+        let mut index = ActiveIndex::new(b"i".to_vec());
+        let len = 40_000_000;
+        for i in [3, 63, 64, 70_000, 20_000_000, 39_999_999] {
+            index.insert(i);
+        }
+
+        assert_eq!(index.next(0, len), Some(3));
+        assert_eq!(index.next(4, len), Some(63));
+        assert_eq!(index.next(65, len), Some(70_000));
+        assert_eq!(index.next(70_001, len), Some(20_000_000));
+        assert_eq!(index.next(20_000_001, len), Some(39_999_999));
+        assert_eq!(index.next(39_999_999, 39_999_999), None);
+
+        index.remove(70_000);
+        index.remove(20_000_000);
+        assert_eq!(index.next(65, len), Some(39_999_999));
+        index.remove(39_999_999);
+        assert_eq!(index.next(65, len), None);
+        assert_eq!(index.next(0, len), Some(3));
+    }
+
+    #[test]
+    fn test_paginate_active_items_at_the_end() {
+        setup_free_gas();
+        let mut vector = ActiveVector::new(b"v".to_vec());
+        for i in 0..5000 {
+            vector.append(item(i, i >= 4400));
+        }
+        // write everything to storage, so reads are not served from cache
+        let bytes = vector.try_to_vec().unwrap();
+        drop(vector);
+        let vector = ActiveVector::<Item>::try_from_slice(&bytes).unwrap();
+
+        testing_env!(VMContextBuilder::new().build());
+        let indices = vector.get_active_item_indices(0, 10);
+        assert_eq!(indices, (4400..4410).collect::<Vec<_>>());
+        // only a few words of the index are read to skip the inactive items
+        assert!(env::used_gas() < Gas::ONE_TERA * 5);
 
-        self.items.replace(index, item);
+        assert_eq!(collect_pages(&vector, 7), (4400..5000).collect::<Vec<_>>());
+        assert_eq!(vector.get_active_item_indices(4999, 10), vec![4999]);
     }
 }
@@ -1,5 +1,3 @@
-use std::convert::TryInto;
-
 use crate::{
     active_vector::{Active, ActiveVector},
     types::{Duration, StorageKey, Timestamp},
@@ -10,7 +8,7 @@ use near_sdk::{
     collections::LookupMap,
     json_types::U128,
     near_bindgen, require,
-    serde::Serialize,
+    serde::{Deserialize, Serialize},
     AccountId, Balance, BlockHeight, PanicOnDefault,
 };
 
@@ -19,7 +17,7 @@ pub const ERR_BOND_WRONG_STATE_TO_CANCEL: &str = "Bond in wrong state to cancel"
 pub const ERR_BOND_WRONG_STATE_TO_COMMIT: &str = "Bond in wrong state to commit";
 pub const ERR_WRONG_TIMESTAMP: &str = "Wrong timestamp when computing note length";
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, PartialEq, Eq)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(crate = "near_sdk::serde")]
 pub enum BondStatus {
     Pending,
@@ -27,7 +25,10 @@ pub enum BondStatus {
     Cancelled,
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Clone)]
+/// NOTE: `referrer_id` is appended to the layout of notes stored before referral
+/// is supported, and is read as `None` from those notes. This relies on each note
+/// being a storage value of its own, so that nothing follows a legacy note.
+#[derive(BorshSerialize, Clone)]
 pub struct BondNote {
     id: u32,
     account_id: AccountId,
@@ -38,6 +39,29 @@ pub struct BondNote {
     settled_at: Timestamp,
     settled_block_height: BlockHeight,
     status: BondStatus,
+    /// account who referred the user to create this note
+    referrer_id: Option<AccountId>,
+}
+
+impl BorshDeserialize for BondNote {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self {
+            id: BorshDeserialize::deserialize(buf)?,
+            account_id: BorshDeserialize::deserialize(buf)?,
+            bond_amount: BorshDeserialize::deserialize(buf)?,
+            committed_pnear_amount: BorshDeserialize::deserialize(buf)?,
+            created_at: BorshDeserialize::deserialize(buf)?,
+            created_block_height: BorshDeserialize::deserialize(buf)?,
+            settled_at: BorshDeserialize::deserialize(buf)?,
+            settled_block_height: BorshDeserialize::deserialize(buf)?,
+            status: BorshDeserialize::deserialize(buf)?,
+            referrer_id: if buf.is_empty() {
+                None
+            } else {
+                BorshDeserialize::deserialize(buf)?
+            },
+        })
+    }
 }
 
 impl BondNote {
@@ -49,10 +73,22 @@ impl BondNote {
         self.bond_amount
     }
 
+    pub fn committed_pnear_amount(&self) -> Balance {
+        self.committed_pnear_amount
+    }
+
     pub fn status(&self) -> BondStatus {
         self.status.clone()
     }
 
+    pub fn created_at(&self) -> Timestamp {
+        self.created_at
+    }
+
+    pub fn referrer_id(&self) -> Option<&AccountId> {
+        self.referrer_id.as_ref()
+    }
+
     pub fn length(&self, ts: Timestamp) -> Duration {
         require!(ts >= self.created_at, ERR_WRONG_TIMESTAMP);
         match self.status {
@@ -89,6 +125,38 @@ impl Active for BondNote {
     }
 }
 
+/// Totals of settled notes
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SettledNotes {
+    pub committed_count: u32,
+    #[serde(with = "u128_dec_format")]
+    pub committed_bond_amount: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub committed_pnear_amount: Balance,
+    pub cancelled_count: u32,
+    #[serde(with = "u128_dec_format")]
+    pub cancelled_bond_amount: Balance,
+}
+
+impl SettledNotes {
+    /// Add a settled note to the totals, pending notes are ignored
+    pub fn add(&mut self, note: &BondNote) {
+        match note.status {
+            BondStatus::Committed => {
+                self.committed_count += 1;
+                self.committed_bond_amount += note.bond_amount;
+                self.committed_pnear_amount += note.committed_pnear_amount;
+            }
+            BondStatus::Cancelled => {
+                self.cancelled_count += 1;
+                self.cancelled_bond_amount += note.bond_amount;
+            }
+            BondStatus::Pending => {}
+        }
+    }
+}
+
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct BondNotes {
     notes: LookupMap<AccountId, ActiveVector<BondNote>>,
@@ -121,25 +189,48 @@ impl BondNotes {
             .unwrap_or(0)
     }
 
-    pub fn get_user_pending_note_ids(&self, account_id: &AccountId) -> Vec<u32> {
+    /// Pending note ids in ascending order, starting from `from_index`
+    pub fn get_user_pending_note_ids(
+        &self,
+        account_id: &AccountId,
+        from_index: u32,
+        limit: u32,
+    ) -> Vec<u32> {
         self.notes
             .get(account_id)
-            .map(|v| v.get_active_item_indices())
+            .map(|v| v.get_active_item_indices(from_index, limit))
             .unwrap_or_default()
     }
 
-    pub fn get_user_note_ids(&self, account_id: &AccountId) -> Vec<u32> {
+    /// Notes with id in `[from_index, from_index + limit)` that are not pruned
+    pub fn get_user_notes(
+        &self,
+        account_id: &AccountId,
+        from_index: u32,
+        limit: u32,
+    ) -> Vec<BondNote> {
         self.notes
             .get(account_id)
-            .map(|v| v.get_item_indices())
+            .map(|v| {
+                v.get_items(from_index, limit)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            })
             .unwrap_or_default()
     }
 
-    pub fn insert_new_note(&mut self, account_id: &AccountId, bond_amount: Balance) -> BondNote {
+    pub fn insert_new_note(
+        &mut self,
+        account_id: &AccountId,
+        bond_amount: Balance,
+        referrer_id: Option<AccountId>,
+    ) -> BondNote {
         let mut user_notes = self
             .notes
             .get(account_id)
             .unwrap_or_else(|| ActiveVector::new(StorageKey::UserNotes(account_id.clone())));
+        user_notes.build_index(StorageKey::UserNotes(account_id.clone()));
 
         let note_id = user_notes.len();
         let note = BondNote {
@@ -152,6 +243,7 @@ impl BondNotes {
             settled_at: 0,
             settled_block_height: 0,
             status: BondStatus::Pending,
+            referrer_id,
         };
 
         user_notes.append(note.clone());
@@ -160,8 +252,15 @@ impl BondNotes {
         note
     }
 
+    /// Remove a settled note from storage
+    pub fn remove_user_note(&mut self, account_id: &AccountId, note_id: u32) -> Option<BondNote> {
+        let mut user_notes = self.notes.get(account_id)?;
+        user_notes.remove(note_id)
+    }
+
     pub fn save_user_note(&mut self, account_id: &AccountId, note_id: u32, bond_note: BondNote) {
         let mut user_notes = self.notes.get(account_id).unwrap();
+        user_notes.build_index(StorageKey::UserNotes(account_id.clone()));
         user_notes.update(note_id, bond_note);
         self.notes.insert(account_id, &user_notes);
     }
@@ -169,25 +268,26 @@ impl BondNotes {
 
 // === contract view functions for bond note
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct BondNoteInfo {
-    id: u32,
-    account_id: AccountId,
+    pub id: u32,
+    pub account_id: AccountId,
     #[serde(with = "u128_dec_format")]
-    bond_amount: Balance,
+    pub bond_amount: Balance,
     #[serde(with = "u128_dec_format")]
-    committed_pnear_amount: Balance,
-    created_at: Timestamp,
-    created_block_height: BlockHeight,
-    settled_at: Timestamp,
-    settled_block_height: BlockHeight,
-    status: BondStatus,
+    pub committed_pnear_amount: Balance,
+    pub created_at: Timestamp,
+    pub created_block_height: BlockHeight,
+    pub settled_at: Timestamp,
+    pub settled_block_height: BlockHeight,
+    pub status: BondStatus,
+    pub referrer_id: Option<AccountId>,
 
     #[serde(with = "u128_dec_format")]
-    cap: Balance,
+    pub cap: Balance,
     #[serde(with = "u128_dec_format")]
-    accrued_pnear: Balance,
+    pub accrued_pnear: Balance,
 }
 
 impl PhoenixBonds {
@@ -202,6 +302,7 @@ impl PhoenixBonds {
             settled_at: note.settled_at,
             settled_block_height: note.settled_block_height,
             status: note.status.clone(),
+            referrer_id: note.referrer_id.clone(),
             cap: self.note_cap(note, linear_price),
             accrued_pnear: self.note_accrued_pnear(note, linear_price, current_timestamp_ms()),
         }
@@ -228,23 +329,27 @@ impl PhoenixBonds {
         self.bond_notes.user_pending_note_len(&account_id)
     }
 
+    /// List pending notes ordered by note id, starting from note `from_index`.
+    /// To get the next page, use the last note id plus one as `from_index`.
     pub fn list_pending_notes(
         &self,
         account_id: AccountId,
         linear_price: U128,
-        offset: u32,
+        from_index: u32,
         limit: u32,
     ) -> Vec<BondNoteInfo> {
         self.bond_notes
-            .get_user_pending_note_ids(&account_id)
+            .get_user_pending_note_ids(&account_id, from_index, limit)
             .iter()
-            .skip(offset.try_into().unwrap())
-            .take(limit.try_into().unwrap())
             .map(|id| self.bond_notes.get_user_note(&account_id, *id))
             .map(|note| self.build_note_info(&note, linear_price.0))
             .collect()
     }
 
+    /// List notes of a user by note id, starting from `offset`.
+    /// Pruned notes are skipped, so a page may contain less than `limit` notes
+    /// even if there are more notes after it. Use `notes_count()` to
+    /// know when to stop.
     pub fn list_notes(
         &self,
         account_id: AccountId,
@@ -253,12 +358,9 @@ impl PhoenixBonds {
         limit: u32,
     ) -> Vec<BondNoteInfo> {
         self.bond_notes
-            .get_user_note_ids(&account_id)
+            .get_user_notes(&account_id, offset, limit)
             .iter()
-            .skip(offset.try_into().unwrap())
-            .take(limit.try_into().unwrap())
-            .map(|id| self.bond_notes.get_user_note(&account_id, *id))
-            .map(|note| self.build_note_info(&note, linear_price.0))
+            .map(|note| self.build_note_info(note, linear_price.0))
             .collect()
     }
 }
@@ -266,6 +368,7 @@ impl PhoenixBonds {
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use near_sdk::IntoStorageKey;
 
     pub fn new_note(bond_amount: Balance, created_at: Timestamp) -> BondNote {
         BondNote {
@@ -278,6 +381,50 @@ pub mod tests {
             settled_at: 0,
             settled_block_height: 0,
             status: BondStatus::Pending,
+            referrer_id: None,
         }
     }
+
+    #[test]
+    fn test_read_note_without_referrer() {
+        let mut note = new_note(100, 1);
+        let mut bytes = note.try_to_vec().unwrap();
+        // notes stored before referral is supported don't have the last field
+        assert_eq!(bytes.pop(), Some(0));
+        let legacy = BondNote::try_from_slice(&bytes).unwrap();
+        assert_eq!(legacy.bond_amount(), 100);
+        assert!(legacy.referrer_id().is_none());
+
+        note.referrer_id = Some(AccountId::new_unchecked("bar".into()));
+        let bytes = note.try_to_vec().unwrap();
+        let note = BondNote::try_from_slice(&bytes).unwrap();
+        assert_eq!(note.referrer_id().unwrap().as_str(), "bar");
+    }
+
+    #[test]
+    fn test_legacy_note_stored_as_own_value() {
+        let account_id = AccountId::new_unchecked("foo".into());
+        let prefix = StorageKey::UserNotes(account_id.clone()).into_storage_key();
+        let mut notes = ActiveVector::new(prefix.clone());
+        let mut note = new_note(100, 1);
+        note.referrer_id = Some(AccountId::new_unchecked("bar".into()));
+        notes.append(note.clone());
+        notes.append(note);
+        // flush notes to storage
+        let notes_bytes = notes.try_to_vec().unwrap();
+        drop(notes);
+
+        // rewrite the first note in the layout before referral is supported
+        let mut legacy_bytes = new_note(200, 1).try_to_vec().unwrap();
+        legacy_bytes.pop();
+        let key = [prefix, 0u32.try_to_vec().unwrap()].concat();
+        assert!(env::storage_has_key(&key));
+        env::storage_write(&key, &legacy_bytes);
+
+        let notes = ActiveVector::<BondNote>::try_from_slice(&notes_bytes).unwrap();
+        let legacy = notes.get(0).unwrap();
+        assert_eq!(legacy.bond_amount(), 200);
+        assert!(legacy.referrer_id().is_none());
+        assert_eq!(notes.get(1).unwrap().referrer_id().unwrap().as_str(), "bar");
+    }
 }
@@ -0,0 +1,122 @@
+use crate::*;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    near_bindgen,
+    serde::{Deserialize, Serialize},
+};
+
+const ERR_INVALID_DYNAMIC_TAU: &str = "Invalid dynamic tau";
+
+/// Policy that adjusts tau by how far treasury pool is from its target.
+/// Tau is `max_tau` when treasury pool is empty, and goes down linearly
+/// to `min_tau` as treasury pool approaches the target.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DynamicTau {
+    /// target of treasury pool NEAR amount
+    #[serde(with = "u128_dec_format")]
+    pub treasury_target: Balance,
+    pub min_tau: BasisPoint,
+    pub max_tau: BasisPoint,
+}
+
+impl DynamicTau {
+    fn assert_valid(&self) {
+        require!(
+            self.treasury_target > 0
+                && self.min_tau <= self.max_tau
+                && self.max_tau < FULL_BASIS_POINT,
+            ERR_INVALID_DYNAMIC_TAU
+        );
+    }
+
+    /// Tau given current treasury pool NEAR amount
+    pub fn tau(&self, treasury_pool_near_amount: Balance) -> BasisPoint {
+        if treasury_pool_near_amount >= self.treasury_target {
+            return self.min_tau;
+        }
+        // treasury pool is below target here, which keeps the product within u128
+        let reduction = u128::from(self.max_tau - self.min_tau) * treasury_pool_near_amount
+            / self.treasury_target;
+        self.max_tau - reduction as BasisPoint
+    }
+}
+
+impl PhoenixBonds {
+    /// Tau applied to bond notes committed now.
+    /// Static tau is used unless a dynamic tau policy is set.
+    pub(crate) fn current_tau(&self) -> BasisPoint {
+        match &self.dynamic_tau {
+            Some(policy) => policy.tau(self.treasury_pool_near_amount),
+            None => self.tau,
+        }
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// Set dynamic tau policy, or pass `None` to use static tau again
+    #[payable]
+    pub fn set_dynamic_tau(&mut self, policy: Option<DynamicTau>) {
+        self.assert_owner_with_one_yocto();
+        if let Some(policy) = &policy {
+            policy.assert_valid();
+        }
+
+        Event::SetDynamicTau {
+            old_policy: self.dynamic_tau.clone(),
+            new_policy: policy.clone(),
+        }
+        .emit();
+
+        self.dynamic_tau = policy;
+    }
+
+    pub fn get_dynamic_tau(&self) -> Option<DynamicTau> {
+        self.dynamic_tau.clone()
+    }
+
+    /// Tau that would be applied if a bond note is committed now
+    pub fn get_current_tau(&self) -> BasisPoint {
+        self.current_tau()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::ONE_NEAR;
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    fn policy() -> DynamicTau {
+        DynamicTau {
+            treasury_target: 1000 * ONE_NEAR,
+            min_tau: 100,
+            max_tau: 500,
+        }
+    }
+
+    #[test]
+    fn test_dynamic_tau() {
+        let policy = policy();
+        assert_eq!(policy.tau(0), 500);
+        assert_eq!(policy.tau(250 * ONE_NEAR), 400);
+        assert_eq!(policy.tau(999 * ONE_NEAR), 101);
+        assert_eq!(policy.tau(1000 * ONE_NEAR), 100);
+        assert_eq!(policy.tau(5000 * ONE_NEAR), 100);
+    }
+
+    #[test]
+    fn test_current_tau() {
+        let mut contract = new_contract(0, 0, 0, 500 * ONE_NEAR, 1, 300);
+        assert_eq!(contract.current_tau(), 300);
+
+        contract.dynamic_tau = Some(policy());
+        assert_eq!(contract.current_tau(), 300);
+
+        contract.treasury_pool_near_amount = 750 * ONE_NEAR;
+        assert_eq!(contract.current_tau(), 200);
+    }
+}
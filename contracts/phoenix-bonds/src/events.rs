@@ -1,9 +1,18 @@
-use near_sdk::{json_types::U128, log, AccountId};
+use near_sdk::{json_types::U128, log, AccountId, Balance, EpochHeight};
+
+use crate::{
+    dynamic_tau::DynamicTau,
+    lost_found::Asset,
+    reconcile::SurplusTarget,
+    types::{BasisPoint, Duration, Timestamp},
+    utils::current_timestamp_ms,
+    PhoenixBonds,
+};
 use serde::Serialize;
 use serde_json::json;
 
 const EVENT_STANDARD: &str = "phoenix_bonds";
-const EVENT_STANDARD_VERSION: &str = "1.0.0";
+const EVENT_STANDARD_VERSION: &str = "1.7.0";
 
 #[derive(Serialize, Debug, Clone)]
 #[serde(tag = "event", content = "data")]
@@ -16,31 +25,93 @@ pub enum Event {
         note_id: u32,
         bond_amount: U128,
         linear_amount: U128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        referrer_id: Option<AccountId>,
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+        pool_state: Option<PoolState>,
     },
     Cancel {
         account_id: AccountId,
         note_id: u32,
         bond_amount: U128,
         refund_linear: U128,
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+        pool_state: Option<PoolState>,
     },
     Commit {
         account_id: AccountId,
         note_id: u32,
         bond_amount: U128,
         pnear_amount: U128,
+        /// tau applied to the bond note
+        tau: BasisPoint,
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+        pool_state: Option<PoolState>,
     },
     Redeem {
         account_id: AccountId,
         pnear_amount: U128,
         redeemed_linear: U128,
+        /// redeem fee that stays in the reserve pool
+        fee_linear: U128,
+        #[serde(flatten, skip_serializing_if = "Option::is_none")]
+        pool_state: Option<PoolState>,
+    },
+    Unstake {
+        account_id: AccountId,
+        linear_amount: U128,
+        near_amount: U128,
+        /// epoch since which the NEAR could be withdrawn
+        unlock_epoch: EpochHeight,
+    },
+    InstantUnstake {
+        account_id: AccountId,
+        linear_amount: U128,
+        near_amount: U128,
+    },
+    UnstakeWithdrawn {
+        account_id: AccountId,
+        near_amount: U128,
+    },
+    /// unstaked NEAR withdrawn from LiNEAR to this contract
+    UnstakeReleased {
+        near_amount: U128,
+    },
+    BondFailed {
+        account_id: AccountId,
+        amount: U128,
+        reason: String,
+    },
+    // referral events
+    ReferralReward {
+        referrer_id: AccountId,
+        account_id: AccountId,
+        note_id: u32,
+        near_amount: U128,
+        linear_amount: U128,
+    },
+    ReferralRewardsClaimed {
+        referrer_id: AccountId,
+        linear_amount: U128,
+    },
+    // note storage events
+    NotesPruned {
+        account_id: AccountId,
+        note_ids: Vec<u32>,
+    },
+    NoteStorageWithdrawn {
+        account_id: AccountId,
+        amount: U128,
     },
     // lost and found events
     LostFoundInsert {
         account_id: AccountId,
+        asset: Asset,
         amount: U128,
     },
     LostFoundClaim {
         account_id: AccountId,
+        asset: Asset,
         amount: U128,
     },
     // treasury withdraw event
@@ -48,6 +119,133 @@ pub enum Event {
         near_amount: U128,
         linear_amount: U128,
     },
+    // reconcile event
+    LinearBalanceReconciled {
+        recorded_linear: U128,
+        actual_linear: U128,
+        surplus_linear: U128,
+        deficit_linear: U128,
+        moved_to: Option<SurplusTarget>,
+    },
+    // flash loan events
+    FlashLoan {
+        receiver_id: AccountId,
+        amount: U128,
+        fee: U128,
+    },
+    FlashLoanDefault {
+        receiver_id: AccountId,
+        amount: U128,
+        fee: U128,
+        returned: U128,
+    },
+    // owner events
+    ChangeOwner {
+        old_owner_id: AccountId,
+        new_owner_id: AccountId,
+    },
+    SetTau {
+        old_tau: BasisPoint,
+        new_tau: BasisPoint,
+    },
+    SetDynamicTau {
+        old_policy: Option<DynamicTau>,
+        new_policy: Option<DynamicTau>,
+    },
+    Pause {},
+    Resume {},
+    SetSnapshotInterval {
+        old_interval: Duration,
+        new_interval: Duration,
+    },
+    AddFlashLoanReceiver {
+        account_id: AccountId,
+    },
+    RemoveFlashLoanReceiver {
+        account_id: AccountId,
+    },
+    SetFlashLoanFee {
+        old_fee: BasisPoint,
+        new_fee: BasisPoint,
+    },
+    SetRedeemFee {
+        old_fee: BasisPoint,
+        new_fee: BasisPoint,
+        old_decay_period: Duration,
+        new_decay_period: Duration,
+    },
+    SetWnearAddress {
+        old_address: Option<AccountId>,
+        new_address: Option<AccountId>,
+    },
+    SetReferralRewardRate {
+        old_rate: BasisPoint,
+        new_rate: BasisPoint,
+    },
+    LostFoundIndexed {
+        asset: Asset,
+        account_ids: Vec<AccountId>,
+        /// lost and found of all indexed accounts after indexing
+        indexed_amount: U128,
+        total_amount: U128,
+    },
+    BondNotesIndexed {
+        notes: Vec<(AccountId, u32)>,
+        /// registered notes after indexing, including pruned legacy notes
+        created_count: u64,
+        bonders_count: u32,
+    },
+    // upgrade events
+    Upgrade {
+        old_version: String,
+    },
+    Migrate {
+        new_version: String,
+    },
+    // accrual events
+    AlphaAdjusted {
+        old_alpha: Duration,
+        new_alpha: Duration,
+        old_exceeds_target_at: Timestamp,
+        new_exceeds_target_at: Timestamp,
+    },
+    /// weighted sum of mean length couldn't cover a removed bond, and was reset
+    /// to zero. Small drift is expected from rounding of the mean
+    MeanLengthDrift {
+        bond_amount: U128,
+        length: Duration,
+        drift: U128,
+    },
+}
+
+/// Pool state right after a user action, which allows
+/// rebuilding pNEAR price history from logs. Omitted from
+/// events that didn't capture it
+#[derive(Serialize, Debug, Clone)]
+pub struct PoolState {
+    /// LiNEAR price used in the action, not available when bonding with NEAR
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linear_price: Option<U128>,
+    pub linear_balance: U128,
+    pub pending_pool_near_amount: U128,
+    pub permanent_pool_near_amount: U128,
+    pub treasury_pool_near_amount: U128,
+    pub pnear_total_supply: U128,
+    pub alpha: Duration,
+}
+
+impl PhoenixBonds {
+    pub(crate) fn pool_state(&self, linear_price: Option<Balance>) -> PoolState {
+        PoolState {
+            linear_price: linear_price.map(U128),
+            linear_balance: self.linear_balance.into(),
+            pending_pool_near_amount: self.pending_pool_near_amount.into(),
+            permanent_pool_near_amount: self.permanent_pool_near_amount.into(),
+            treasury_pool_near_amount: self.treasury_pool_near_amount.into(),
+            pnear_total_supply: self.pnear_total_supply().into(),
+            alpha: self.accrual_param.current_alpha(current_timestamp_ms()),
+        }
+    }
 }
 
 impl Event {
@@ -80,11 +278,112 @@ mod tests {
             note_id: 1,
             bond_amount: U128(1000),
             linear_amount: U128(1000),
+            referrer_id: None,
+            pool_state: Some(PoolState {
+                linear_price: None,
+                linear_balance: U128(1000),
+                pending_pool_near_amount: U128(1000),
+                permanent_pool_near_amount: U128(0),
+                treasury_pool_near_amount: U128(0),
+                pnear_total_supply: U128(0),
+                alpha: 8,
+            }),
+        }
+        .emit();
+        assert_eq!(
+            test_utils::get_logs()[0],
+            r#"EVENT_JSON:{"standard":"phoenix_bonds","version":"1.7.0","event":"bond","data":[{"account_id":"alice","note_id":1,"bond_amount":"1000","linear_amount":"1000","linear_balance":"1000","pending_pool_near_amount":"1000","permanent_pool_near_amount":"0","treasury_pool_near_amount":"0","pnear_total_supply":"0","alpha":8}]}"#
+        );
+    }
+
+    #[test]
+    fn cancel_without_pool_state() {
+        Event::Cancel {
+            account_id: alice(),
+            note_id: 1,
+            bond_amount: U128(1000),
+            refund_linear: U128(990),
+            pool_state: None,
+        }
+        .emit();
+        assert_eq!(
+            test_utils::get_logs()[0],
+            r#"EVENT_JSON:{"standard":"phoenix_bonds","version":"1.7.0","event":"cancel","data":[{"account_id":"alice","note_id":1,"bond_amount":"1000","refund_linear":"990"}]}"#
+        );
+    }
+
+    #[test]
+    fn set_tau() {
+        Event::SetTau {
+            old_tau: 300,
+            new_tau: 500,
+        }
+        .emit();
+        assert_eq!(
+            test_utils::get_logs()[0],
+            r#"EVENT_JSON:{"standard":"phoenix_bonds","version":"1.7.0","event":"set_tau","data":[{"old_tau":300,"new_tau":500}]}"#
+        );
+    }
+
+    #[test]
+    fn mean_length_drift() {
+        Event::MeanLengthDrift {
+            bond_amount: U128(1000),
+            length: 86400000,
+            drift: U128(1),
+        }
+        .emit();
+        assert_eq!(
+            test_utils::get_logs()[0],
+            r#"EVENT_JSON:{"standard":"phoenix_bonds","version":"1.7.0","event":"mean_length_drift","data":[{"bond_amount":"1000","length":86400000,"drift":"1"}]}"#
+        );
+    }
+
+    #[test]
+    fn lost_found_indexed() {
+        Event::LostFoundIndexed {
+            asset: Asset::Near,
+            account_ids: vec![alice()],
+            indexed_amount: U128(100),
+            total_amount: U128(300),
+        }
+        .emit();
+        assert_eq!(
+            test_utils::get_logs()[0],
+            r#"EVENT_JSON:{"standard":"phoenix_bonds","version":"1.7.0","event":"lost_found_indexed","data":[{"asset":"near","account_ids":["alice"],"indexed_amount":"100","total_amount":"300"}]}"#
+        );
+    }
+
+    #[test]
+    fn pause() {
+        Event::Pause {}.emit();
+        assert_eq!(
+            test_utils::get_logs()[0],
+            r#"EVENT_JSON:{"standard":"phoenix_bonds","version":"1.7.0","event":"pause","data":[{}]}"#
+        );
+    }
+
+    #[test]
+    fn redeem_with_pool_state() {
+        Event::Redeem {
+            account_id: alice(),
+            pnear_amount: U128(1000),
+            redeemed_linear: U128(900),
+            fee_linear: U128(10),
+            pool_state: Some(PoolState {
+                linear_price: Some(U128(2)),
+                linear_balance: U128(3),
+                pending_pool_near_amount: U128(4),
+                permanent_pool_near_amount: U128(5),
+                treasury_pool_near_amount: U128(6),
+                pnear_total_supply: U128(7),
+                alpha: 8,
+            }),
         }
         .emit();
         assert_eq!(
             test_utils::get_logs()[0],
-            r#"EVENT_JSON:{"standard":"phoenix_bonds","version":"1.0.0","event":"bond","data":[{"account_id":"alice","note_id":1,"bond_amount":"1000","linear_amount":"1000"}]}"#
+            r#"EVENT_JSON:{"standard":"phoenix_bonds","version":"1.7.0","event":"redeem","data":[{"account_id":"alice","pnear_amount":"1000","redeemed_linear":"900","fee_linear":"10","linear_price":"2","linear_balance":"3","pending_pool_near_amount":"4","permanent_pool_near_amount":"5","treasury_pool_near_amount":"6","pnear_total_supply":"7","alpha":8}]}"#
         );
     }
 }
@@ -0,0 +1,184 @@
+use crate::{unstake::UNSTAKE_TICKET_STORAGE_DEPOSIT, *};
+use near_sdk::{
+    near_bindgen,
+    serde::{Deserialize, Serialize},
+    Gas, PromiseOrValue,
+};
+
+const ERR_NO_MIN_AMOUNT_OUT: &str = "min_amount_out is required for instant NEAR exit";
+const ERR_UNSTAKE_TICKET_DEPOSIT: &str =
+    "Requires attached deposit of exactly 0.002 NEAR for delayed NEAR exit";
+
+/// How LiNEAR is paid out to users on cancel and redeem
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Exit {
+    /// transfer LiNEAR to the user
+    Linear,
+    /// unstake LiNEAR for NEAR, which can be withdrawn with `withdraw_unstaked`
+    /// once LiNEAR releases it. Requires a deposit of 0.002 NEAR for the storage
+    /// of the unstake ticket, which is refunded on withdraw. Not available while
+    /// NEAR unstaked in an earlier epoch is still locked in LiNEAR
+    DelayedNear,
+    /// swap LiNEAR for NEAR in LiNEAR's liquidity pool, which charges a fee
+    InstantNear,
+}
+
+pub(crate) fn assert_exit(exit: Option<Exit>, min_amount_out: Option<U128>) {
+    if exit == Some(Exit::InstantNear) {
+        require!(min_amount_out.is_some(), ERR_NO_MIN_AMOUNT_OUT);
+    }
+}
+
+/// `delayed_near` pays for the storage of an unstake ticket,
+/// the other exits require 1 yoctoNEAR
+pub(crate) fn assert_exit_deposit(exit: Option<Exit>) {
+    if exit == Some(Exit::DelayedNear) {
+        require!(
+            env::attached_deposit() == UNSTAKE_TICKET_STORAGE_DEPOSIT,
+            ERR_UNSTAKE_TICKET_DEPOSIT
+        );
+    } else {
+        assert_one_yocto();
+    }
+}
+
+/// Gas on top of `GAS_FT_TRANSFER_AND_CALLBACK` to pay out in the way of `exit`,
+/// since `delayed_near` may release NEAR unstaked in an earlier epoch first
+pub(crate) fn extra_exit_gas(exit: Option<Exit>) -> Gas {
+    if exit == Some(Exit::DelayedNear) {
+        GAS_LINEAR_WITHDRAW
+    } else {
+        Gas(0)
+    }
+}
+
+impl PhoenixBonds {
+    /// Pay out LiNEAR owned by `user_id` in the way of `exit`, LiNEAR by default.
+    /// Each way takes no more gas than `GAS_FT_TRANSFER_AND_CALLBACK + extra_exit_gas(exit)`.
+    /// NOTE: Make sure LiNEAR balance is decreased before calling this!
+    pub(crate) fn exit_linear(
+        &mut self,
+        user_id: &AccountId,
+        linear_amount: Balance,
+        linear_price: Balance,
+        exit: Option<Exit>,
+        min_amount_out: Option<U128>,
+        memo: &str,
+    ) -> Promise {
+        match exit.unwrap_or(Exit::Linear) {
+            Exit::Linear => self.transfer_linear(user_id, linear_amount, memo),
+            Exit::DelayedNear => self.unstake_linear(user_id, linear_amount, linear_price),
+            Exit::InstantNear => self.instant_unstake_linear(
+                user_id,
+                linear_amount,
+                min_amount_out.expect(ERR_NO_MIN_AMOUNT_OUT),
+            ),
+        }
+    }
+
+    /// Swap LiNEAR owned by `user_id` for NEAR and transfer NEAR to the user.
+    /// If the swap failed, these LiNEAR will be moved to lost and found.
+    /// NOTE: Make sure LiNEAR balance is decreased before calling this!
+    fn instant_unstake_linear(
+        &mut self,
+        user_id: &AccountId,
+        linear_amount: Balance,
+        min_amount_out: U128,
+    ) -> Promise {
+        require!(linear_amount > 0, ERR_INVALID_TRANSFER_AMOUNT);
+        self.on_linear_out(linear_amount);
+
+        linear_contract::ext(self.linear_address.clone())
+            .with_static_gas(GAS_INSTANT_UNSTAKE)
+            .instant_unstake(linear_amount.into(), min_amount_out)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_INSTANT_UNSTAKE_CALLBACK)
+                    .on_linear_instant_unstaked(user_id.clone(), linear_amount.into()),
+            )
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// NEAR received from the liquidity pool is transferred to the user.
+    /// If the swap failed, LiNEAR will be moved to the lost and found pool.
+    /// Returns the amount of NEAR that was transferred.
+    #[private]
+    pub fn on_linear_instant_unstaked(
+        &mut self,
+        user_id: AccountId,
+        linear_amount: U128,
+        #[callback_result] near_amount: Result<U128, PromiseError>,
+    ) -> PromiseOrValue<U128> {
+        self.on_linear_out_resolved(linear_amount.0);
+        let near_amount = match near_amount {
+            Ok(near_amount) => near_amount,
+            Err(_) => {
+                self.lost_and_found
+                    .insert(Asset::Linear, &user_id, linear_amount.0);
+                return PromiseOrValue::Value(0.into());
+            }
+        };
+
+        Event::InstantUnstake {
+            account_id: user_id.clone(),
+            linear_amount,
+            near_amount,
+        }
+        .emit();
+
+        // LiNEAR sends NEAR before resolving the swap, so it's already here
+        self.transfer_near(&user_id, near_amount.0).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    #[test]
+    fn test_exit_from_json() {
+        let exit: Exit = serde_json::from_str("\"instant_near\"").unwrap();
+        assert_eq!(exit, Exit::InstantNear);
+        let exit: Exit = serde_json::from_str("\"delayed_near\"").unwrap();
+        assert_eq!(exit, Exit::DelayedNear);
+    }
+
+    #[test]
+    fn test_exit_with_min_amount_out() {
+        assert_exit(None, None);
+        assert_exit(Some(Exit::DelayedNear), None);
+        assert_exit(Some(Exit::InstantNear), Some(U128(0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "min_amount_out is required for instant NEAR exit")]
+    fn test_instant_near_requires_min_amount_out() {
+        assert_exit(Some(Exit::InstantNear), None);
+    }
+
+    #[test]
+    fn test_exit_deposit() {
+        testing_env!(VMContextBuilder::new().attached_deposit(1).build());
+        assert_exit_deposit(None);
+        assert_exit_deposit(Some(Exit::InstantNear));
+
+        testing_env!(VMContextBuilder::new()
+            .attached_deposit(UNSTAKE_TICKET_STORAGE_DEPOSIT)
+            .build());
+        assert_exit_deposit(Some(Exit::DelayedNear));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Requires attached deposit of exactly 0.002 NEAR for delayed NEAR exit"
+    )]
+    fn test_delayed_near_requires_ticket_deposit() {
+        testing_env!(VMContextBuilder::new().attached_deposit(1).build());
+        assert_exit_deposit(Some(Exit::DelayedNear));
+    }
+}
@@ -0,0 +1,359 @@
+use crate::*;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    near_bindgen,
+    serde::Serialize,
+    store::UnorderedSet,
+    PanicOnDefault,
+};
+
+const DEFAULT_FLASH_LOAN_FEE: BasisPoint = 10; // 0.1%
+
+const ERR_FLASH_LOAN_IN_PROGRESS: &str = "A flash loan is in progress. Please try again later";
+const ERR_NO_FLASH_LOAN: &str = "No flash loan to repay";
+const ERR_NOT_FLASH_LOAN_RECEIVER: &str = "Not an approved flash loan receiver";
+const ERR_WRONG_REPAY_ACCOUNT: &str = "Flash loan must be repaid by its receiver";
+const ERR_BAD_FLASH_LOAN_AMOUNT: &str = "Invalid flash loan amount";
+const ERR_INVALID_FLASH_LOAN_FEE: &str = "Invalid flash loan fee";
+
+/// A flash loan that hasn't been resolved
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FlashLoan {
+    receiver_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    amount: Balance,
+    #[serde(with = "u128_dec_format")]
+    fee: Balance,
+    /// LiNEAR repaid by the receiver so far
+    #[serde(with = "u128_dec_format")]
+    repaid: Balance,
+}
+
+/// Message passed to the receiver with the borrowed LiNEAR
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FlashLoanMessage {
+    amount: U128,
+    fee: U128,
+    msg: String,
+}
+
+/// Since a LiNEAR transfer could not be reverted once the receiver gets it,
+/// flash loans are only available to receivers approved by the owner.
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct FlashLoans {
+    fee: BasisPoint,
+    receivers: UnorderedSet<AccountId>,
+    /// the open flash loan, which locks actions that move LiNEAR
+    current: Option<FlashLoan>,
+}
+
+impl FlashLoans {
+    pub fn new() -> Self {
+        Self {
+            fee: DEFAULT_FLASH_LOAN_FEE,
+            receivers: UnorderedSet::new(StorageKey::FlashLoanReceivers),
+            current: None,
+        }
+    }
+}
+
+impl PhoenixBonds {
+    pub(crate) fn assert_no_flash_loan(&self) {
+        require!(
+            self.flash_loans.current.is_none(),
+            ERR_FLASH_LOAN_IN_PROGRESS
+        );
+    }
+
+    /// Record LiNEAR repaid by the receiver of the open flash loan
+    pub(crate) fn internal_repay_flash_loan(&mut self, sender_id: &AccountId, amount: Balance) {
+        let loan = self
+            .flash_loans
+            .current
+            .as_mut()
+            .unwrap_or_else(|| env::panic_str(ERR_NO_FLASH_LOAN));
+        require!(loan.receiver_id == *sender_id, ERR_WRONG_REPAY_ACCOUNT);
+        loan.repaid += amount;
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// Borrow LiNEAR of the reserve pool. LiNEAR is sent to the caller with
+    /// `ft_transfer_call` and a `FlashLoanMessage`. Before its `ft_on_transfer`
+    /// resolves, the caller must send back `amount + fee` LiNEAR with
+    /// `ft_transfer_call` and message `"FlashLoanRepay"`, or return unused LiNEAR.
+    /// The fee goes to the reserve pool.
+    pub fn flash_loan(&mut self, amount: U128, msg: String) -> Promise {
+        // 210 Tgas
+        require!(
+            env::prepaid_gas()
+                >= GAS_FLASH_LOAN + GAS_GET_LINEAR_PRICE + GAS_FLASH_LOAN_PRICE_CALLBACK,
+            ERR_NOT_ENOUGH_GAS
+        );
+        require!(!self.paused, ERR_PAUSED);
+        self.assert_no_flash_loan();
+
+        let receiver_id = env::predecessor_account_id();
+        require!(
+            self.flash_loans.receivers.contains(&receiver_id),
+            ERR_NOT_FLASH_LOAN_RECEIVER
+        );
+        require!(amount.0 > 0, ERR_BAD_FLASH_LOAN_AMOUNT);
+
+        self.get_linear_price().then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FLASH_LOAN_PRICE_CALLBACK)
+                .on_get_linear_price_for_flash_loan(receiver_id, amount, msg),
+        )
+    }
+
+    /// Open the flash loan if the reserve pool has enough LiNEAR,
+    /// LiNEAR of the other pools is never lent.
+    #[private]
+    pub fn on_get_linear_price_for_flash_loan(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        msg: String,
+        #[callback_result] linear_price: Result<U128, PromiseError>,
+    ) -> Promise {
+        let linear_price = self.record_linear_price(linear_price);
+        require!(!self.paused, ERR_PAUSED);
+        self.assert_no_flash_loan();
+
+        let reserve_pool_linear_amount = near2linear(
+            self.reserve_pool_near_amount(linear_price.0),
+            linear_price.0,
+        );
+        require!(
+            amount.0 <= reserve_pool_linear_amount,
+            ERR_BAD_FLASH_LOAN_AMOUNT
+        );
+
+        let fee = apply_basis_point(amount.0, self.flash_loans.fee);
+        self.flash_loans.current = Some(FlashLoan {
+            receiver_id: receiver_id.clone(),
+            amount: amount.0,
+            fee,
+            repaid: 0,
+        });
+
+        let message = FlashLoanMessage {
+            amount,
+            fee: fee.into(),
+            msg,
+        };
+
+        ext_fungible_token::ext(self.linear_address.clone())
+            .with_static_gas(GAS_FLASH_LOAN_TRANSFER)
+            .with_attached_deposit(ONE_YOCTO)
+            .ft_transfer_call(
+                receiver_id,
+                amount,
+                Some("Flash loan".to_string()),
+                serde_json::to_string(&message).unwrap(),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FLASH_LOAN_CALLBACK)
+                    .on_flash_loan_transferred(),
+            )
+    }
+
+    /// Resolve the open flash loan. If less than `amount + fee` LiNEAR was returned,
+    /// the receiver is removed from approved receivers. If the principal was not
+    /// fully returned either, it's written off the LiNEAR balance, and the contract
+    /// is paused so that the owner can look into it.
+    /// Returns the amount of LiNEAR that was returned.
+    #[private]
+    pub fn on_flash_loan_transferred(
+        &mut self,
+        #[callback_result] used_amount: Result<U128, PromiseError>,
+    ) -> U128 {
+        let loan = self
+            .flash_loans
+            .current
+            .take()
+            .unwrap_or_else(|| env::panic_str(ERR_NO_FLASH_LOAN));
+
+        let used_amount = min(used_amount.map(|v| v.0).unwrap_or(0), loan.amount);
+        let returned = loan.amount - used_amount + loan.repaid;
+        // LiNEAR returned beyond the principal goes to the reserve pool
+        self.linear_balance += returned.saturating_sub(loan.amount);
+
+        if returned >= loan.amount + loan.fee {
+            Event::FlashLoan {
+                receiver_id: loan.receiver_id,
+                amount: loan.amount.into(),
+                fee: (returned - loan.amount).into(),
+            }
+            .emit();
+        } else {
+            self.flash_loans.receivers.remove(&loan.receiver_id);
+            Event::FlashLoanDefault {
+                receiver_id: loan.receiver_id.clone(),
+                amount: loan.amount.into(),
+                fee: loan.fee.into(),
+                returned: returned.into(),
+            }
+            .emit();
+            Event::RemoveFlashLoanReceiver {
+                account_id: loan.receiver_id,
+            }
+            .emit();
+
+            // only a lost principal affects other users
+            if returned < loan.amount {
+                self.linear_balance = self.linear_balance.saturating_sub(loan.amount - returned);
+                self.paused = true;
+                Event::Pause {}.emit();
+            }
+        }
+
+        returned.into()
+    }
+
+    #[payable]
+    pub fn add_flash_loan_receiver(&mut self, account_id: AccountId) {
+        self.assert_owner_with_one_yocto();
+        self.flash_loans.receivers.insert(account_id.clone());
+
+        Event::AddFlashLoanReceiver { account_id }.emit();
+    }
+
+    #[payable]
+    pub fn remove_flash_loan_receiver(&mut self, account_id: AccountId) {
+        self.assert_owner_with_one_yocto();
+        self.flash_loans.receivers.remove(&account_id);
+
+        Event::RemoveFlashLoanReceiver { account_id }.emit();
+    }
+
+    #[payable]
+    pub fn set_flash_loan_fee(&mut self, new_fee: BasisPoint) {
+        self.assert_owner_with_one_yocto();
+        require!(new_fee < FULL_BASIS_POINT, ERR_INVALID_FLASH_LOAN_FEE);
+
+        Event::SetFlashLoanFee {
+            old_fee: self.flash_loans.fee,
+            new_fee,
+        }
+        .emit();
+
+        self.flash_loans.fee = new_fee;
+    }
+
+    pub fn get_flash_loan_fee(&self) -> BasisPoint {
+        self.flash_loans.fee
+    }
+
+    pub fn get_flash_loan_receivers(&self) -> Vec<AccountId> {
+        self.flash_loans.receivers.iter().cloned().collect()
+    }
+
+    /// The flash loan in progress, if any
+    pub fn get_current_flash_loan(&self) -> Option<FlashLoan> {
+        self.flash_loans.current.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, Gas, ONE_NEAR};
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    fn borrower() -> AccountId {
+        AccountId::new_unchecked("borrower".into())
+    }
+
+    fn open_loan(amount: Balance) -> PhoenixBonds {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(borrower())
+            .prepaid_gas(Gas(300 * TGAS))
+            .build());
+        // 900 NEAR in the reserve pool
+        let mut contract = new_contract(1000 * ONE_NEAR, 100 * ONE_NEAR, 0, 0, 1, 0);
+        contract.flash_loans.receivers.insert(borrower());
+        let _ = contract.flash_loan(amount.into(), "".to_string());
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(borrower())
+            .prepaid_gas(Gas(300 * TGAS))
+            .build());
+        let _ = contract.on_get_linear_price_for_flash_loan(
+            borrower(),
+            amount.into(),
+            "".to_string(),
+            Ok(U128(ONE_NEAR)),
+        );
+        contract
+    }
+
+    #[test]
+    fn test_repaid_flash_loan() {
+        let mut contract = open_loan(100 * ONE_NEAR);
+        let loan = contract.get_current_flash_loan().unwrap();
+        assert_eq!(loan.fee, ONE_NEAR / 10);
+
+        contract.internal_repay_flash_loan(&borrower(), 100 * ONE_NEAR + ONE_NEAR / 10);
+        let returned = contract.on_flash_loan_transferred(Ok(U128(100 * ONE_NEAR)));
+
+        assert_eq!(returned.0, 100 * ONE_NEAR + ONE_NEAR / 10);
+        assert_eq!(contract.linear_balance, 1000 * ONE_NEAR + ONE_NEAR / 10);
+        assert!(contract.get_current_flash_loan().is_none());
+        assert!(!contract.paused);
+    }
+
+    #[test]
+    fn test_unused_flash_loan_returned() {
+        let mut contract = open_loan(100 * ONE_NEAR);
+
+        // half of the loan is returned unused, fee is repaid
+        contract.internal_repay_flash_loan(&borrower(), 50 * ONE_NEAR + ONE_NEAR / 10);
+        contract.on_flash_loan_transferred(Ok(U128(50 * ONE_NEAR)));
+
+        assert_eq!(contract.linear_balance, 1000 * ONE_NEAR + ONE_NEAR / 10);
+        assert!(!contract.paused);
+    }
+
+    #[test]
+    fn test_defaulted_flash_loan_pauses_contract() {
+        let mut contract = open_loan(100 * ONE_NEAR);
+
+        // 30 LiNEAR is kept by the borrower, and written off
+        contract.internal_repay_flash_loan(&borrower(), 70 * ONE_NEAR);
+        let returned = contract.on_flash_loan_transferred(Ok(U128(100 * ONE_NEAR)));
+
+        assert_eq!(returned.0, 70 * ONE_NEAR);
+        assert_eq!(contract.linear_balance, 970 * ONE_NEAR);
+        assert!(contract.get_current_flash_loan().is_none());
+        assert!(contract.get_flash_loan_receivers().is_empty());
+        assert!(contract.paused);
+    }
+
+    #[test]
+    fn test_unpaid_fee_removes_receiver() {
+        let mut contract = open_loan(100 * ONE_NEAR);
+
+        // principal is repaid without fee
+        contract.internal_repay_flash_loan(&borrower(), 100 * ONE_NEAR);
+        let returned = contract.on_flash_loan_transferred(Ok(U128(100 * ONE_NEAR)));
+
+        assert_eq!(returned.0, 100 * ONE_NEAR);
+        assert_eq!(contract.linear_balance, 1000 * ONE_NEAR);
+        assert!(contract.get_flash_loan_receivers().is_empty());
+        assert!(!contract.paused);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid flash loan amount")]
+    fn test_flash_loan_capped_by_reserve_pool() {
+        open_loan(900 * ONE_NEAR + 1);
+    }
+}
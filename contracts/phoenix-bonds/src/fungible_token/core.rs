@@ -6,13 +6,12 @@ use near_contract_standards::fungible_token::{
 };
 use near_sdk::{json_types::U128, near_bindgen, AccountId, Balance, PromiseOrValue};
 
-near_contract_standards::impl_fungible_token_storage!(PhoenixBonds, ft);
-
 #[near_bindgen]
 impl FungibleTokenCore for PhoenixBonds {
     #[payable]
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
         require!(!self.paused, ERR_PAUSED);
+        self.record_pnear_received(&receiver_id, amount.0);
         self.ft.ft_transfer(receiver_id, amount, memo)
     }
 
@@ -25,6 +24,7 @@ impl FungibleTokenCore for PhoenixBonds {
         msg: String,
     ) -> PromiseOrValue<U128> {
         require!(!self.paused, ERR_PAUSED);
+        self.record_pnear_received(&receiver_id, amount.0);
         self.ft.ft_transfer_call(receiver_id, amount, memo, msg)
     }
 
@@ -46,9 +46,17 @@ impl FungibleTokenResolver for PhoenixBonds {
         receiver_id: AccountId,
         amount: U128,
     ) -> U128 {
-        let (used_amount, _) =
+        let (used_amount, burned_amount) =
             self.ft
                 .internal_ft_resolve_transfer(&sender_id, receiver_id, amount);
+        // The refund couldn't be returned to the sender, who has unregistered.
+        // Instead of burning, keep these pNEAR in lost and found for the sender.
+        if burned_amount > 0 {
+            let contract_id = env::current_account_id();
+            self.mint_pnear(&contract_id, burned_amount, Some("Lost and found"));
+            self.lost_and_found
+                .insert(Asset::Pnear, &sender_id, burned_amount);
+        }
         used_amount.into()
     }
 }
@@ -61,8 +69,9 @@ impl PhoenixBonds {
         memo: Option<&str>,
     ) {
         if !self.ft.accounts.contains_key(account_id) {
-            self.ft.internal_register_account(account_id);
+            self.register_pnear_account(account_id);
         }
+        self.record_pnear_received(account_id, amount);
         self.ft.internal_deposit(account_id, amount);
         FtMint {
             owner_id: account_id,
@@ -87,6 +96,33 @@ impl PhoenixBonds {
         .emit();
     }
 
+    /// Transfer pNEAR held by this contract to given account
+    pub(crate) fn transfer_pnear_from_contract(
+        &mut self,
+        account_id: &AccountId,
+        amount: Balance,
+        memo: &str,
+    ) {
+        if !self.ft.accounts.contains_key(account_id) {
+            self.register_pnear_account(account_id);
+        }
+        self.record_pnear_received(account_id, amount);
+        self.ft.internal_transfer(
+            &env::current_account_id(),
+            account_id,
+            amount,
+            Some(memo.to_string()),
+        );
+    }
+
+    /// Register a pNEAR account on behalf of the user, whose storage,
+    /// including pNEAR holding time, is paid by this contract
+    fn register_pnear_account(&mut self, account_id: &AccountId) {
+        self.ft.internal_register_account(account_id);
+        self.redeem_fee
+            .track_held_since(account_id, current_timestamp_ms());
+    }
+
     pub(crate) fn pnear_total_supply(&self) -> Balance {
         self.ft.total_supply
     }
@@ -1,2 +1,3 @@
 mod core;
 mod metadata;
+mod storage;
@@ -0,0 +1,90 @@
+use crate::{redeem_fee::held_since_storage_cost, *};
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::{log, near_bindgen, AccountId, Promise};
+
+const ERR_HELD_SINCE_DEPOSIT: &str =
+    "The attached deposit is less than the storage of pNEAR holding time";
+
+/// Storage management of a standard fungible token, except that
+/// the storage of pNEAR holding time is charged to the account
+#[near_bindgen]
+impl StorageManagement for PhoenixBonds {
+    /// Register the account as a standard fungible token does. Accounts registered
+    /// before redeem fee was introduced have no pNEAR holding time, and are charged
+    /// the full redeem fee. They can deposit again to pay for the holding time,
+    /// which starts from then on.
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        if !self.ft.accounts.contains_key(&account_id) {
+            let storage_balance = self
+                .ft
+                .storage_deposit(Some(account_id.clone()), registration_only);
+            self.redeem_fee
+                .track_held_since(&account_id, current_timestamp_ms());
+            return storage_balance;
+        }
+        if self.redeem_fee.is_held_since_tracked(&account_id) {
+            // refunds the deposit
+            return self.ft.storage_deposit(Some(account_id), registration_only);
+        }
+
+        let amount = env::attached_deposit();
+        let cost = held_since_storage_cost();
+        require!(amount >= cost, ERR_HELD_SINCE_DEPOSIT);
+        self.redeem_fee
+            .track_held_since(&account_id, current_timestamp_ms());
+        let refund = amount - cost;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        self.ft.storage_balance_of(account_id).unwrap()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        self.ft.storage_withdraw(amount)
+    }
+
+    /// Unregister the account as a standard fungible token does, the storage
+    /// of pNEAR holding time is only refunded if it was paid for.
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let balance = match self.ft.accounts.get(&account_id) {
+            Some(balance) => balance,
+            None => {
+                log!("The account {} is not registered", &account_id);
+                return false;
+            }
+        };
+        require!(
+            balance == 0 || force.unwrap_or(false),
+            "Can't unregister the account with the positive balance without force"
+        );
+
+        self.ft.accounts.remove(&account_id);
+        self.ft.total_supply -= balance;
+        let mut refund = self.ft.storage_balance_bounds().min.0 + 1;
+        if !self.redeem_fee.untrack_held_since(&account_id) {
+            refund -= held_since_storage_cost();
+        }
+        Promise::new(account_id).transfer(refund);
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        self.ft.storage_balance_bounds()
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.ft.storage_balance_of(account_id)
+    }
+}
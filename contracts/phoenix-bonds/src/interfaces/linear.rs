@@ -1,7 +1,12 @@
 use near_sdk::{ext_contract, json_types::U128};
 
+// only the generated module is used
+#[allow(dead_code)]
 #[ext_contract(linear_contract)]
 pub trait LiNEARInterface {
     fn deposit_and_stake(&self) -> U128;
     fn ft_price(&self) -> U128;
+    fn unstake(&mut self, amount: U128);
+    fn withdraw(&mut self, amount: U128);
+    fn instant_unstake(&mut self, stake_shares_in: U128, min_amount_out: U128) -> U128;
 }
@@ -1,5 +1,7 @@
 mod ft;
 mod linear;
+mod wnear;
 
 pub use self::ft::*;
 pub use self::linear::*;
+pub use self::wnear::*;
@@ -0,0 +1,9 @@
+use near_sdk::{ext_contract, json_types::U128};
+
+// only the generated module is used
+#[allow(dead_code)]
+#[ext_contract(wnear_contract)]
+pub trait WrappedNearInterface {
+    fn near_deposit(&mut self);
+    fn near_withdraw(&mut self, amount: U128);
+}
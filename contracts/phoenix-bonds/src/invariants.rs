@@ -0,0 +1,652 @@
+use crate::*;
+use near_sdk::{near_bindgen, serde::Serialize};
+
+const ERR_INVARIANTS_BROKEN: &str = "Protocol invariants broken";
+
+/// Each conversion between NEAR and LiNEAR rounds to yocto, so LiNEAR could fall
+/// short of the pools by about 1 yocto NEAR per bond, cancel, commit or redeem
+const LINEAR_ROUNDING_TOLERANCE: Balance = 1_000_000_000;
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InvariantCheck {
+    name: String,
+    passed: bool,
+    /// values that were compared
+    detail: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InvariantReport {
+    passed: bool,
+    /// LiNEAR price used by the checks, price related checks are skipped without it
+    linear_price: Option<U128>,
+    /// if `linear_price` is the latest price seen by the contract, rather than a given one
+    linear_price_stale: bool,
+    checks: Vec<InvariantCheck>,
+}
+
+impl InvariantReport {
+    fn new(linear_price: Option<Balance>) -> Self {
+        Self {
+            passed: true,
+            linear_price: linear_price.map(U128),
+            linear_price_stale: false,
+            checks: vec![],
+        }
+    }
+
+    fn failed_checks(&self) -> Vec<&str> {
+        self.checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    fn check(&mut self, name: &str, passed: bool, detail: String) {
+        self.passed = self.passed && passed;
+        self.checks.push(InvariantCheck {
+            name: name.to_string(),
+            passed,
+            detail,
+        });
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingNotesAmount {
+    amount: U128,
+    /// where the next page starts, if there could be more pending notes
+    next_index: Option<u32>,
+}
+
+impl PhoenixBonds {
+    pub(crate) fn invariant_report(
+        &self,
+        linear_price: Option<Balance>,
+        pending_notes_limit: Option<u32>,
+    ) -> InvariantReport {
+        let mut report = InvariantReport::new(linear_price);
+
+        let pools_near_amount = self.pending_pool_near_amount
+            + self.permanent_pool_near_amount
+            + self.treasury_pool_near_amount;
+
+        // LiNEAR price is needed to value LiNEAR in NEAR
+        if let Some(linear_price) = linear_price {
+            let linear_near_amount = linear2near(self.linear_balance, linear_price);
+            report.check(
+                "linear_covers_pools",
+                linear_near_amount + LINEAR_ROUNDING_TOLERANCE >= pools_near_amount,
+                format!("linear in near: {linear_near_amount}, pools: {pools_near_amount}"),
+            );
+
+            let reserve = self.reserve_pool_near_amount(linear_price);
+            let supply = self.pnear_total_supply();
+            report.check(
+                "pnear_backed_by_reserve",
+                supply == 0 || reserve > 0,
+                format!("pnear supply: {supply}, reserve: {reserve}"),
+            );
+        }
+
+        // pending notes are loaded one by one, so they are only summed on request.
+        // If there are more pending notes than the limit, the first ones
+        // must not exceed the pending pool. Pending legacy notes not yet indexed
+        // are added as a whole.
+        if let Some(limit) = pending_notes_limit {
+            let (indexed_amount, next_index) = self.pending_notes_amount(0, limit);
+            let legacy_amount = self.note_registry.legacy_pending_amount();
+            let pending_notes_amount = indexed_amount + legacy_amount;
+            let (passed, counted) = match next_index {
+                None => (pending_notes_amount == self.pending_pool_near_amount, "all"),
+                Some(_) => (
+                    pending_notes_amount <= self.pending_pool_near_amount,
+                    "first",
+                ),
+            };
+            report.check(
+                "pending_pool_matches_notes",
+                passed,
+                format!(
+                    "{counted} pending notes: {indexed_amount}, legacy pending notes: {legacy_amount}, pending pool: {}",
+                    self.pending_pool_near_amount
+                ),
+            );
+        }
+
+        let mean_length_weight = self.accrual_param.mean_length.total_weight();
+        report.check(
+            "pending_pool_matches_mean_length",
+            mean_length_weight == self.pending_pool_near_amount,
+            format!(
+                "mean length weight: {mean_length_weight}, pending pool: {}",
+                self.pending_pool_near_amount
+            ),
+        );
+
+        let created = self.note_registry.created_count();
+        let settled = self.note_registry.committed_count() + self.note_registry.cancelled_count();
+        report.check(
+            "note_counters",
+            settled <= created,
+            format!("created: {created}, settled: {settled}"),
+        );
+
+        // accounts with lost and found created before they were indexed
+        // are not listed, so only require indexed amount not to exceed total
+        for (name, asset) in [
+            ("lost_and_found_linear", Asset::Linear),
+            ("lost_and_found_near", Asset::Near),
+            ("lost_and_found_pnear", Asset::Pnear),
+        ] {
+            let indexed = self.lost_and_found.indexed_amount(asset);
+            let total = self.lost_and_found.total_amount(asset);
+            report.check(
+                name,
+                indexed <= total,
+                format!("indexed: {indexed}, total: {total}"),
+            );
+        }
+
+        let held_pnear = self
+            .ft
+            .accounts
+            .get(&env::current_account_id())
+            .unwrap_or(0);
+        let lost_pnear = self.lost_and_found.total_amount(Asset::Pnear);
+        report.check(
+            "lost_and_found_pnear_held",
+            held_pnear >= lost_pnear,
+            format!("held: {held_pnear}, lost and found: {lost_pnear}"),
+        );
+
+        report
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// Check accounting invariants of the protocol.
+    /// If `linear_price` is not given, the latest LiNEAR price seen by the contract is used
+    /// and reported as stale. Checks depending on LiNEAR price are skipped if no price is known.
+    /// Pending notes in the registry are summed only if `pending_notes_limit` is given.
+    /// If there are more pending notes than the limit, only the first `pending_notes_limit`
+    /// of them are checked not to exceed the pending pool. To compare all of them,
+    /// sum them page by page with `get_pending_notes_amount`.
+    /// Pending notes created before the registry that are not yet indexed are counted as a whole.
+    /// If `assert_passed` is true, panic when any check fails.
+    pub fn check_invariants(
+        &self,
+        linear_price: Option<U128>,
+        pending_notes_limit: Option<u32>,
+        assert_passed: Option<bool>,
+    ) -> InvariantReport {
+        let linear_price_stale = linear_price.is_none() && self.last_linear_price.is_some();
+        let linear_price = linear_price.map(|p| p.0).or(self.last_linear_price);
+        let mut report = self.invariant_report(linear_price, pending_notes_limit);
+        report.linear_price_stale = linear_price_stale;
+        if assert_passed.unwrap_or(false) && !report.passed {
+            env::panic_str(&format!(
+                "{ERR_INVARIANTS_BROKEN}: {}",
+                report.failed_checks().join(", ")
+            ));
+        }
+        report
+    }
+
+    /// Sum of bond amount of pending notes in the registry, starting from `from_index`.
+    /// Pass `next_index` as `from_index` to get the next page.
+    pub fn get_pending_notes_amount(&self, from_index: u32, limit: u32) -> PendingNotesAmount {
+        let (amount, next_index) = self.pending_notes_amount(from_index, limit);
+        PendingNotesAmount {
+            amount: amount.into(),
+            next_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::ONE_NEAR;
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    fn alice() -> AccountId {
+        AccountId::new_unchecked("alice".into())
+    }
+
+    #[test]
+    fn test_invariants_passed() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 0, 100 * ONE_NEAR, 10 * ONE_NEAR, 1, 0);
+        contract.internal_create_bond(alice(), 300 * ONE_NEAR, 300 * ONE_NEAR, None, None);
+        contract.internal_create_bond(alice(), 200 * ONE_NEAR, 200 * ONE_NEAR, None, None);
+
+        let report = contract.invariant_report(Some(ONE_NEAR), Some(10));
+        assert!(report.passed, "{:?}", report);
+        assert!(report
+            .checks
+            .iter()
+            .any(|c| c.name == "pending_pool_matches_notes"));
+
+        // only the first pending notes are checked if they don't fit in the limit
+        let report = contract.invariant_report(Some(ONE_NEAR), Some(1));
+        assert!(report.passed, "{:?}", report);
+        contract.pending_pool_near_amount = 200 * ONE_NEAR;
+        let report = contract.invariant_report(Some(ONE_NEAR), Some(1));
+        assert!(report
+            .failed_checks()
+            .contains(&"pending_pool_matches_notes"));
+    }
+
+    #[test]
+    fn test_invariants_broken() {
+        // pools are larger than LiNEAR balance, and no pending notes are tracked
+        let contract = new_contract(
+            1000 * ONE_NEAR,
+            900 * ONE_NEAR,
+            100 * ONE_NEAR,
+            10 * ONE_NEAR,
+            1,
+            0,
+        );
+
+        let report = contract.invariant_report(Some(ONE_NEAR), Some(10));
+        assert!(!report.passed);
+        assert_eq!(
+            report.failed_checks(),
+            vec![
+                "linear_covers_pools",
+                "pending_pool_matches_notes",
+                "pending_pool_matches_mean_length"
+            ]
+        );
+
+        // price related checks are skipped without LiNEAR price
+        let report = contract.invariant_report(None, None);
+        assert_eq!(
+            report.failed_checks(),
+            vec!["pending_pool_matches_mean_length"]
+        );
+    }
+
+    #[test]
+    fn test_check_invariants_with_latest_linear_price() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 0, 1000 * ONE_NEAR, 0, 1, 0);
+
+        // no LiNEAR price is known yet
+        let report = contract.check_invariants(None, None, Some(true));
+        assert_eq!(report.linear_price, None);
+        assert!(!report.linear_price_stale);
+        assert!(!report
+            .checks
+            .iter()
+            .any(|c| c.name == "linear_covers_pools"));
+
+        contract.record_linear_price(Ok(U128(ONE_NEAR)));
+        let report = contract.check_invariants(None, None, Some(true));
+        assert_eq!(report.linear_price, Some(U128(ONE_NEAR)));
+        assert!(report.linear_price_stale);
+
+        let report = contract.check_invariants(Some(U128(2 * ONE_NEAR)), None, Some(true));
+        assert_eq!(report.linear_price, Some(U128(2 * ONE_NEAR)));
+        assert!(!report.linear_price_stale);
+    }
+
+    #[test]
+    fn test_linear_rounding_tolerance() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 0, 1000 * ONE_NEAR, 0, 1, 0);
+        contract.permanent_pool_near_amount += LINEAR_ROUNDING_TOLERANCE;
+        let report = contract.invariant_report(Some(ONE_NEAR), None);
+        assert!(report.passed, "{:?}", report);
+
+        contract.permanent_pool_near_amount += 1;
+        let report = contract.invariant_report(Some(ONE_NEAR), None);
+        assert_eq!(report.failed_checks(), vec!["linear_covers_pools"]);
+    }
+
+    #[test]
+    fn test_pending_pool_drift_from_notes() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 0, 0, 0, 1, 0);
+        contract.internal_create_bond(alice(), 100 * ONE_NEAR, 100 * ONE_NEAR, None, None);
+        // pool and mean length are updated, but no note is created
+        contract.pending_pool_near_amount += ONE_NEAR;
+        contract.accrual_weighted_mean_insert(ONE_NEAR, 0);
+
+        let report = contract.invariant_report(Some(ONE_NEAR), Some(10));
+        assert_eq!(report.failed_checks(), vec!["pending_pool_matches_notes"]);
+    }
+
+    #[test]
+    fn test_paginate_pending_notes_amount() {
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+        for i in 1..=5 {
+            contract.internal_create_bond(alice(), i * ONE_NEAR, i * ONE_NEAR, None, None);
+        }
+        let mut note = contract.bond_notes.get_user_note(&alice(), 1);
+        note.cancel();
+        contract.save_settled_note(&alice(), 1, note);
+
+        let page = contract.get_pending_notes_amount(0, 2);
+        assert_eq!(page.amount.0, 4 * ONE_NEAR);
+        assert_eq!(page.next_index, Some(3));
+        let page = contract.get_pending_notes_amount(3, 2);
+        assert_eq!(page.amount.0, 9 * ONE_NEAR);
+        assert_eq!(page.next_index, Some(5));
+        let page = contract.get_pending_notes_amount(5, 2);
+        assert_eq!(page.amount.0, 0);
+        assert_eq!(page.next_index, None);
+    }
+}
+
+/// Drive the contract through random flows of bond, commit, cancel and redeem
+/// under a non-decreasing LiNEAR price, and check the invariants after each step.
+#[cfg(test)]
+mod prop_tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, ONE_NEAR};
+    use proptest::{prelude::*, sample::Index};
+
+    use crate::{tests::new_contract, utils::tests::ONE_DAY_MS};
+
+    use super::*;
+
+    /// bond amounts are generated in units of the minimum bond amount,
+    /// so that the brute-force mean length could be computed without overflow
+    const AMOUNT_UNIT: Balance = MINIMUM_BOND_AMOUNT;
+    const USERS: [&str; 3] = ["alice", "bob", "charlie"];
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Bond {
+            user: usize,
+            units: u128,
+        },
+        Commit {
+            note: Index,
+        },
+        Cancel {
+            note: Index,
+        },
+        Redeem {
+            user: usize,
+            share: BasisPoint,
+        },
+        /// time passes and LiNEAR price grows
+        Advance {
+            ms: Duration,
+            growth: BasisPoint,
+        },
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            3 => (0..USERS.len(), 1..=10_000u128).prop_map(|(user, units)| Op::Bond { user, units }),
+            2 => any::<Index>().prop_map(|note| Op::Commit { note }),
+            1 => any::<Index>().prop_map(|note| Op::Cancel { note }),
+            2 => (0..USERS.len(), 1..=FULL_BASIS_POINT)
+                .prop_map(|(user, share)| Op::Redeem { user, share }),
+            3 => (0..=3 * ONE_DAY_MS, 0..=50u32).prop_map(|(ms, growth)| Op::Advance { ms, growth }),
+        ]
+    }
+
+    struct PendingNote {
+        user: AccountId,
+        note_id: u32,
+        units: u128,
+        created_at: Timestamp,
+    }
+
+    /// Reference model of pending notes, with a brute-force mean length
+    struct Model {
+        ts: Timestamp,
+        linear_price: Balance,
+        pending: Vec<PendingNote>,
+        /// upper bound of accumulated rounding error of the weighted sum, in units * ms,
+        /// since `WeightedMeanLength` rounds the mean on each update
+        mean_error: u128,
+        /// upper bound of yoctoNEAR lost by rounding LiNEAR conversions
+        linear_rounding: Balance,
+    }
+
+    impl Model {
+        fn total_units(&self) -> u128 {
+            self.pending.iter().map(|n| n.units).sum()
+        }
+
+        fn brute_force_mean(&self) -> u128 {
+            let total = self.total_units();
+            if total == 0 {
+                return 0;
+            }
+            let weighted: u128 = self
+                .pending
+                .iter()
+                .map(|n| n.units * u128::from(self.ts - n.created_at))
+                .sum();
+            weighted / total
+        }
+
+        /// called before each insert or remove of the weighted mean
+        fn on_mean_update(&mut self) {
+            let total = self.total_units();
+            self.mean_error = if total == 0 {
+                0
+            } else {
+                self.mean_error + total.div_ceil(2)
+            };
+        }
+    }
+
+    fn account(name: &str) -> AccountId {
+        AccountId::new_unchecked(name.into())
+    }
+
+    fn set_timestamp_ms(ms: Timestamp) {
+        testing_env!(VMContextBuilder::new()
+            .block_timestamp(ms * 1_000_000)
+            .build());
+    }
+
+    /// Conversions round to yocto, so pNEAR price could drop by the rounding of
+    /// a few yocto pNEAR (valued in NEAR) per pNEAR moved, spread over the supply
+    fn assert_pnear_price_kept(
+        action: &str,
+        price_before: Balance,
+        price_after: Balance,
+        pnear_amount: Balance,
+        pnear_total_supply: Balance,
+    ) {
+        let rounding_near_amount = (pnear_amount / ONE_PNEAR + 2) * (price_before / ONE_NEAR + 1);
+        let tolerance = rounding_near_amount * ONE_PNEAR / pnear_total_supply.max(1) + 1;
+        assert!(
+            price_after + tolerance >= price_before,
+            "pNEAR price decreased on {}: {} -> {}",
+            action,
+            price_before,
+            price_after
+        );
+    }
+
+    fn check_state(contract: &PhoenixBonds, model: &Model) {
+        let report = contract.invariant_report(Some(model.linear_price), Some(u32::MAX));
+        assert!(report.passed, "{:?}", report);
+        // upgrade checks invariants at the latest price seen by the contract
+        let report = contract.invariant_report(contract.last_linear_price, Some(u32::MAX));
+        assert!(report.passed, "{:?}", report);
+        let pools_near_amount = contract.pending_pool_near_amount
+            + contract.permanent_pool_near_amount
+            + contract.treasury_pool_near_amount;
+        assert!(
+            linear2near(contract.linear_balance, model.linear_price) + model.linear_rounding
+                >= pools_near_amount,
+            "{:?}",
+            report
+        );
+
+        let pending_amount: Balance = model
+            .pending
+            .iter()
+            .map(|n| {
+                let note = contract.bond_notes.get_user_note(&n.user, n.note_id);
+                assert!(note.status() == BondStatus::Pending);
+                note.bond_amount()
+            })
+            .sum();
+        assert_eq!(pending_amount, contract.pending_pool_near_amount);
+
+        let mean = u128::from(contract.accrual_param.mean_length.mean(model.ts));
+        let expected = model.brute_force_mean();
+        let total = model.total_units();
+        // mean is rounded while brute force mean is floored
+        let tolerance = if total == 0 {
+            0
+        } else {
+            model.mean_error.div_ceil(total) + 2
+        };
+        assert!(
+            mean.max(expected) - mean.min(expected) <= tolerance,
+            "mean length: {}, brute force: {}, tolerance: {}",
+            mean,
+            expected,
+            tolerance
+        );
+    }
+
+    fn run(ops: Vec<Op>) {
+        // start each case from empty storage
+        near_sdk::mock::with_mocked_blockchain(|b| b.take_storage());
+        set_timestamp_ms(0);
+        let mut contract = new_contract(0, 0, 0, 0, 10 * ONE_DAY_MS, 300);
+        let mut model = Model {
+            ts: 0,
+            linear_price: ONE_NEAR,
+            pending: vec![],
+            mean_error: 0,
+            linear_rounding: 0,
+        };
+
+        for op in ops {
+            // each operation runs as a new function call with fresh gas
+            set_timestamp_ms(model.ts);
+            match op {
+                Op::Bond { user, units } => {
+                    let user = account(USERS[user]);
+                    let bond_amount = units * AMOUNT_UNIT;
+                    let linear_amount = near2linear(bond_amount, model.linear_price);
+                    model.linear_rounding += 1;
+                    model.on_mean_update();
+                    let note = contract.internal_create_bond(
+                        user.clone(),
+                        bond_amount,
+                        linear_amount,
+                        None,
+                        None,
+                    );
+                    model.pending.push(PendingNote {
+                        user,
+                        note_id: note.id(),
+                        units,
+                        created_at: model.ts,
+                    });
+                }
+                Op::Commit { note } | Op::Cancel { note } if !model.pending.is_empty() => {
+                    let price_before = contract.pnear_price(model.linear_price);
+                    let supply_before = contract.pnear_total_supply();
+                    model.on_mean_update();
+                    model.linear_rounding += 1;
+                    let note = model.pending.swap_remove(note.index(model.pending.len()));
+                    let drift = contract.accrual_param.mean_length.checked_remove_drift(
+                        note.units * AMOUNT_UNIT,
+                        model.ts - note.created_at,
+                        model.ts,
+                    );
+                    // only rounding of the mean could make the sum short
+                    assert!(
+                        drift.unwrap() <= model.mean_error * AMOUNT_UNIT,
+                        "mean length drift: {:?}, rounding: {}",
+                        drift,
+                        model.mean_error
+                    );
+                    let linear_price = Ok(U128(model.linear_price));
+                    if let Op::Commit { .. } = op {
+                        contract.on_get_linear_price_for_commit(
+                            note.user,
+                            note.note_id,
+                            linear_price,
+                        );
+                        let supply_after = contract.pnear_total_supply();
+                        assert_pnear_price_kept(
+                            "commit",
+                            price_before,
+                            contract.pnear_price(model.linear_price),
+                            supply_after - supply_before,
+                            supply_after,
+                        );
+                    } else {
+                        contract.on_get_linear_price_for_cancel(
+                            note.user,
+                            note.note_id,
+                            None,
+                            None,
+                            linear_price,
+                        );
+                    }
+                }
+                Op::Commit { .. } | Op::Cancel { .. } => {}
+                Op::Redeem { user, share } => {
+                    let user = account(USERS[user]);
+                    let amount =
+                        apply_basis_point(contract.ft.accounts.get(&user).unwrap_or(0), share);
+                    let redeemed = contract
+                        .pools()
+                        .redeem(amount, 0, model.linear_price)
+                        .redeemed_linear;
+                    // skip redeem that would be rejected
+                    if amount == 0
+                        || redeemed == 0
+                        || contract.pnear_total_supply() - amount <= ONE_PNEAR
+                    {
+                        continue;
+                    }
+
+                    let price_before = contract.pnear_price(model.linear_price);
+                    model.linear_rounding += 1;
+                    contract.on_get_linear_price_for_redeem(
+                        user,
+                        U128(amount),
+                        None,
+                        None,
+                        Ok(U128(model.linear_price)),
+                    );
+                    assert_pnear_price_kept(
+                        "redeem",
+                        price_before,
+                        contract.pnear_price(model.linear_price),
+                        amount,
+                        contract.pnear_total_supply(),
+                    );
+                }
+                Op::Advance { ms, growth } => {
+                    model.ts += ms;
+                    model.linear_price += apply_basis_point(model.linear_price, growth);
+                }
+            }
+
+            check_state(&contract, &model);
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_random_flows_keep_invariants(ops in prop::collection::vec(op(), 1..80)) {
+            run(ops);
+        }
+    }
+}
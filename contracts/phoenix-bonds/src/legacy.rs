@@ -1,13 +1,34 @@
 //! This module contains all contract state versions, which are needed
 //! when upgrading contract.
-use crate::{accrual::WeightedMeanLength, *};
+use crate::{accrual::WeightedMeanLength, lost_found::AssetLostAndFound, *};
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
-    near_bindgen, AccountId, Balance,
+    near_bindgen,
+    store::LookupMap,
+    AccountId, Balance, IntoStorageKey,
 };
 
+/// LiNEAR lost and found before v1.2.0
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct LostAndFoundV1_1_0 {
+    pub total_amount: Balance,
+    pub user_lost_found: LookupMap<AccountId, Balance>,
+}
+
+impl From<LostAndFoundV1_1_0> for AssetLostAndFound {
+    fn from(val: LostAndFoundV1_1_0) -> Self {
+        AssetLostAndFound::with_user_lost_found(
+            val.total_amount,
+            val.user_lost_found,
+            StorageKey::UserLostFound.into_storage_key(),
+        )
+    }
+}
+
 // ------ v1.0.0 ------
 
+// only read back through state migration
+#[allow(dead_code)]
 #[near_bindgen]
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct ContractV1_0_0 {
@@ -32,7 +53,7 @@ pub struct ContractV1_0_0 {
     pub tau: BasisPoint,
 
     /// amount of LiNEAR that was not successfully transferred
-    pub linear_lost_and_found: LostAndFound,
+    pub linear_lost_and_found: LostAndFoundV1_1_0,
     /// bond note for each user
     pub bond_notes: BondNotes,
     /// when bootstrapping period ends, before which commit & redeem are disabled
@@ -76,6 +97,7 @@ impl From<AccrualParameterV1_0_0> for AccrualParameter {
 // ------ v1.0.1 ------
 // - Replace last_updated_at with exceeds_target_at in AccrualParameterV1_0_0
 
+#[allow(dead_code)]
 #[near_bindgen]
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct ContractV1_0_1 {
@@ -100,7 +122,7 @@ pub struct ContractV1_0_1 {
     tau: BasisPoint,
 
     /// amount of LiNEAR that was not successfully transferred
-    linear_lost_and_found: LostAndFound,
+    linear_lost_and_found: LostAndFoundV1_1_0,
     /// bond note for each user
     bond_notes: BondNotes,
     /// when bootstrapping period ends, before which commit & redeem are disabled
@@ -108,3 +130,38 @@ pub struct ContractV1_0_1 {
     /// helper module to calculate accrual parameter (alpha)
     accrual_param: AccrualParameter,
 }
+
+// ------ v1.1.0 ------
+
+#[near_bindgen]
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ContractV1_1_0 {
+    /// pNEAR token
+    pub ft: FungibleToken,
+    /// contract owner
+    pub owner_id: AccountId,
+    /// LiNEAR contract address
+    pub linear_address: AccountId,
+    /// if all user interactions of the contract should be paused
+    pub paused: bool,
+
+    /// total LiNEAR balance this contract holds
+    pub linear_balance: Balance,
+    /// amount of NEAR that has been bonded but not yet claimed/canceled
+    pub pending_pool_near_amount: Balance,
+    /// amount of NEAR that the protocol owns
+    pub permanent_pool_near_amount: Balance,
+    /// amount of NEAR to reward AMM liquidity provider
+    pub treasury_pool_near_amount: Balance,
+    /// percentage of bond amount that goes to treasury pool when a user claims
+    pub tau: BasisPoint,
+
+    /// amount of LiNEAR that was not successfully transferred
+    pub linear_lost_and_found: LostAndFoundV1_1_0,
+    /// bond note for each user
+    pub bond_notes: BondNotes,
+    /// when bootstrapping period ends, before which commit & redeem are disabled
+    pub bootstrap_ends_at: Timestamp,
+    /// helper module to calculate accrual parameter (alpha)
+    pub accrual_param: AccrualParameter,
+}
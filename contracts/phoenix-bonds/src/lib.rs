@@ -4,9 +4,12 @@ use crate::{
     utils::*,
 };
 use accrual::{AccrualConfig, AccrualParameter};
-use bond_note::{BondNote, BondNotes, BondStatus};
+use bond_note::{BondNote, BondNotes};
+use dynamic_tau::DynamicTau;
 use events::Event;
-use lost_found::LostAndFound;
+use exit::{assert_exit, assert_exit_deposit, extra_exit_gas, Exit};
+use flash_loan::FlashLoans;
+use lost_found::{Asset, LostAndFound};
 use near_contract_standards::fungible_token::FungibleToken;
 use near_sdk::{
     assert_one_yocto,
@@ -16,26 +19,47 @@ use near_sdk::{
     near_bindgen, require, AccountId, Balance, PanicOnDefault, Promise, PromiseError, ONE_NEAR,
     ONE_YOCTO,
 };
+use note_registry::NoteRegistry;
+use note_storage::NoteStorage;
+use redeem_fee::{with_held_since_storage, RedeemFee};
+use referral::Referrals;
+use snapshots::Snapshots;
 use types::{BasisPoint, Duration, StorageKey, Timestamp, FULL_BASIS_POINT};
+use unstake::UnstakeTickets;
+
+pub use bond_note::{BondNoteInfo, BondStatus};
+pub use view::{AccrualInfo, Summary};
 
 use std::cmp::min;
 
 mod accrual;
 mod active_vector;
 mod bond_note;
+mod dynamic_tau;
 mod events;
+mod exit;
+mod flash_loan;
 mod fungible_token;
 mod interfaces;
+mod invariants;
 mod legacy;
 mod lost_found;
 mod math;
 mod metadata;
+mod note_registry;
+mod note_storage;
 mod owner;
+mod reconcile;
+mod redeem_fee;
+mod referral;
+mod snapshots;
 mod token_receiver;
 mod types;
+mod unstake;
 mod upgrade;
 mod utils;
 mod view;
+mod wnear;
 
 const MINIMUM_BOND_AMOUNT: u128 = ONE_NEAR / 10; // 0.1 NEAR
 const BOND_STORAGE_DEPOSIT: u128 = ONE_NEAR / 100; // 0.01 NEAR
@@ -53,6 +77,7 @@ const ERR_BOOTSTRAPPING: &str = "Commit and redeem are not allowed now";
 const ERR_BAD_BOOTSTRAP_END: &str = "Bootstrap end time must be in the future";
 const ERR_NOT_ENOUGH_GAS: &str = "Not enough gas";
 const ERR_BURN_TOO_MANY: &str = "At least one pNEAR must be left";
+const ERR_STAKE_FAILED: &str = "Failed to stake NEAR on LiNEAR";
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -77,14 +102,37 @@ pub struct PhoenixBonds {
     /// percentage of bond amount that goes to treasury pool when a user claims
     tau: BasisPoint,
 
-    /// amount of LiNEAR that was not successfully transferred
-    linear_lost_and_found: LostAndFound,
+    /// assets that were not successfully transferred
+    lost_and_found: LostAndFound,
     /// bond note for each user
     bond_notes: BondNotes,
     /// when bootstrapping period ends, before which commit & redeem are disabled
     bootstrap_ends_at: Timestamp,
     /// helper module to calculate accrual parameter (alpha)
     accrual_param: AccrualParameter,
+    /// storage deposit and usage of bond notes for each user
+    note_storage: NoteStorage,
+    /// global index of bond notes of all users
+    note_registry: NoteRegistry,
+    /// recent snapshots of protocol state
+    snapshots: Snapshots,
+    /// flash loans of LiNEAR held by the protocol
+    flash_loans: FlashLoans,
+    /// fee charged when redeeming pNEAR
+    redeem_fee: RedeemFee,
+    /// if set, tau is adjusted by treasury pool instead of using the static one
+    dynamic_tau: Option<DynamicTau>,
+    /// NEAR unstaked from LiNEAR for users, not yet withdrawn
+    unstake_tickets: UnstakeTickets,
+    /// wNEAR contract address, if set wNEAR can be used to bond
+    wnear_address: Option<AccountId>,
+    /// referral rewards taken from the treasury cut of committed notes
+    referrals: Referrals,
+    /// latest LiNEAR price returned by `ft_price`, to check invariants on upgrade
+    last_linear_price: Option<Balance>,
+    /// LiNEAR deducted from balances but still held by this contract,
+    /// until its outbound transfer or unstake is resolved
+    linear_in_flight: Balance,
 }
 
 pub(crate) fn assert_tau(tau: BasisPoint) {
@@ -100,6 +148,7 @@ impl PhoenixBonds {
         tau: BasisPoint,
         bootstrap_ends: Timestamp,
         accrual: AccrualConfig,
+        wnear_address: Option<AccountId>,
     ) -> Self {
         require!(
             bootstrap_ends > current_timestamp_ms(),
@@ -109,7 +158,7 @@ impl PhoenixBonds {
         accrual.assert_valid();
 
         Self {
-            ft: FungibleToken::new(StorageKey::FungibleToken),
+            ft: with_held_since_storage(FungibleToken::new(StorageKey::FungibleToken)),
             owner_id,
             linear_address,
             paused: false,
@@ -118,7 +167,7 @@ impl PhoenixBonds {
             permanent_pool_near_amount: 0,
             treasury_pool_near_amount: 0,
             tau,
-            linear_lost_and_found: LostAndFound::new(),
+            lost_and_found: LostAndFound::new(),
             bond_notes: BondNotes::new(),
             bootstrap_ends_at: bootstrap_ends,
             accrual_param: AccrualParameter::new(
@@ -128,14 +177,25 @@ impl PhoenixBonds {
                 accrual.adjust_interval,
                 accrual.adjust_rate,
             ),
+            note_storage: NoteStorage::new(),
+            note_registry: NoteRegistry::new(),
+            snapshots: Snapshots::new(),
+            flash_loans: FlashLoans::new(),
+            redeem_fee: RedeemFee::new(),
+            dynamic_tau: None,
+            unstake_tickets: UnstakeTickets::new(),
+            wnear_address,
+            referrals: Referrals::new(),
+            last_linear_price: None,
+            linear_in_flight: 0,
         }
     }
 
     // ======== Bond ========
 
-    /// Create a new bond by depositing NEAR
+    /// Create a new bond by depositing NEAR, optionally referred by `referrer_id`
     #[payable]
-    pub fn bond(&mut self) -> Promise {
+    pub fn bond(&mut self, referrer_id: Option<AccountId>) -> Promise {
         // 120 Tgas
         require!(
             env::prepaid_gas() >= GAS_BOND + GAS_DEPOSIT_AND_STAKE + GAS_BOND_CALLBACK,
@@ -144,6 +204,7 @@ impl PhoenixBonds {
         require!(!self.paused, ERR_PAUSED);
 
         let user_id = env::predecessor_account_id();
+        self.assert_referrer(&user_id, referrer_id.as_ref());
 
         require!(
             env::attached_deposit() > BOND_STORAGE_DEPOSIT,
@@ -160,7 +221,7 @@ impl PhoenixBonds {
             .then(
                 Self::ext(env::current_account_id())
                     .with_static_gas(GAS_BOND_CALLBACK)
-                    .on_staked(user_id, U128(bond_amount)),
+                    .on_staked(user_id, U128(bond_amount), referrer_id),
             )
     }
 
@@ -169,30 +230,59 @@ impl PhoenixBonds {
         &mut self,
         user_id: AccountId,
         bond_amount: U128,
+        referrer_id: Option<AccountId>,
         #[callback_result] staked_linear_amount: Result<U128, PromiseError>,
     ) -> Option<u32> {
         if let Ok(linear_amount) = staked_linear_amount {
-            let note = self.internal_create_bond(user_id, bond_amount.0, linear_amount.0);
+            let note = self.internal_create_bond(
+                user_id.clone(),
+                bond_amount.0,
+                linear_amount.0,
+                None,
+                referrer_id,
+            );
+            self.deposit_note_storage(&user_id, BOND_STORAGE_DEPOSIT);
             Some(note.id())
         } else {
+            Event::BondFailed {
+                account_id: user_id.clone(),
+                amount: bond_amount,
+                reason: ERR_STAKE_FAILED.to_string(),
+            }
+            .emit();
+
             // refund user deposited NEAR
-            Promise::new(user_id).transfer(bond_amount.0 + BOND_STORAGE_DEPOSIT);
+            self.transfer_near(&user_id, bond_amount.0 + BOND_STORAGE_DEPOSIT);
             None
         }
     }
 
     // ======== Cancel ========
 
-    /// Cancel a bond, will return corresponding LiNEAR tokens to the user
+    /// Cancel a bond, will return corresponding LiNEAR tokens to the user,
+    /// or NEAR if `exit` is `delayed_near` or `instant_near`.
+    /// `min_amount_out` of NEAR is required for `instant_near`, and
+    /// `delayed_near` requires a deposit for the storage of the unstake ticket.
     #[payable]
-    pub fn cancel(&mut self, note_id: u32) -> Promise {
-        // 160 Tgas
+    pub fn cancel(
+        &mut self,
+        note_id: u32,
+        exit: Option<Exit>,
+        min_amount_out: Option<U128>,
+    ) -> Promise {
+        // 160 Tgas, 210 Tgas for `delayed_near`
         require!(
-            env::prepaid_gas() >= GAS_CANCEL + GAS_GET_LINEAR_PRICE + GAS_CANCEL_CALLBACK,
+            env::prepaid_gas()
+                >= GAS_CANCEL + GAS_GET_LINEAR_PRICE + GAS_CANCEL_CALLBACK + extra_exit_gas(exit),
             ERR_NOT_ENOUGH_GAS
         );
-        assert_one_yocto();
+        assert_exit_deposit(exit);
         require!(!self.paused, ERR_PAUSED);
+        self.assert_no_flash_loan();
+        assert_exit(exit, min_amount_out);
+        if exit == Some(Exit::DelayedNear) {
+            self.assert_can_unstake();
+        }
 
         let user_id = env::predecessor_account_id();
         let bond_note = self.bond_notes.get_user_note(&user_id, note_id);
@@ -204,8 +294,8 @@ impl PhoenixBonds {
 
         self.get_linear_price().then(
             Self::ext(env::current_account_id())
-                .with_static_gas(GAS_CANCEL_CALLBACK)
-                .on_get_linear_price_for_cancel(user_id, note_id),
+                .with_static_gas(GAS_CANCEL_CALLBACK + extra_exit_gas(exit))
+                .on_get_linear_price_for_cancel(user_id, note_id, exit, min_amount_out),
         )
     }
 
@@ -214,45 +304,51 @@ impl PhoenixBonds {
         &mut self,
         user_id: AccountId,
         note_id: u32,
+        exit: Option<Exit>,
+        min_amount_out: Option<U128>,
         #[callback_result] linear_price: Result<U128, PromiseError>,
     ) -> Promise {
-        let linear_price = linear_price.expect(ERR_GET_LINEAR_PRICE);
+        let linear_price = self.record_linear_price(linear_price);
+        self.assert_no_flash_loan();
         let mut bond_note = self.bond_notes.get_user_note(&user_id, note_id);
 
-        // Due to precision, the calculated refund amount can be slightly more than the actual balance,
-        // use `min` here to avoid subtraction overflow
-        let refund_linear = min(
-            near2linear(bond_note.bond_amount(), linear_price.0),
-            self.linear_balance,
-        );
-
         // update user note
         bond_note.cancel();
-        self.bond_notes
-            .save_user_note(&user_id, note_id, bond_note.clone());
+        self.save_settled_note(&user_id, note_id, bond_note.clone());
 
         // update status
-        self.pending_pool_near_amount -= bond_note.bond_amount();
-        self.linear_balance -= refund_linear;
+        let mut pools = self.pools();
+        let refund_linear = pools.cancel(bond_note.bond_amount(), linear_price.0);
+        self.save_pools(pools);
 
         let current_timestamp = current_timestamp_ms();
-        self.accrual_param.weighted_mean_remove(
+        self.accrual_weighted_mean_remove(
             bond_note.bond_amount(),
             bond_note.length(current_timestamp),
             current_timestamp,
         );
 
+        self.record_snapshot(linear_price.0);
+
         Event::Cancel {
             account_id: user_id.clone(),
             note_id,
             bond_amount: bond_note.bond_amount().into(),
             refund_linear: refund_linear.into(),
+            pool_state: Some(self.pool_state(Some(linear_price.0))),
         }
         .emit();
 
-        // transfer LiNEAR to user
+        // pay out LiNEAR to user
         let memo = format!("Cancel Bond #{note_id}");
-        self.transfer_linear(&user_id, refund_linear, memo.as_str())
+        self.exit_linear(
+            &user_id,
+            refund_linear,
+            linear_price.0,
+            exit,
+            min_amount_out,
+            memo.as_str(),
+        )
     }
 
     // ======== Commit ========
@@ -266,6 +362,7 @@ impl PhoenixBonds {
         );
         assert_one_yocto();
         require!(!self.paused, ERR_PAUSED);
+        self.assert_no_flash_loan();
 
         require!(
             current_timestamp_ms() >= self.bootstrap_ends_at,
@@ -293,52 +390,53 @@ impl PhoenixBonds {
         note_id: u32,
         #[callback_result] linear_price: Result<U128, PromiseError>,
     ) -> U128 {
-        let linear_price = linear_price.expect(ERR_GET_LINEAR_PRICE);
+        let linear_price = self.record_linear_price(linear_price);
+        self.assert_no_flash_loan();
         let mut bond_note = self.bond_notes.get_user_note(&user_id, note_id);
         let bond_amount = bond_note.bond_amount();
 
         let current_timestamp = current_timestamp_ms();
-        let is_first_commit = self.pnear_total_supply() == 0;
-
-        let amount_for_treasury = apply_basis_point(bond_amount, self.tau);
-        let mut treasury_gained_near_amount = amount_for_treasury;
-        if is_first_commit {
-            // assign all staking profits before the first commit to treasury
-            treasury_gained_near_amount +=
-                linear2near(self.linear_balance, linear_price.0) - self.pending_pool_near_amount;
-        }
+        let note_length = bond_note.length(current_timestamp);
+        let tau = self.current_tau();
 
-        let reserve_should_gain_near_amount = self.accrued_amount(
-            bond_amount - amount_for_treasury,
-            bond_note.length(current_timestamp),
-            current_timestamp,
+        let mut pools = self.pools();
+        let pnear_to_mint = pools.commit(
+            bond_amount,
+            note_length,
+            tau,
+            self.accrual_param.current_alpha(current_timestamp),
+            linear_price.0,
         );
 
-        // this should be equal to: near2pnear(reserve_should_gain_near_amount, self.pnear_price(linear_price.0))
-        let pnear_to_mint = self.note_accrued_pnear(&bond_note, linear_price.0, current_timestamp);
-
-        let permanent_gained_near_amount =
-            bond_amount - amount_for_treasury - reserve_should_gain_near_amount;
-
         // update state
         bond_note.commit(pnear_to_mint);
-        let note_length = bond_note.length(current_timestamp);
-        self.bond_notes.save_user_note(&user_id, note_id, bond_note);
-
-        self.treasury_pool_near_amount += treasury_gained_near_amount;
-        self.permanent_pool_near_amount += permanent_gained_near_amount;
-        self.pending_pool_near_amount -= bond_amount;
+        let referrer_id = bond_note.referrer_id().cloned();
+        self.save_settled_note(&user_id, note_id, bond_note);
+        self.save_pools(pools);
+
+        if let Some(referrer_id) = referrer_id {
+            self.reward_referrer(
+                &referrer_id,
+                &user_id,
+                note_id,
+                bond_amount,
+                tau,
+                linear_price.0,
+            );
+        }
 
-        self.accrual_param
-            .weighted_mean_remove(bond_amount, note_length, current_timestamp);
+        self.accrual_weighted_mean_remove(bond_amount, note_length, current_timestamp);
 
         self.mint_pnear(&user_id, pnear_to_mint, Some("Commit Bond"));
+        self.record_snapshot(linear_price.0);
 
         Event::Commit {
             account_id: user_id,
             note_id,
             bond_amount: bond_amount.into(),
             pnear_amount: pnear_to_mint.into(),
+            tau,
+            pool_state: Some(self.pool_state(Some(linear_price.0))),
         }
         .emit();
 
@@ -347,15 +445,25 @@ impl PhoenixBonds {
 
     // ======== Redeem ========
 
+    /// Redeem pNEAR for LiNEAR, or for NEAR if `exit` is `delayed_near` or `instant_near`.
+    /// `min_amount_out` of NEAR is required for `instant_near`, and
+    /// `delayed_near` requires a deposit for the storage of the unstake ticket.
     #[payable]
-    pub fn redeem(&mut self, amount: U128) -> Promise {
-        // 160 Tgas
+    pub fn redeem(
+        &mut self,
+        amount: U128,
+        exit: Option<Exit>,
+        min_amount_out: Option<U128>,
+    ) -> Promise {
+        // 160 Tgas, 210 Tgas for `delayed_near`
         require!(
-            env::prepaid_gas() >= GAS_REDEEM + GAS_GET_LINEAR_PRICE + GAS_REDEEM_CALLBACK,
+            env::prepaid_gas()
+                >= GAS_REDEEM + GAS_GET_LINEAR_PRICE + GAS_REDEEM_CALLBACK + extra_exit_gas(exit),
             ERR_NOT_ENOUGH_GAS
         );
-        assert_one_yocto();
+        assert_exit_deposit(exit);
         require!(!self.paused, ERR_PAUSED);
+        self.assert_no_flash_loan();
 
         require!(
             current_timestamp_ms() >= self.bootstrap_ends_at,
@@ -363,6 +471,10 @@ impl PhoenixBonds {
         );
 
         require!(amount.0 > 0, ERR_BAD_REDEEM_AMOUNT);
+        assert_exit(exit, min_amount_out);
+        if exit == Some(Exit::DelayedNear) {
+            self.assert_can_unstake();
+        }
 
         let user_id = env::predecessor_account_id();
         require!(
@@ -376,8 +488,8 @@ impl PhoenixBonds {
 
         self.get_linear_price().then(
             Self::ext(env::current_account_id())
-                .with_static_gas(GAS_REDEEM_CALLBACK)
-                .on_get_linear_price_for_redeem(user_id, amount),
+                .with_static_gas(GAS_REDEEM_CALLBACK + extra_exit_gas(exit))
+                .on_get_linear_price_for_redeem(user_id, amount, exit, min_amount_out),
         )
     }
 
@@ -386,9 +498,12 @@ impl PhoenixBonds {
         &mut self,
         user_id: AccountId,
         pnear_amount: U128,
+        exit: Option<Exit>,
+        min_amount_out: Option<U128>,
         #[callback_result] linear_price: Result<U128, PromiseError>,
     ) -> Promise {
-        let linear_price = linear_price.expect(ERR_GET_LINEAR_PRICE);
+        let linear_price = self.record_linear_price(linear_price);
+        self.assert_no_flash_loan();
         require!(
             self.ft.internal_unwrap_balance_of(&user_id) >= pnear_amount.0,
             ERR_NOT_ENOUGH_PNEAR_BALANCE
@@ -398,27 +513,33 @@ impl PhoenixBonds {
             ERR_BURN_TOO_MANY
         );
 
-        // Due to precision, the calculated redeemed amount can be slightly more than the actual balance,
-        // use `min` here to avoid subtraction overflow
-        let redeemed_linear = min(
-            near2linear(
-                pnear2near(pnear_amount.0, self.pnear_price(linear_price.0)),
-                linear_price.0,
-            ),
-            self.linear_balance,
-        );
+        // redeem fee stays in the reserve pool
+        let redeem_fee = self.redeem_fee.fee_of(&user_id, current_timestamp_ms());
+        let mut pools = self.pools();
+        let outcome = pools.redeem(pnear_amount.0, redeem_fee, linear_price.0);
+        let redeemed_linear = outcome.redeemed_linear;
 
-        self.linear_balance -= redeemed_linear;
+        self.save_pools(pools);
         self.burn_pnear(&user_id, pnear_amount.0, Some("Redeem pNEAR"));
+        self.record_snapshot(linear_price.0);
 
         Event::Redeem {
             account_id: user_id.clone(),
             pnear_amount,
             redeemed_linear: redeemed_linear.into(),
+            fee_linear: outcome.fee_linear.into(),
+            pool_state: Some(self.pool_state(Some(linear_price.0))),
         }
         .emit();
 
-        self.transfer_linear(&user_id, redeemed_linear, "pNEAR Redeem")
+        self.exit_linear(
+            &user_id,
+            redeemed_linear,
+            linear_price.0,
+            exit,
+            min_amount_out,
+            "pNEAR Redeem",
+        )
     }
 
     /// We assume all LiNEAR token transfer will succeed,
@@ -427,11 +548,45 @@ impl PhoenixBonds {
     /// Returns the amount of LiNEAR that was successfully transferred.
     #[private]
     pub fn on_linear_transferred(&mut self, user_id: AccountId, linear_amount: U128) -> U128 {
+        self.on_linear_out_resolved(linear_amount.0);
         if is_promise_success() {
             return linear_amount;
         }
 
-        self.linear_lost_and_found.insert(&user_id, linear_amount.0);
+        self.lost_and_found
+            .insert(Asset::Linear, &user_id, linear_amount.0);
+        0.into()
+    }
+
+    /// Returns the amount of LiNEAR that was used by the receiver of `ft_transfer_call`,
+    /// the rest will be moved to the lost and found pool.
+    #[private]
+    pub fn on_linear_transferred_call(
+        &mut self,
+        user_id: AccountId,
+        linear_amount: U128,
+        #[callback_result] used_amount: Result<U128, PromiseError>,
+    ) -> U128 {
+        self.on_linear_out_resolved(linear_amount.0);
+        let used_amount = min(used_amount.map(|v| v.0).unwrap_or(0), linear_amount.0);
+        let unused_amount = linear_amount.0 - used_amount;
+        if unused_amount > 0 {
+            self.lost_and_found
+                .insert(Asset::Linear, &user_id, unused_amount);
+        }
+        used_amount.into()
+    }
+
+    /// If NEAR transfer failed, the NEAR will be moved to the lost and found pool.
+    /// Returns the amount of NEAR that was successfully transferred.
+    #[private]
+    pub fn on_near_transferred(&mut self, user_id: AccountId, near_amount: U128) -> U128 {
+        if is_promise_success() {
+            return near_amount;
+        }
+
+        self.lost_and_found
+            .insert(Asset::Near, &user_id, near_amount.0);
         0.into()
     }
 }
@@ -442,20 +597,30 @@ impl PhoenixBonds {
         user_id: AccountId,
         bond_amount: u128,
         staked_linear_amount: u128,
+        linear_price: Option<Balance>,
+        referrer_id: Option<AccountId>,
     ) -> BondNote {
-        self.pending_pool_near_amount += bond_amount;
-        self.linear_balance += staked_linear_amount;
+        let mut pools = self.pools();
+        pools.bond(bond_amount, staked_linear_amount);
+        self.save_pools(pools);
+        // NEAR bonds don't query LiNEAR price, the price is implied by staking
+        if let Some(price) =
+            linear_price.or_else(|| linear_price_of(bond_amount, staked_linear_amount))
+        {
+            self.last_linear_price = Some(price);
+        }
 
-        self.accrual_param
-            .weighted_mean_insert(bond_amount, current_timestamp_ms());
+        self.accrual_weighted_mean_insert(bond_amount, current_timestamp_ms());
 
-        let note = self.bond_notes.insert_new_note(&user_id, bond_amount);
+        let note = self.insert_new_note_with_storage(&user_id, bond_amount, referrer_id);
 
         Event::Bond {
             account_id: user_id,
             note_id: note.id(),
             bond_amount: U128(bond_amount),
             linear_amount: U128(staked_linear_amount),
+            referrer_id: note.referrer_id().cloned(),
+            pool_state: Some(self.pool_state(linear_price)),
         }
         .emit();
 
@@ -472,18 +637,89 @@ impl PhoenixBonds {
     /// If transfer failed, these LiNEAR will be moved to lost and found
     /// NOTE: Make sure LiNEAR balance is decreased before calling this!
     fn transfer_linear(&mut self, account_id: &AccountId, amount: Balance, memo: &str) -> Promise {
+        self.transfer_linear_to(account_id, account_id, amount, memo)
+    }
+
+    /// Transfer LiNEAR owned by `user_id` to `receiver_id`
+    /// If transfer failed, these LiNEAR will be moved to lost and found of `user_id`
+    /// NOTE: Make sure LiNEAR balance is decreased before calling this!
+    fn transfer_linear_to(
+        &mut self,
+        user_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+        memo: &str,
+    ) -> Promise {
         require!(amount > 0, ERR_INVALID_TRANSFER_AMOUNT);
+        self.on_linear_out(amount);
 
         ext_fungible_token::ext(self.linear_address.clone())
             .with_static_gas(GAS_FT_TRANSFER)
             .with_attached_deposit(ONE_YOCTO)
-            .ft_transfer(account_id.clone(), amount.into(), Some(memo.to_string()))
+            .ft_transfer(receiver_id.clone(), amount.into(), Some(memo.to_string()))
             .then(
                 Self::ext(env::current_account_id())
                     .with_static_gas(GAS_FT_TRANSFER_CALLBACK)
-                    .on_linear_transferred(account_id.clone(), amount.into()),
+                    .on_linear_transferred(user_id.clone(), amount.into()),
             )
     }
+
+    /// Transfer LiNEAR owned by `user_id` to `receiver_id` with `ft_transfer_call`
+    /// Unused or failed amount will be moved to lost and found of `user_id`
+    /// NOTE: Make sure LiNEAR balance is decreased before calling this!
+    fn transfer_call_linear(
+        &mut self,
+        user_id: &AccountId,
+        receiver_id: &AccountId,
+        amount: Balance,
+        memo: &str,
+        msg: String,
+    ) -> Promise {
+        require!(amount > 0, ERR_INVALID_TRANSFER_AMOUNT);
+        self.on_linear_out(amount);
+
+        ext_fungible_token::ext(self.linear_address.clone())
+            .with_static_gas(GAS_FT_TRANSFER_CALL)
+            .with_attached_deposit(ONE_YOCTO)
+            .ft_transfer_call(
+                receiver_id.clone(),
+                amount.into(),
+                Some(memo.to_string()),
+                msg,
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FT_TRANSFER_CALLBACK)
+                    .on_linear_transferred_call(user_id.clone(), amount.into()),
+            )
+    }
+
+    /// Transfer NEAR to given account
+    /// If transfer failed, these NEAR will be moved to lost and found
+    fn transfer_near(&mut self, account_id: &AccountId, amount: Balance) -> Promise {
+        Promise::new(account_id.clone()).transfer(amount).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_NEAR_TRANSFER_CALLBACK)
+                .on_near_transferred(account_id.clone(), amount.into()),
+        )
+    }
+}
+
+/// Unwrap LiNEAR price returned by `ft_price`. A zero price is treated as
+/// a failure too, since all conversions between NEAR and LiNEAR rely on it.
+fn unwrap_linear_price(linear_price: Result<U128, PromiseError>) -> U128 {
+    let linear_price = linear_price.expect(ERR_GET_LINEAR_PRICE);
+    require!(linear_price.0 > 0, ERR_GET_LINEAR_PRICE);
+    linear_price
+}
+
+impl PhoenixBonds {
+    /// Unwrap LiNEAR price returned by `ft_price`, and keep it as the latest price
+    pub(crate) fn record_linear_price(&mut self, linear_price: Result<U128, PromiseError>) -> U128 {
+        let linear_price = unwrap_linear_price(linear_price);
+        self.last_linear_price = Some(linear_price.0);
+        linear_price
+    }
 }
 
 #[cfg(test)]
@@ -517,6 +753,7 @@ mod tests {
                 adjust_interval,
                 adjust_rate,
             },
+            None,
         );
 
         contract.linear_balance = linear_balance;
@@ -1,58 +1,204 @@
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
+    collections::TreeMap,
     env,
     json_types::U128,
     near_bindgen,
+    serde::{Deserialize, Serialize},
     store::LookupMap,
-    AccountId, Balance, PanicOnDefault, Promise,
+    AccountId, Balance, IntoStorageKey, PanicOnDefault, PromiseOrValue,
 };
 
 use crate::*;
-use crate::{types::StorageKey, PhoenixBonds};
 
 const ERR_NO_LINEAR_TO_CLAIM: &str = "No lost and found LiNEAR to claim";
+const ERR_NO_NEAR_TO_CLAIM: &str = "No lost and found NEAR to claim";
+const ERR_NO_PNEAR_TO_CLAIM: &str = "No lost and found pNEAR to claim";
 
+/// Assets that could be kept in lost and found
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Asset {
+    Linear,
+    Near,
+    Pnear,
+}
+
+/// Lost and found of a single asset
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
-pub struct LostAndFound {
+pub struct AssetLostAndFound {
     total_amount: Balance,
     user_lost_found: LookupMap<AccountId, Balance>,
+    /// indexed accounts that have a non-zero lost and found balance,
+    /// ordered by their balance
+    accounts: TreeMap<(Balance, AccountId), ()>,
+    /// sum of lost and found of indexed accounts
+    indexed_amount: Balance,
+}
+
+impl AssetLostAndFound {
+    pub fn new<S>(key: S) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        let prefix = key.into_storage_key();
+        Self::with_user_lost_found(0, LookupMap::new(prefix.clone()), prefix)
+    }
+
+    /// Build from existing user balances, whose accounts need to be
+    /// indexed again with `index_lost_and_found`
+    pub fn with_user_lost_found(
+        total_amount: Balance,
+        user_lost_found: LookupMap<AccountId, Balance>,
+        prefix: Vec<u8>,
+    ) -> Self {
+        let accounts_prefix = [prefix.as_slice(), "_accounts".as_bytes()].concat();
+        Self {
+            total_amount,
+            user_lost_found,
+            accounts: TreeMap::new(accounts_prefix),
+            indexed_amount: 0,
+        }
+    }
+
+    fn insert(&mut self, user_id: &AccountId, amount: Balance) {
+        self.total_amount += amount;
+        let prev = self.user_amount(user_id);
+        self.user_lost_found.insert(user_id.clone(), prev + amount);
+        self.indexed_amount += if self.accounts.remove(&(prev, user_id.clone())).is_some() {
+            amount
+        } else {
+            prev + amount
+        };
+        self.accounts.insert(&(prev + amount, user_id.clone()), &());
+    }
+
+    fn user_amount(&self, user_id: &AccountId) -> Balance {
+        self.user_lost_found.get(user_id).copied().unwrap_or(0)
+    }
+
+    fn remove(&mut self, user_id: &AccountId) -> Balance {
+        let amount = self.user_amount(user_id);
+        self.total_amount -= amount;
+        self.user_lost_found.remove(user_id);
+        if self.accounts.remove(&(amount, user_id.clone())).is_some() {
+            self.indexed_amount -= amount;
+        }
+        amount
+    }
+
+    fn index(&mut self, user_id: &AccountId) {
+        let amount = self.user_amount(user_id);
+        if amount > 0
+            && self
+                .accounts
+                .insert(&(amount, user_id.clone()), &())
+                .is_none()
+        {
+            self.indexed_amount += amount;
+        }
+    }
+
+    /// A page of indexed entries, largest amount first, starting
+    /// after the entry of `after` if given
+    fn entries(
+        &self,
+        after: Option<(Balance, AccountId)>,
+        limit: u32,
+    ) -> Vec<(AccountId, Balance)> {
+        let entries: Box<dyn Iterator<Item = ((Balance, AccountId), ())>> = match after {
+            Some(key) => Box::new(self.accounts.iter_rev_from(key)),
+            None => Box::new(self.accounts.iter_rev()),
+        };
+        entries
+            .take(limit as usize)
+            .map(|((amount, account_id), _)| (account_id, amount))
+            .collect()
+    }
+}
+
+/// Keeps assets that were not successfully transferred to users.
+/// Each asset is stored under its own prefix, so the LiNEAR entries
+/// created before multi-asset support remain valid.
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct LostAndFound {
+    linear: AssetLostAndFound,
+    near: AssetLostAndFound,
+    pnear: AssetLostAndFound,
 }
 
 impl LostAndFound {
     pub fn new() -> Self {
+        Self::from_linear(AssetLostAndFound::new(StorageKey::UserLostFound))
+    }
+
+    /// Build lost and found on top of existing LiNEAR lost and found
+    pub fn from_linear(linear: AssetLostAndFound) -> Self {
         Self {
-            total_amount: 0,
-            user_lost_found: LookupMap::new(StorageKey::UserLostFound),
+            linear,
+            near: AssetLostAndFound::new(StorageKey::UserNearLostFound),
+            pnear: AssetLostAndFound::new(StorageKey::UserPnearLostFound),
         }
     }
 
-    pub fn total_amount(&self) -> Balance {
-        self.total_amount
+    fn asset(&self, asset: Asset) -> &AssetLostAndFound {
+        match asset {
+            Asset::Linear => &self.linear,
+            Asset::Near => &self.near,
+            Asset::Pnear => &self.pnear,
+        }
     }
 
-    pub fn insert(&mut self, user_id: &AccountId, amount: Balance) {
-        self.total_amount += amount;
-        let prev = self.user_amount(user_id);
-        self.user_lost_found.insert(user_id.clone(), prev + amount);
+    fn asset_mut(&mut self, asset: Asset) -> &mut AssetLostAndFound {
+        match asset {
+            Asset::Linear => &mut self.linear,
+            Asset::Near => &mut self.near,
+            Asset::Pnear => &mut self.pnear,
+        }
+    }
+
+    pub fn total_amount(&self, asset: Asset) -> Balance {
+        self.asset(asset).total_amount
+    }
+
+    /// Sum of lost and found of all indexed accounts
+    pub fn indexed_amount(&self, asset: Asset) -> Balance {
+        self.asset(asset).indexed_amount
+    }
+
+    pub fn user_amount(&self, asset: Asset, user_id: &AccountId) -> Balance {
+        self.asset(asset).user_amount(user_id)
+    }
+
+    pub fn insert(&mut self, asset: Asset, user_id: &AccountId, amount: Balance) {
+        self.asset_mut(asset).insert(user_id, amount);
 
         Event::LostFoundInsert {
             account_id: user_id.clone(),
+            asset,
             amount: amount.into(),
         }
         .emit();
     }
 
-    fn user_amount(&self, user_id: &AccountId) -> Balance {
-        self.user_lost_found.get(user_id).copied().unwrap_or(0)
+    /// Index accounts whose lost and found were created before
+    /// accounts were tracked, so they show up in listing.
+    pub fn index(&mut self, asset: Asset, user_ids: &[AccountId]) {
+        let asset_lost_and_found = self.asset_mut(asset);
+        for user_id in user_ids {
+            asset_lost_and_found.index(user_id);
+        }
     }
 
-    fn remove(&mut self, user_id: &AccountId) -> Balance {
-        let amount = self.user_amount(user_id);
-        self.total_amount -= amount;
-        self.user_lost_found.remove(user_id);
+    fn remove(&mut self, asset: Asset, user_id: &AccountId) -> Balance {
+        let amount = self.asset_mut(asset).remove(user_id);
 
         Event::LostFoundClaim {
             account_id: user_id.clone(),
+            asset,
             amount: amount.into(),
         }
         .emit();
@@ -61,25 +207,212 @@ impl LostAndFound {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LostAndFoundEntry {
+    account_id: AccountId,
+    amount: U128,
+}
+
 #[near_bindgen]
 impl PhoenixBonds {
-    pub fn user_lost_and_found(&self, account_id: AccountId) -> U128 {
-        self.linear_lost_and_found.user_amount(&account_id).into()
+    /// Lost and found entries of given asset, largest amount first.
+    /// To get the next page, pass the last entry of this page as `after`.
+    pub fn list_lost_and_found(
+        &self,
+        asset: Asset,
+        after: Option<LostAndFoundEntry>,
+        limit: u32,
+    ) -> Vec<LostAndFoundEntry> {
+        self.lost_and_found
+            .asset(asset)
+            .entries(after.map(|e| (e.amount.0, e.account_id)), limit)
+            .into_iter()
+            .map(|(account_id, amount)| LostAndFoundEntry {
+                account_id,
+                amount: amount.into(),
+            })
+            .collect()
     }
 
-    pub fn claim_lost_and_found(&mut self) -> Promise {
-        // 100 Tgas
-        require!(
-            env::prepaid_gas() >= GAS_CLAIM + GAS_FT_TRANSFER_AND_CALLBACK,
-            ERR_NOT_ENOUGH_GAS
-        );
+    /// Index existing lost and found entries so that they can be listed
+    #[payable]
+    pub fn index_lost_and_found(&mut self, asset: Asset, account_ids: Vec<AccountId>) {
+        self.assert_owner_with_one_yocto();
+        self.lost_and_found.index(asset, &account_ids);
+
+        Event::LostFoundIndexed {
+            asset,
+            account_ids,
+            indexed_amount: self.lost_and_found.indexed_amount(asset).into(),
+            total_amount: self.lost_and_found.total_amount(asset).into(),
+        }
+        .emit();
+    }
+
+    /// Lost and found amount of given asset, LiNEAR by default
+    pub fn user_lost_and_found(&self, account_id: AccountId, asset: Option<Asset>) -> U128 {
+        self.lost_and_found
+            .user_amount(asset.unwrap_or(Asset::Linear), &account_id)
+            .into()
+    }
+
+    pub fn total_lost_and_found(&self, asset: Asset) -> U128 {
+        self.lost_and_found.total_amount(asset).into()
+    }
+
+    /// Claim lost LiNEAR to another account. If `msg` is given, LiNEAR will be
+    /// sent with `ft_transfer_call`. Unused or failed transfers return to
+    /// the caller's lost and found.
+    pub fn claim_lost_and_found_to(
+        &mut self,
+        receiver_id: AccountId,
+        msg: Option<String>,
+    ) -> Promise {
+        let required_gas = if msg.is_some() {
+            // 150 Tgas
+            GAS_CLAIM + GAS_FT_TRANSFER_CALL + GAS_FT_TRANSFER_CALLBACK
+        } else {
+            // 100 Tgas
+            GAS_CLAIM + GAS_FT_TRANSFER_AND_CALLBACK
+        };
+        require!(env::prepaid_gas() >= required_gas, ERR_NOT_ENOUGH_GAS);
         require!(!self.paused, ERR_PAUSED);
 
         let user_id = env::predecessor_account_id();
-        let amount = self.linear_lost_and_found.remove(&user_id);
+        let amount = self.lost_and_found.remove(Asset::Linear, &user_id);
 
         require!(amount > 0, ERR_NO_LINEAR_TO_CLAIM);
 
-        self.transfer_linear(&user_id, amount, "Claim lost and found")
+        let memo = "Claim lost and found";
+        match msg {
+            Some(msg) => self.transfer_call_linear(&user_id, &receiver_id, amount, memo, msg),
+            None => self.transfer_linear_to(&user_id, &receiver_id, amount, memo),
+        }
+    }
+
+    /// Claim lost and found of given asset, LiNEAR by default
+    pub fn claim_lost_and_found(&mut self, asset: Option<Asset>) -> PromiseOrValue<U128> {
+        let asset = asset.unwrap_or(Asset::Linear);
+        let (required_gas, err_nothing_to_claim) = match asset {
+            // 100 Tgas
+            Asset::Linear => (
+                GAS_CLAIM + GAS_FT_TRANSFER_AND_CALLBACK,
+                ERR_NO_LINEAR_TO_CLAIM,
+            ),
+            // 30 Tgas
+            Asset::Near => (GAS_CLAIM + GAS_NEAR_TRANSFER_CALLBACK, ERR_NO_NEAR_TO_CLAIM),
+            // 20 Tgas
+            Asset::Pnear => (GAS_CLAIM, ERR_NO_PNEAR_TO_CLAIM),
+        };
+        require!(env::prepaid_gas() >= required_gas, ERR_NOT_ENOUGH_GAS);
+        require!(!self.paused, ERR_PAUSED);
+
+        let user_id = env::predecessor_account_id();
+        let amount = self.lost_and_found.remove(asset, &user_id);
+
+        require!(amount > 0, err_nothing_to_claim);
+
+        match asset {
+            Asset::Linear => self
+                .transfer_linear(&user_id, amount, "Claim lost and found")
+                .into(),
+            Asset::Near => self.transfer_near(&user_id, amount).into(),
+            Asset::Pnear => {
+                self.transfer_pnear_from_contract(&user_id, amount, "Claim lost and found");
+                PromiseOrValue::Value(amount.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alice() -> AccountId {
+        AccountId::new_unchecked("alice".into())
+    }
+
+    #[test]
+    fn test_assets_are_tracked_separately() {
+        let mut lost_and_found = LostAndFound::new();
+
+        lost_and_found.insert(Asset::Linear, &alice(), 100);
+        lost_and_found.insert(Asset::Near, &alice(), 20);
+        lost_and_found.insert(Asset::Linear, &alice(), 50);
+
+        assert_eq!(lost_and_found.user_amount(Asset::Linear, &alice()), 150);
+        assert_eq!(lost_and_found.user_amount(Asset::Near, &alice()), 20);
+        assert_eq!(lost_and_found.user_amount(Asset::Pnear, &alice()), 0);
+
+        assert_eq!(lost_and_found.remove(Asset::Linear, &alice()), 150);
+        assert_eq!(lost_and_found.total_amount(Asset::Linear), 0);
+        assert_eq!(lost_and_found.total_amount(Asset::Near), 20);
+    }
+
+    #[test]
+    fn test_indexed_amount() {
+        let bob = AccountId::new_unchecked("bob".into());
+        let charlie = AccountId::new_unchecked("charlie".into());
+        let prefix = b"l".to_vec();
+        // entries created before accounts were indexed
+        let mut user_lost_found = LookupMap::new(prefix.clone());
+        user_lost_found.insert(alice(), 100);
+        user_lost_found.insert(bob.clone(), 200);
+        let mut lost_and_found = LostAndFound::from_linear(
+            AssetLostAndFound::with_user_lost_found(300, user_lost_found, prefix),
+        );
+        assert_eq!(lost_and_found.indexed_amount(Asset::Linear), 0);
+
+        // inserting indexes the whole balance of the account
+        lost_and_found.insert(Asset::Linear, &alice(), 5);
+        lost_and_found.insert(Asset::Linear, &charlie, 10);
+        assert_eq!(lost_and_found.indexed_amount(Asset::Linear), 115);
+
+        lost_and_found.index(Asset::Linear, &[bob.clone(), bob.clone(), alice()]);
+        assert_eq!(lost_and_found.indexed_amount(Asset::Linear), 315);
+
+        lost_and_found.remove(Asset::Linear, &bob);
+        assert_eq!(lost_and_found.indexed_amount(Asset::Linear), 115);
+        assert_eq!(lost_and_found.total_amount(Asset::Linear), 115);
+    }
+
+    #[test]
+    fn test_paginated_entries() {
+        let bob = AccountId::new_unchecked("bob".into());
+        let charlie = AccountId::new_unchecked("charlie".into());
+        let mut lost_and_found = LostAndFound::new();
+
+        lost_and_found.insert(Asset::Linear, &alice(), 100);
+        lost_and_found.insert(Asset::Linear, &bob, 300);
+        lost_and_found.insert(Asset::Linear, &charlie, 200);
+        lost_and_found.insert(Asset::Linear, &alice(), 50);
+        lost_and_found.insert(Asset::Near, &alice(), 1000);
+
+        let linear = lost_and_found.asset(Asset::Linear);
+        assert_eq!(
+            linear.entries(None, 10),
+            vec![(bob.clone(), 300), (charlie.clone(), 200), (alice(), 150)]
+        );
+        assert_eq!(linear.entries(None, 1), vec![(bob.clone(), 300)]);
+        assert_eq!(
+            linear.entries(Some((300, bob.clone())), 1),
+            vec![(charlie.clone(), 200)]
+        );
+        assert!(linear.entries(Some((150, alice())), 10).is_empty());
+
+        // entries are reordered when amount changes
+        lost_and_found.insert(Asset::Linear, &alice(), 200);
+        assert_eq!(
+            lost_and_found.asset(Asset::Linear).entries(None, 10),
+            vec![(alice(), 350), (bob.clone(), 300), (charlie.clone(), 200)]
+        );
+        lost_and_found.remove(Asset::Linear, &alice());
+        assert_eq!(
+            lost_and_found.asset(Asset::Linear).entries(None, 10),
+            vec![(bob, 300), (charlie, 200)]
+        );
+        assert_eq!(lost_and_found.indexed_amount(Asset::Linear), 500);
     }
 }
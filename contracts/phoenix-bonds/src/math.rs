@@ -1,35 +1,34 @@
-use crate::{
-    types::{Timestamp, ONE_PNEAR},
-    utils::linear2near,
-    *,
-};
-use near_bigdecimal::*;
+use phoenix_math::{accrued_amount, Pools};
+
+use crate::*;
 
 impl PhoenixBonds {
-    pub(crate) fn reserve_pool_near_amount(&self, linear_price: Balance) -> Balance {
-        let protocol_owned_near_amount = linear2near(self.linear_balance, linear_price);
-        if protocol_owned_near_amount
-            > self.pending_pool_near_amount
-                + self.permanent_pool_near_amount
-                + self.treasury_pool_near_amount
-        {
-            protocol_owned_near_amount
-                - self.pending_pool_near_amount
-                - self.permanent_pool_near_amount
-                - self.treasury_pool_near_amount
-        } else {
-            0_u128
+    /// Current pool accounting, including pNEAR total supply
+    pub(crate) fn pools(&self) -> Pools {
+        Pools {
+            linear_balance: self.linear_balance,
+            pending_pool_near_amount: self.pending_pool_near_amount,
+            permanent_pool_near_amount: self.permanent_pool_near_amount,
+            treasury_pool_near_amount: self.treasury_pool_near_amount,
+            pnear_total_supply: self.pnear_total_supply(),
         }
     }
 
-    pub(crate) fn pnear_price(&self, linear_price: Balance) -> Balance {
-        if self.pnear_total_supply() == 0 {
-            return ONE_PNEAR;
-        }
+    /// Save pool accounting. pNEAR total supply is not saved here,
+    /// and should be updated by minting or burning pNEAR.
+    pub(crate) fn save_pools(&mut self, pools: Pools) {
+        self.linear_balance = pools.linear_balance;
+        self.pending_pool_near_amount = pools.pending_pool_near_amount;
+        self.permanent_pool_near_amount = pools.permanent_pool_near_amount;
+        self.treasury_pool_near_amount = pools.treasury_pool_near_amount;
+    }
 
-        (BigDecimal::from(self.reserve_pool_near_amount(linear_price)) * ONE_PNEAR.into()
-            / self.pnear_total_supply().into())
-        .round_u128()
+    pub(crate) fn reserve_pool_near_amount(&self, linear_price: Balance) -> Balance {
+        self.pools().reserve_pool_near_amount(linear_price)
+    }
+
+    pub(crate) fn pnear_price(&self, linear_price: Balance) -> Balance {
+        self.pools().pnear_price(linear_price)
     }
 
     pub(crate) fn accrued_amount(
@@ -38,19 +37,18 @@ impl PhoenixBonds {
         length: Duration,
         current_timestamp: Timestamp,
     ) -> Balance {
-        let alpha = self.accrual_param.current_alpha(current_timestamp);
-        (BigDecimal::from(value) * length.into() / (length + alpha).into()).round_u128()
+        accrued_amount(
+            value,
+            length,
+            self.accrual_param.current_alpha(current_timestamp),
+        )
     }
 
     /// Cap of pNEAR that a bond note is worth.
     /// Note that it's meaningless to call this on a committed/cancelled note.
     pub(crate) fn note_cap(&self, note: &BondNote, linear_price: Balance) -> Balance {
-        let bond_amount = note.bond_amount();
-        let amount_to_treasury = apply_basis_point(bond_amount, self.tau);
-        near2pnear(
-            bond_amount - amount_to_treasury,
-            self.pnear_price(linear_price),
-        )
+        self.pools()
+            .note_cap(note.bond_amount(), self.current_tau(), linear_price)
     }
 
     /// How many pNEAR can a bond note get if committed now
@@ -2,6 +2,7 @@ use crate::*;
 use near_sdk::near_bindgen;
 use serde::Serialize;
 
+#[allow(dead_code)]
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Standard {
@@ -13,6 +14,7 @@ pub struct Standard {
 /// and 3rd party, we adopt [NEP-330 standard](https://github.com/near/NEPs/blob/master/neps/nep-0330.md)
 /// to make contract source metadata (including versions, source code links and implemented standards)
 /// available to auditors, developers and users.
+#[allow(dead_code)]
 #[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct ContractSourceMetadata {
@@ -21,6 +23,7 @@ pub struct ContractSourceMetadata {
     pub standards: Vec<Standard>,
 }
 
+#[allow(dead_code)]
 pub trait ContractSourceMetadataTrait {
     fn contract_source_metadata(&self) -> ContractSourceMetadata;
 }
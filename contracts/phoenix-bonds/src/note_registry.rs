@@ -0,0 +1,616 @@
+use crate::{
+    active_vector::{Active, ActiveVector},
+    bond_note::{BondNoteInfo, SettledNotes},
+    *,
+};
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    near_bindgen,
+    serde::Serialize,
+    store::LookupMap,
+    PanicOnDefault,
+};
+use std::cmp::min;
+
+const ERR_LEGACY_INDEXING_CLOSED: &str =
+    "Legacy notes can only be indexed before new notes are registered";
+const ERR_NOT_LEGACY_NOTE: &str = "Not a legacy note that is left to index";
+const ERR_LEGACY_NOTE_ORDER: &str = "Legacy notes must be indexed in creation order";
+
+/// Reference to a bond note of a user
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct NoteRef {
+    account_id: AccountId,
+    note_id: u32,
+    pending: bool,
+}
+
+impl Active for NoteRef {
+    fn is_active(&self) -> bool {
+        self.pending
+    }
+}
+
+/// Registry record of a user
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct UserRegistry {
+    /// number of notes created before the registry was introduced,
+    /// which are registered only by `index_bond_notes`
+    legacy_notes: u32,
+    /// legacy notes before this id were either registered or pruned
+    legacy_indexed: u32,
+    /// totals of registered notes that were settled
+    settled: SettledNotes,
+}
+
+/// Global index of bond notes of all users, in the order they were created.
+/// Notes created before the registry was introduced are registered by the owner
+/// in creation order while the contract is paused after migration, and no more
+/// legacy notes can be registered once a new note is registered.
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct NoteRegistry {
+    /// references to all notes, pending notes are active
+    notes: ActiveVector<NoteRef>,
+    /// global index of each registered note
+    indices: LookupMap<(AccountId, u32), u32>,
+    /// registry record of each user
+    users: LookupMap<AccountId, UserRegistry>,
+    bonders_count: u32,
+    created_count: u64,
+    committed_count: u64,
+    cancelled_count: u64,
+    /// if a new note was registered, after which legacy notes can't be registered
+    legacy_indexing_closed: bool,
+    /// creation time of the last registered legacy note
+    last_legacy_created_at: Timestamp,
+    /// bond amount of pending legacy notes that are not yet indexed
+    legacy_pending_amount: Balance,
+}
+
+impl NoteRegistry {
+    pub fn new() -> Self {
+        Self::with_legacy_pending_amount(0)
+    }
+
+    /// Registry of a contract with pending notes created before it,
+    /// which are expected to be indexed by `index_bond_notes`
+    pub fn with_legacy_pending_amount(legacy_pending_amount: Balance) -> Self {
+        Self {
+            notes: ActiveVector::new(StorageKey::NoteRegistry),
+            indices: LookupMap::new(StorageKey::NoteRegistryIndices),
+            users: LookupMap::new(StorageKey::NoteRegistryUsers),
+            bonders_count: 0,
+            created_count: 0,
+            committed_count: 0,
+            cancelled_count: 0,
+            legacy_indexing_closed: false,
+            last_legacy_created_at: 0,
+            legacy_pending_amount,
+        }
+    }
+
+    pub fn bonders_count(&self) -> u32 {
+        self.bonders_count
+    }
+
+    pub fn created_count(&self) -> u64 {
+        self.created_count
+    }
+
+    pub fn committed_count(&self) -> u64 {
+        self.committed_count
+    }
+
+    pub fn cancelled_count(&self) -> u64 {
+        self.cancelled_count
+    }
+
+    pub fn legacy_pending_amount(&self) -> Balance {
+        self.legacy_pending_amount
+    }
+
+    /// If all pending legacy notes are indexed. Settled legacy notes
+    /// left unindexed must be pruned, so they don't affect pending notes.
+    pub fn legacy_indexing_done(&self) -> bool {
+        self.legacy_pending_amount == 0
+    }
+
+    /// Take the registry record of a user out to update it. A new record
+    /// treats the first `legacy_notes` notes of the user as legacy notes.
+    fn take_user(&mut self, account_id: &AccountId, legacy_notes: u32) -> UserRegistry {
+        self.users.remove(account_id).unwrap_or_else(|| {
+            self.bonders_count += 1;
+            UserRegistry {
+                legacy_notes,
+                legacy_indexed: 0,
+                settled: SettledNotes::default(),
+            }
+        })
+    }
+
+    fn count_settled(&mut self, user: &mut UserRegistry, note: &BondNote) {
+        match note.status() {
+            BondStatus::Committed => self.committed_count += 1,
+            BondStatus::Cancelled => self.cancelled_count += 1,
+            BondStatus::Pending => {}
+        }
+        user.settled.add(note);
+    }
+
+    /// Register a newly created note. Notes of the user created before it
+    /// are legacy notes if the user is not known to the registry yet.
+    pub fn register(&mut self, account_id: &AccountId, note: &BondNote) {
+        self.legacy_indexing_closed = true;
+        let mut user = self.take_user(account_id, note.id());
+        self.append(account_id, &mut user, note);
+        self.users.insert(account_id.clone(), user);
+    }
+
+    fn append(&mut self, account_id: &AccountId, user: &mut UserRegistry, note: &BondNote) {
+        let index = self.notes.append(NoteRef {
+            account_id: account_id.clone(),
+            note_id: note.id(),
+            pending: note.status() == BondStatus::Pending,
+        });
+        self.indices.insert((account_id.clone(), note.id()), index);
+
+        self.created_count += 1;
+        self.count_settled(user, note);
+    }
+
+    pub fn settle(&mut self, account_id: &AccountId, note: &BondNote) {
+        if let Some(index) = self.indices.get(&(account_id.clone(), note.id())).copied() {
+            self.notes.update(
+                index,
+                NoteRef {
+                    account_id: account_id.clone(),
+                    note_id: note.id(),
+                    pending: false,
+                },
+            );
+            let mut user = self.take_user(account_id, note.id());
+            self.count_settled(&mut user, note);
+            self.users.insert(account_id.clone(), user);
+        }
+    }
+
+    /// Remove a pruned note from the registry, counters are kept.
+    /// Returns false if the note is not registered.
+    fn remove(&mut self, account_id: &AccountId, note_id: u32) -> bool {
+        match self.indices.remove(&(account_id.clone(), note_id)) {
+            Some(index) => {
+                self.notes.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn get_refs(&self, from_index: u32, limit: u32) -> Vec<(u32, &NoteRef)> {
+        (from_index..min(self.notes.len(), from_index.saturating_add(limit)))
+            .filter_map(|index| self.notes.get(index).map(|note_ref| (index, note_ref)))
+            .collect()
+    }
+
+    fn get_pending_refs(&self, from_index: u32, limit: u32) -> Vec<(u32, &NoteRef)> {
+        self.notes
+            .get_active_item_indices(from_index, limit)
+            .into_iter()
+            .filter_map(|index| self.notes.get(index).map(|note_ref| (index, note_ref)))
+            .collect()
+    }
+}
+
+impl PhoenixBonds {
+    /// Register a note of a user that was created before the registry. Legacy notes
+    /// of the user before it must have been registered or pruned, and it must not
+    /// be created earlier than the last registered legacy note.
+    fn index_legacy_note(&mut self, account_id: &AccountId, note_id: u32) {
+        require!(
+            !self.note_registry.legacy_indexing_closed,
+            ERR_LEGACY_INDEXING_CLOSED
+        );
+        let mut user = self
+            .note_registry
+            .take_user(account_id, self.bond_notes.user_note_len(account_id));
+        require!(
+            note_id >= user.legacy_indexed && note_id < user.legacy_notes,
+            ERR_NOT_LEGACY_NOTE
+        );
+        require!(
+            self.bond_notes
+                .get_user_notes(
+                    account_id,
+                    user.legacy_indexed,
+                    note_id - user.legacy_indexed
+                )
+                .is_empty(),
+            ERR_LEGACY_NOTE_ORDER
+        );
+        let note = self.bond_notes.get_user_note(account_id, note_id);
+        require!(
+            note.created_at() >= self.note_registry.last_legacy_created_at,
+            ERR_LEGACY_NOTE_ORDER
+        );
+
+        if note.status() == BondStatus::Pending {
+            self.note_registry.legacy_pending_amount = self
+                .note_registry
+                .legacy_pending_amount
+                .saturating_sub(note.bond_amount());
+        }
+        self.note_registry.last_legacy_created_at = note.created_at();
+        self.note_registry.append(account_id, &mut user, &note);
+        user.legacy_indexed = note_id + 1;
+        self.note_registry.users.insert(account_id.clone(), user);
+    }
+
+    /// Remove a pruned note from the registry. A legacy note that is not indexed
+    /// yet is counted as it would be when indexed, since it will be skipped then.
+    pub(crate) fn unregister_note(&mut self, account_id: &AccountId, note: &BondNote) {
+        if self.note_registry.remove(account_id, note.id()) {
+            return;
+        }
+
+        let mut user = self
+            .note_registry
+            .take_user(account_id, self.bond_notes.user_note_len(account_id));
+        if note.id() >= user.legacy_indexed && note.id() < user.legacy_notes {
+            self.note_registry.created_count += 1;
+            self.note_registry.count_settled(&mut user, note);
+        }
+        self.note_registry.users.insert(account_id.clone(), user);
+    }
+
+    /// Sum of bond amount of pending notes in the registry, starting from `from_index`.
+    /// Also returns the index to continue from if there could be more pending notes.
+    pub(crate) fn pending_notes_amount(
+        &self,
+        from_index: u32,
+        limit: u32,
+    ) -> (Balance, Option<u32>) {
+        let refs = self.note_registry.get_pending_refs(from_index, limit);
+        let next_index = if refs.len() < limit as usize {
+            None
+        } else {
+            Some(refs.last().map_or(from_index, |(index, _)| index + 1))
+        };
+        let amount = refs
+            .into_iter()
+            .map(|(_, note_ref)| {
+                self.bond_notes
+                    .get_user_note(&note_ref.account_id, note_ref.note_id)
+                    .bond_amount()
+            })
+            .sum();
+        (amount, next_index)
+    }
+
+    /// Lifetime totals of settled notes of a user. Legacy notes
+    /// are included only after they are indexed or pruned.
+    pub(crate) fn user_settled_notes(&self, account_id: &AccountId) -> SettledNotes {
+        self.note_registry
+            .users
+            .get(account_id)
+            .map(|u| u.settled.clone())
+            .unwrap_or_default()
+    }
+
+    fn build_global_note_info(
+        &self,
+        global_index: u32,
+        note_ref: &NoteRef,
+        linear_price: Balance,
+    ) -> GlobalNoteInfo {
+        let note = self
+            .bond_notes
+            .get_user_note(&note_ref.account_id, note_ref.note_id);
+        GlobalNoteInfo {
+            global_index,
+            note: self.build_note_info(&note, linear_price),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GlobalNoteInfo {
+    /// index of the note in the global registry, for pagination
+    global_index: u32,
+    #[serde(flatten)]
+    note: BondNoteInfo,
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// List notes of all users in the order they were created, starting from
+    /// `from_index` in the registry. Pruned notes are skipped, so a page could
+    /// contain fewer than `limit` notes.
+    pub fn list_all_notes(
+        &self,
+        linear_price: U128,
+        from_index: u32,
+        limit: u32,
+    ) -> Vec<GlobalNoteInfo> {
+        self.note_registry
+            .get_refs(from_index, limit)
+            .into_iter()
+            .map(|(index, note_ref)| self.build_global_note_info(index, note_ref, linear_price.0))
+            .collect()
+    }
+
+    /// List pending notes of all users in the order they were created, starting
+    /// from `from_index` in the registry. To get the next page, use the last
+    /// `global_index` plus one as `from_index`.
+    pub fn list_all_pending_notes(
+        &self,
+        linear_price: U128,
+        from_index: u32,
+        limit: u32,
+    ) -> Vec<GlobalNoteInfo> {
+        self.note_registry
+            .get_pending_refs(from_index, limit)
+            .into_iter()
+            .map(|(index, note_ref)| self.build_global_note_info(index, note_ref, linear_price.0))
+            .collect()
+    }
+
+    /// Number of accounts that have registered bond notes
+    pub fn bonders_count(&self) -> u32 {
+        self.note_registry.bonders_count()
+    }
+
+    /// Register notes created before the registry, given as `[account_id, note_id]`
+    /// in the order they were created, across batches too. Notes of an account
+    /// that are skipped must have been pruned. This is a pre-launch step,
+    /// which is only allowed before any new note is registered.
+    #[payable]
+    pub fn index_bond_notes(&mut self, notes: Vec<(AccountId, u32)>) {
+        self.assert_owner_with_one_yocto();
+        for (account_id, note_id) in notes.iter() {
+            self.index_legacy_note(account_id, *note_id);
+        }
+
+        Event::BondNotesIndexed {
+            notes,
+            created_count: self.note_registry.created_count(),
+            bonders_count: self.note_registry.bonders_count(),
+        }
+        .emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{
+        test_utils::{self, VMContextBuilder},
+        testing_env, ONE_NEAR,
+    };
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    fn account(name: &str) -> AccountId {
+        AccountId::new_unchecked(name.into())
+    }
+
+    #[test]
+    fn test_register_notes() {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account("alice"))
+            .build());
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+
+        contract.internal_create_bond(account("alice"), ONE_NEAR, ONE_NEAR, None, None);
+        contract.internal_create_bond(account("bob"), ONE_NEAR, ONE_NEAR, None, None);
+        let note = contract.internal_create_bond(account("alice"), ONE_NEAR, ONE_NEAR, None, None);
+        assert_eq!(contract.bonders_count(), 2);
+
+        let mut note = contract
+            .bond_notes
+            .get_user_note(&account("alice"), note.id());
+        note.cancel();
+        contract.save_settled_note(&account("alice"), note.id(), note.clone());
+
+        let registry = &contract.note_registry;
+        assert_eq!(registry.created_count(), 3);
+        assert_eq!(registry.cancelled_count(), 1);
+        assert_eq!(registry.committed_count(), 0);
+
+        let refs: Vec<(String, u32)> = registry
+            .get_pending_refs(0, 10)
+            .into_iter()
+            .map(|(_, r)| (r.account_id.to_string(), r.note_id))
+            .collect();
+        assert_eq!(refs, vec![("alice".into(), 0), ("bob".into(), 0)]);
+        assert_eq!(registry.get_refs(1, 10).len(), 2);
+
+        // pruned notes are no longer listed
+        contract.prune_notes(vec![note.id()]);
+        assert_eq!(contract.note_registry.get_refs(0, 10).len(), 2);
+        assert_eq!(contract.note_registry.created_count(), 3);
+    }
+
+    fn set_context(predecessor: &str, timestamp_ms: Timestamp) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account(predecessor))
+            .attached_deposit(1)
+            .block_timestamp(timestamp_ms * 1_000_000)
+            .build());
+    }
+
+    /// Create notes before the registry, alice's notes 0 to 4 are
+    /// created at 1, 2, 4, 5 and 6, and bob's note 0 at 3
+    fn new_contract_with_legacy_notes() -> PhoenixBonds {
+        set_context("foo", 0);
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+        for (name, timestamp_ms) in [
+            ("alice", 1),
+            ("alice", 2),
+            ("bob", 3),
+            ("alice", 4),
+            ("alice", 5),
+            ("alice", 6),
+        ] {
+            set_context("foo", timestamp_ms);
+            contract
+                .bond_notes
+                .insert_new_note(&account(name), ONE_NEAR, None);
+        }
+        contract
+    }
+
+    #[test]
+    fn test_index_legacy_notes() {
+        let mut contract = new_contract_with_legacy_notes();
+        for note_id in [1, 2] {
+            let mut note = contract
+                .bond_notes
+                .get_user_note(&account("alice"), note_id);
+            match note_id {
+                1 => note.commit(ONE_NEAR),
+                _ => note.cancel(),
+            }
+            contract
+                .bond_notes
+                .save_user_note(&account("alice"), note_id, note);
+        }
+
+        // pruned legacy notes are counted, since they won't be indexed
+        set_context("alice", 7);
+        contract.prune_notes(vec![2]);
+        assert_eq!(contract.note_registry.created_count(), 1);
+        assert_eq!(contract.note_registry.cancelled_count(), 1);
+
+        // notes of all accounts are indexed in creation order, in batches
+        set_context("foo", 7);
+        contract.index_bond_notes(vec![(account("alice"), 0), (account("alice"), 1)]);
+        contract.index_bond_notes(vec![
+            (account("bob"), 0),
+            (account("alice"), 3),
+            (account("alice"), 4),
+        ]);
+        let log = test_utils::get_logs().pop().unwrap();
+        assert!(log.contains(r#""event":"bond_notes_indexed""#), "{}", log);
+        assert!(
+            log.contains(r#""created_count":6,"bonders_count":2"#),
+            "{}",
+            log
+        );
+
+        let registry = &contract.note_registry;
+        assert_eq!(registry.bonders_count(), 2);
+        assert_eq!(registry.created_count(), 6);
+        assert_eq!(registry.committed_count(), 1);
+        assert_eq!(registry.cancelled_count(), 1);
+        let settled = contract.user_settled_notes(&account("alice"));
+        assert_eq!(settled.committed_count, 1);
+        assert_eq!(settled.cancelled_count, 1);
+
+        // new notes are registered after legacy notes
+        let note = contract.internal_create_bond(account("bob"), ONE_NEAR, ONE_NEAR, None, None);
+        assert_eq!(note.id(), 1);
+        let registry = &contract.note_registry;
+        let notes: Vec<(String, u32)> = registry
+            .get_refs(0, 10)
+            .into_iter()
+            .map(|(_, r)| (r.account_id.to_string(), r.note_id))
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                ("alice".into(), 0),
+                ("alice".into(), 1),
+                ("bob".into(), 0),
+                ("alice".into(), 3),
+                ("alice".into(), 4),
+                ("bob".into(), 1),
+            ]
+        );
+        let pending: Vec<u32> = registry
+            .get_pending_refs(0, 10)
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(pending, vec![0, 2, 3, 4, 5]);
+    }
+
+    /// Contract migrated with pending legacy notes and paused
+    fn migrated_contract_with_legacy_notes() -> PhoenixBonds {
+        let mut contract = new_contract_with_legacy_notes();
+        contract.pending_pool_near_amount = 6 * ONE_NEAR;
+        contract.accrual_weighted_mean_insert(6 * ONE_NEAR, 0);
+        contract.note_registry = NoteRegistry::with_legacy_pending_amount(6 * ONE_NEAR);
+        contract.paused = true;
+        contract
+    }
+
+    #[test]
+    fn test_check_invariants_while_indexing() {
+        let mut contract = migrated_contract_with_legacy_notes();
+        set_context("foo", 7);
+        contract.index_bond_notes(vec![(account("alice"), 0), (account("alice"), 1)]);
+        assert!(!contract.note_registry.legacy_indexing_done());
+
+        // legacy notes that are not indexed yet are counted as a whole
+        let report = contract.check_invariants(None, Some(10), Some(true));
+        let report = serde_json::to_string(&report).unwrap();
+        assert!(
+            report.contains(&format!(
+                "all pending notes: {}, legacy pending notes: {}, pending pool: {}",
+                2 * ONE_NEAR,
+                4 * ONE_NEAR,
+                6 * ONE_NEAR
+            )),
+            "{}",
+            report
+        );
+
+        contract.index_bond_notes(vec![
+            (account("bob"), 0),
+            (account("alice"), 2),
+            (account("alice"), 3),
+            (account("alice"), 4),
+        ]);
+        assert!(contract.note_registry.legacy_indexing_done());
+        contract.resume();
+        contract.check_invariants(None, Some(10), Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "Pending legacy notes must be indexed before resuming")]
+    fn test_resume_while_indexing() {
+        let mut contract = migrated_contract_with_legacy_notes();
+        set_context("foo", 7);
+        contract.index_bond_notes(vec![(account("alice"), 0), (account("alice"), 1)]);
+        contract.resume();
+    }
+
+    #[test]
+    #[should_panic(expected = "Legacy notes must be indexed in creation order")]
+    fn test_index_legacy_notes_out_of_order() {
+        let mut contract = new_contract_with_legacy_notes();
+        set_context("foo", 7);
+        contract.index_bond_notes(vec![(account("alice"), 0), (account("alice"), 1)]);
+        // alice's note 2 is created after bob's note 0
+        contract.index_bond_notes(vec![(account("alice"), 2), (account("bob"), 0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Legacy notes must be indexed in creation order")]
+    fn test_index_legacy_notes_skip_unpruned() {
+        let mut contract = new_contract_with_legacy_notes();
+        set_context("foo", 7);
+        contract.index_bond_notes(vec![(account("alice"), 0), (account("alice"), 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Legacy notes can only be indexed before new notes are registered")]
+    fn test_index_legacy_notes_after_new_note() {
+        let mut contract = new_contract_with_legacy_notes();
+        set_context("foo", 7);
+        contract.internal_create_bond(account("alice"), ONE_NEAR, ONE_NEAR, None, None);
+        contract.index_bond_notes(vec![(account("alice"), 0)]);
+    }
+}
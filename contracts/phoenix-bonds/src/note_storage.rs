@@ -0,0 +1,395 @@
+use crate::{bond_note::SettledNotes, *};
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    collections::LookupMap,
+    near_bindgen,
+    serde::Serialize,
+    StorageUsage,
+};
+
+const ERR_NOTE_NOT_SETTLED: &str = "Only committed or cancelled notes can be pruned";
+const ERR_NOTHING_TO_WITHDRAW: &str = "No storage deposit to withdraw";
+const ERR_WITHDRAW_TOO_MUCH: &str = "Not enough storage deposit to withdraw";
+const ERR_NOTES_PENDING: &str = "Storage deposit cannot be withdrawn while notes are pending";
+
+/// Storage used by bond notes of a user
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct UserNoteStorage {
+    /// NEAR deposited for storing bond notes
+    deposit: Balance,
+    /// bytes used by bond notes, measured when notes are created,
+    /// and by this record itself
+    used_bytes: StorageUsage,
+    /// number of notes created before storage was measured. Their storage is not
+    /// counted, and no deposit is released when they are pruned, since notes
+    /// bonded with LiNEAR kept the storage deposit as LiNEAR.
+    legacy_notes: u32,
+    /// notes that were pruned
+    archived: SettledNotes,
+}
+
+impl UserNoteStorage {
+    fn is_legacy(&self, note_id: u32) -> bool {
+        note_id < self.legacy_notes
+    }
+
+    fn available(&self) -> Balance {
+        self.deposit
+            .saturating_sub(Balance::from(self.used_bytes) * env::storage_byte_cost())
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct NoteStorage {
+    users: LookupMap<AccountId, UserNoteStorage>,
+}
+
+impl NoteStorage {
+    pub fn new() -> Self {
+        Self {
+            users: LookupMap::new(StorageKey::NoteStorage),
+        }
+    }
+}
+
+impl PhoenixBonds {
+    fn user_note_storage(&self, account_id: &AccountId) -> UserNoteStorage {
+        self.note_storage
+            .users
+            .get(account_id)
+            .unwrap_or_else(|| UserNoteStorage {
+                // notes created before storage accounting was introduced
+                legacy_notes: self.bond_notes.user_note_len(account_id),
+                ..Default::default()
+            })
+    }
+
+    /// Save the storage record of a user. The record has a fixed size,
+    /// which is added to its used bytes when it's created.
+    fn save_user_note_storage(&mut self, account_id: &AccountId, storage: &mut UserNoteStorage) {
+        let storage_before = env::storage_usage();
+        self.note_storage.users.insert(account_id, storage);
+        let record_bytes = env::storage_usage().saturating_sub(storage_before);
+        if record_bytes > 0 {
+            storage.used_bytes += record_bytes;
+            self.note_storage.users.insert(account_id, storage);
+        }
+    }
+
+    /// Storage deposit of a user that can be withdrawn. Settling a pending note
+    /// may use storage that's not yet measured, so none can be withdrawn
+    /// while the user has pending notes.
+    fn withdrawable_note_storage(
+        &self,
+        account_id: &AccountId,
+        storage: &UserNoteStorage,
+    ) -> Balance {
+        if self.bond_notes.user_pending_note_len(account_id) > 0 {
+            return 0;
+        }
+        storage.available()
+    }
+
+    /// Record NEAR attached for storing bond notes of a user.
+    /// Only NEAR actually held by this contract should be deposited, since it can be
+    /// withdrawn once the notes are pruned.
+    pub(crate) fn deposit_note_storage(&mut self, account_id: &AccountId, amount: Balance) {
+        let mut storage = self.user_note_storage(account_id);
+        storage.deposit += amount;
+        self.save_user_note_storage(account_id, &mut storage);
+    }
+
+    /// Create a new note, and record its storage usage.
+    /// The storage deposit, if any, should be recorded by `deposit_note_storage`.
+    pub(crate) fn insert_new_note_with_storage(
+        &mut self,
+        account_id: &AccountId,
+        bond_amount: Balance,
+        referrer_id: Option<AccountId>,
+    ) -> BondNote {
+        let mut storage = self.user_note_storage(account_id);
+
+        let storage_before = env::storage_usage();
+        let note = self
+            .bond_notes
+            .insert_new_note(account_id, bond_amount, referrer_id);
+        self.note_registry.register(account_id, &note);
+        let storage_after = env::storage_usage();
+
+        storage.used_bytes += storage_after.saturating_sub(storage_before);
+        self.save_user_note_storage(account_id, &mut storage);
+
+        note
+    }
+
+    /// Save a settled note, and release storage that's no longer used
+    pub(crate) fn save_settled_note(
+        &mut self,
+        account_id: &AccountId,
+        note_id: u32,
+        note: BondNote,
+    ) {
+        let storage_before = env::storage_usage();
+        self.note_registry.settle(account_id, &note);
+        self.bond_notes.save_user_note(account_id, note_id, note);
+        let released_bytes = storage_before.saturating_sub(env::storage_usage());
+
+        let mut storage = self.user_note_storage(account_id);
+        if !storage.is_legacy(note_id) {
+            storage.used_bytes = storage.used_bytes.saturating_sub(released_bytes);
+            self.save_user_note_storage(account_id, &mut storage);
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NoteStorageBalance {
+    pub deposit: U128,
+    pub used_bytes: StorageUsage,
+    pub available: U128,
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    pub fn note_storage_balance_of(&self, account_id: AccountId) -> NoteStorageBalance {
+        let storage = self.user_note_storage(&account_id);
+        NoteStorageBalance {
+            deposit: storage.deposit.into(),
+            used_bytes: storage.used_bytes,
+            available: self.withdrawable_note_storage(&account_id, &storage).into(),
+        }
+    }
+
+    pub fn archived_notes(&self, account_id: AccountId) -> SettledNotes {
+        self.user_note_storage(&account_id).archived
+    }
+
+    /// Remove committed or cancelled notes, and archive them into a compact record.
+    /// Storage deposit of these notes can be withdrawn afterwards, except for notes
+    /// created before storage was measured.
+    pub fn prune_notes(&mut self, note_ids: Vec<u32>) {
+        require!(!self.paused, ERR_PAUSED);
+
+        let user_id = env::predecessor_account_id();
+        let mut storage = self.user_note_storage(&user_id);
+
+        for note_id in note_ids.iter() {
+            let note = self.bond_notes.get_user_note(&user_id, *note_id);
+            require!(note.status() != BondStatus::Pending, ERR_NOTE_NOT_SETTLED);
+
+            let storage_before = env::storage_usage();
+            self.bond_notes.remove_user_note(&user_id, *note_id);
+            self.unregister_note(&user_id, &note);
+            let released_bytes = storage_before.saturating_sub(env::storage_usage());
+
+            if !storage.is_legacy(*note_id) {
+                storage.used_bytes = storage.used_bytes.saturating_sub(released_bytes);
+            }
+            storage.archived.add(&note);
+        }
+
+        self.save_user_note_storage(&user_id, &mut storage);
+
+        Event::NotesPruned {
+            account_id: user_id,
+            note_ids,
+        }
+        .emit();
+    }
+
+    /// Withdraw storage deposit that's not used by bond notes, once all notes
+    /// are settled. Withdraw all available deposit if amount is not given.
+    #[payable]
+    pub fn note_storage_withdraw(&mut self, amount: Option<U128>) -> Promise {
+        assert_one_yocto();
+        // 30 Tgas
+        require!(
+            env::prepaid_gas() >= GAS_CLAIM + GAS_NEAR_TRANSFER_CALLBACK,
+            ERR_NOT_ENOUGH_GAS
+        );
+        require!(!self.paused, ERR_PAUSED);
+
+        let user_id = env::predecessor_account_id();
+        require!(
+            self.bond_notes.user_pending_note_len(&user_id) == 0,
+            ERR_NOTES_PENDING
+        );
+        let mut storage = self.user_note_storage(&user_id);
+        let available = self.withdrawable_note_storage(&user_id, &storage);
+        let amount = amount.map(|a| a.0).unwrap_or(available);
+        require!(amount > 0, ERR_NOTHING_TO_WITHDRAW);
+        require!(amount <= available, ERR_WITHDRAW_TOO_MUCH);
+
+        storage.deposit -= amount;
+        self.save_user_note_storage(&user_id, &mut storage);
+
+        Event::NoteStorageWithdrawn {
+            account_id: user_id.clone(),
+            amount: amount.into(),
+        }
+        .emit();
+
+        self.transfer_near(&user_id, amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, Gas, ONE_NEAR};
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    fn alice() -> AccountId {
+        AccountId::new_unchecked("alice".into())
+    }
+
+    #[test]
+    fn test_prune_settled_note() {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice())
+            .build());
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+
+        let note_id = contract
+            .on_staked(
+                alice(),
+                U128(100 * ONE_NEAR),
+                None,
+                Ok(U128(100 * ONE_NEAR)),
+            )
+            .unwrap();
+        let storage = contract.user_note_storage(&alice());
+        assert_eq!(storage.deposit, BOND_STORAGE_DEPOSIT);
+        assert!(storage.used_bytes > 0);
+        assert!(storage.available() < BOND_STORAGE_DEPOSIT);
+
+        // settling releases storage of the pending index
+        let used_bytes_before_settle = storage.used_bytes;
+        let mut note = contract.bond_notes.get_user_note(&alice(), note_id);
+        note.cancel();
+        contract.save_settled_note(&alice(), note.id(), note.clone());
+        assert!(contract.user_note_storage(&alice()).used_bytes < used_bytes_before_settle);
+
+        let available_before_prune = contract.user_note_storage(&alice()).available();
+        contract.prune_notes(vec![note.id()]);
+        let storage = contract.user_note_storage(&alice());
+        // only the user's note list is still stored
+        assert!(storage.used_bytes > 0);
+        assert!(storage.available() > available_before_prune);
+        assert_eq!(storage.archived.cancelled_count, 1);
+        assert_eq!(storage.archived.cancelled_bond_amount, 100 * ONE_NEAR);
+
+        // note ids are not reused after pruning
+        let note = contract.internal_create_bond(alice(), ONE_NEAR, ONE_NEAR, None, None);
+        assert_eq!(note.id(), 1);
+        assert_eq!(contract.notes_count(alice()), 2);
+
+        // pruned notes are skipped when listing
+        let notes = contract.list_notes(alice(), U128(ONE_NEAR), 0, 2);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id, 1);
+        assert!(contract
+            .list_notes(alice(), U128(ONE_NEAR), 0, 1)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Storage deposit cannot be withdrawn while notes are pending")]
+    fn test_withdraw_with_pending_notes() {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice())
+            .attached_deposit(1)
+            .prepaid_gas(Gas(300 * TGAS))
+            .build());
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+
+        contract
+            .on_staked(
+                alice(),
+                U128(100 * ONE_NEAR),
+                None,
+                Ok(U128(100 * ONE_NEAR)),
+            )
+            .unwrap();
+        contract.deposit_note_storage(&alice(), ONE_NEAR);
+        assert!(contract.user_note_storage(&alice()).available() > 0);
+        assert_eq!(contract.note_storage_balance_of(alice()).available.0, 0);
+
+        contract.note_storage_withdraw(None);
+    }
+
+    #[test]
+    fn test_record_storage_is_used() {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice())
+            .build());
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+
+        let storage_before = env::storage_usage();
+        contract.deposit_note_storage(&alice(), ONE_NEAR);
+        let record_bytes = env::storage_usage() - storage_before;
+        let storage = contract.user_note_storage(&alice());
+        assert_eq!(storage.used_bytes, record_bytes);
+        assert_eq!(
+            storage.available(),
+            ONE_NEAR - Balance::from(record_bytes) * env::storage_byte_cost()
+        );
+
+        // the record is only counted once
+        contract.deposit_note_storage(&alice(), ONE_NEAR);
+        assert_eq!(
+            contract.user_note_storage(&alice()).used_bytes,
+            record_bytes
+        );
+    }
+
+    #[test]
+    fn test_prune_legacy_note() {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice())
+            .build());
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+
+        // a note created before storage was measured
+        let mut note = contract
+            .bond_notes
+            .insert_new_note(&alice(), ONE_NEAR, None);
+        note.commit(ONE_NEAR);
+        contract.save_settled_note(&alice(), note.id(), note.clone());
+        assert_eq!(contract.user_note_storage(&alice()).legacy_notes, 1);
+
+        contract.prune_notes(vec![note.id()]);
+        let storage = contract.user_note_storage(&alice());
+        // it's unknown whether the note was bonded with NEAR
+        assert_eq!(storage.available(), 0);
+        assert_eq!(storage.archived.committed_count, 1);
+    }
+
+    #[test]
+    fn test_linear_bond_has_no_storage_deposit() {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice())
+            .build());
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+
+        contract.on_get_linear_price_for_linear_bond(
+            alice(),
+            U128(ONE_NEAR),
+            None,
+            Ok(U128(ONE_NEAR)),
+        );
+        let mut note = contract.bond_notes.get_user_note(&alice(), 0);
+        let storage = contract.user_note_storage(&alice());
+        assert_eq!(storage.deposit, 0);
+        assert!(storage.used_bytes > 0);
+
+        // storage deposit of LiNEAR bonds is kept as LiNEAR, not as NEAR
+        note.cancel();
+        contract.save_settled_note(&alice(), 0, note);
+        contract.prune_notes(vec![0]);
+        assert_eq!(contract.user_note_storage(&alice()).available(), 0);
+    }
+}
@@ -8,6 +8,13 @@ impl PhoenixBonds {
     #[payable]
     pub fn change_owner(&mut self, new_owner_id: AccountId) {
         self.assert_owner_with_one_yocto();
+
+        Event::ChangeOwner {
+            old_owner_id: self.owner_id.clone(),
+            new_owner_id: new_owner_id.clone(),
+        }
+        .emit();
+
         self.owner_id = new_owner_id;
     }
 
@@ -15,6 +22,13 @@ impl PhoenixBonds {
     pub fn set_tau(&mut self, new_tau: BasisPoint) {
         assert_tau(new_tau);
         self.assert_owner_with_one_yocto();
+
+        Event::SetTau {
+            old_tau: self.tau,
+            new_tau,
+        }
+        .emit();
+
         self.tau = new_tau;
     }
 
@@ -23,19 +37,29 @@ impl PhoenixBonds {
         self.assert_owner_with_one_yocto();
         require!(!self.paused, "Already paused");
         self.paused = true;
+
+        Event::Pause {}.emit();
     }
 
     #[payable]
     pub fn resume(&mut self) {
         self.assert_owner_with_one_yocto();
         require!(self.paused, "Not paused");
+        // pending notes are checked against the pending pool after resumed
+        require!(
+            self.note_registry.legacy_indexing_done(),
+            "Pending legacy notes must be indexed before resuming"
+        );
         self.paused = false;
+
+        Event::Resume {}.emit();
     }
 
     #[payable]
     pub fn withdraw_treasury(&mut self) -> Promise {
         self.assert_owner_with_one_yocto();
         require!(self.treasury_pool_near_amount > 0, "Nothing to withdraw");
+        self.assert_no_flash_loan();
         require!(
             env::prepaid_gas() >= GAS_WITHDRAW + GAS_WITHDRAW_CALLBACK + GAS_GET_LINEAR_PRICE, // 160 Tgas
             ERR_NOT_ENOUGH_GAS
@@ -53,7 +77,8 @@ impl PhoenixBonds {
         &mut self,
         #[callback_result] linear_price: Result<U128, PromiseError>,
     ) -> Promise {
-        let linear_price = linear_price.expect(ERR_GET_LINEAR_PRICE);
+        let linear_price = self.record_linear_price(linear_price);
+        self.assert_no_flash_loan();
         let near_amount = self.treasury_pool_near_amount;
         require!(near_amount > 0, "Nothing to withdraw");
         // Due to precision, the calculated withdrawn amount can be slightly more than the actual balance,
@@ -65,6 +90,7 @@ impl PhoenixBonds {
 
         self.treasury_pool_near_amount = 0;
         self.linear_balance -= linear_amount;
+        self.on_linear_out(linear_amount);
 
         ext_fungible_token::ext(self.linear_address.clone())
             .with_static_gas(GAS_FT_TRANSFER)
@@ -83,6 +109,7 @@ impl PhoenixBonds {
 
     #[private]
     pub fn on_treasury_withdrawn(&mut self, near_amount: U128, linear_amount: U128) {
+        self.on_linear_out_resolved(linear_amount.0);
         if is_promise_success() {
             Event::TreasuryWithdrawn {
                 near_amount,
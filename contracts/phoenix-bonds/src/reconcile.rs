@@ -0,0 +1,229 @@
+use crate::*;
+use near_sdk::{
+    near_bindgen,
+    serde::{Deserialize, Serialize},
+};
+
+const ERR_GET_LINEAR_BALANCE: &str = "Failed to get LiNEAR balance";
+const ERR_RECONCILE_NOT_PAUSED: &str = "Contract must be paused to move surplus LiNEAR";
+const ERR_LINEAR_IN_FLIGHT: &str =
+    "Cannot move surplus LiNEAR while LiNEAR transfers are in flight";
+
+/// Where LiNEAR surplus should go when reconciling
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum SurplusTarget {
+    Treasury,
+    Reserve,
+}
+
+/// Difference between the LiNEAR this contract records
+/// and the LiNEAR it actually holds
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LinearBalanceDiscrepancy {
+    /// LiNEAR in pools plus lost and found, unclaimed referral LiNEAR
+    /// and LiNEAR in flight
+    recorded_linear: U128,
+    actual_linear: U128,
+    surplus_linear: U128,
+    deficit_linear: U128,
+}
+
+impl PhoenixBonds {
+    /// LiNEAR leaving this contract is deducted from balances but still held
+    /// until resolved, record it as in flight
+    pub(crate) fn on_linear_out(&mut self, amount: Balance) {
+        self.linear_in_flight += amount;
+    }
+
+    /// Resolve LiNEAR recorded by `on_linear_out`, whether it left or came back
+    pub(crate) fn on_linear_out_resolved(&mut self, amount: Balance) {
+        // transfers started before the counter was added are not recorded
+        self.linear_in_flight = self.linear_in_flight.saturating_sub(amount);
+    }
+
+    fn assert_can_move_surplus(&self, target: Option<SurplusTarget>) {
+        if target.is_some() {
+            require!(self.paused, ERR_RECONCILE_NOT_PAUSED);
+            // a failed transfer in flight returns to lost and found, so it
+            // would be counted twice if moved as surplus
+            require!(self.linear_in_flight == 0, ERR_LINEAR_IN_FLIGHT);
+        }
+    }
+
+    fn linear_balance_discrepancy(&self, actual_linear: Balance) -> LinearBalanceDiscrepancy {
+        let recorded_linear = self.linear_balance
+            + self.lost_and_found.total_amount(Asset::Linear)
+            + self.referrals.total_unclaimed_linear()
+            + self.linear_in_flight;
+        LinearBalanceDiscrepancy {
+            recorded_linear: recorded_linear.into(),
+            actual_linear: actual_linear.into(),
+            surplus_linear: actual_linear.saturating_sub(recorded_linear).into(),
+            deficit_linear: recorded_linear.saturating_sub(actual_linear).into(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// Compare recorded LiNEAR with given actual LiNEAR balance of this contract
+    pub fn get_linear_balance_discrepancy(
+        &self,
+        actual_linear_balance: U128,
+    ) -> LinearBalanceDiscrepancy {
+        self.linear_balance_discrepancy(actual_linear_balance.0)
+    }
+
+    /// Query actual LiNEAR balance and report the discrepancy.
+    /// If `target` is given, surplus LiNEAR will be moved into treasury or reserve pool.
+    /// The contract must be paused, with no LiNEAR transfer in flight, to move surplus.
+    #[payable]
+    pub fn reconcile_linear_balance(&mut self, target: Option<SurplusTarget>) -> Promise {
+        self.assert_owner_with_one_yocto();
+        self.assert_can_move_surplus(target);
+        // lent LiNEAR would be seen as a deficit
+        self.assert_no_flash_loan();
+        // 80 Tgas
+        require!(
+            env::prepaid_gas()
+                >= GAS_RECONCILE
+                    + GAS_GET_LINEAR_PRICE
+                    + GAS_FT_BALANCE_OF
+                    + GAS_RECONCILE_CALLBACK,
+            ERR_NOT_ENOUGH_GAS
+        );
+
+        self.get_linear_price()
+            .and(
+                ext_fungible_token::ext(self.linear_address.clone())
+                    .with_static_gas(GAS_FT_BALANCE_OF)
+                    .ft_balance_of(env::current_account_id()),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_RECONCILE_CALLBACK)
+                    .on_get_linear_balance_for_reconcile(target),
+            )
+    }
+
+    #[private]
+    pub fn on_get_linear_balance_for_reconcile(
+        &mut self,
+        target: Option<SurplusTarget>,
+        #[callback_result] linear_price: Result<U128, PromiseError>,
+        #[callback_result] actual_linear: Result<U128, PromiseError>,
+    ) -> LinearBalanceDiscrepancy {
+        let linear_price = self.record_linear_price(linear_price).0;
+        let actual_linear = actual_linear.expect(ERR_GET_LINEAR_BALANCE).0;
+        self.assert_no_flash_loan();
+        self.assert_can_move_surplus(target);
+        let discrepancy = self.linear_balance_discrepancy(actual_linear);
+        let surplus_linear = discrepancy.surplus_linear.0;
+
+        let moved_to = target.filter(|_| surplus_linear > 0);
+        match moved_to {
+            Some(SurplusTarget::Treasury) => {
+                self.linear_balance += surplus_linear;
+                self.treasury_pool_near_amount += linear2near(surplus_linear, linear_price);
+            }
+            Some(SurplusTarget::Reserve) => {
+                // reserve pool owns all LiNEAR not belonging to other pools
+                self.linear_balance += surplus_linear;
+            }
+            None => {}
+        }
+
+        Event::LinearBalanceReconciled {
+            recorded_linear: discrepancy.recorded_linear,
+            actual_linear: discrepancy.actual_linear,
+            surplus_linear: discrepancy.surplus_linear,
+            deficit_linear: discrepancy.deficit_linear,
+            moved_to,
+        }
+        .emit();
+
+        discrepancy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::ONE_NEAR;
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    #[test]
+    fn test_linear_balance_discrepancy() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 1000 * ONE_NEAR, 0, 0, 1, 0);
+        let alice = AccountId::new_unchecked("alice".into());
+        contract
+            .lost_and_found
+            .insert(Asset::Linear, &alice, 10 * ONE_NEAR);
+
+        let discrepancy = contract.linear_balance_discrepancy(1015 * ONE_NEAR);
+        assert_eq!(discrepancy.recorded_linear.0, 1010 * ONE_NEAR);
+        assert_eq!(discrepancy.surplus_linear.0, 5 * ONE_NEAR);
+        assert_eq!(discrepancy.deficit_linear.0, 0);
+
+        let discrepancy = contract.linear_balance_discrepancy(1000 * ONE_NEAR);
+        assert_eq!(discrepancy.surplus_linear.0, 0);
+        assert_eq!(discrepancy.deficit_linear.0, 10 * ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract must be paused to move surplus LiNEAR")]
+    fn test_move_surplus_requires_pause() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 1000 * ONE_NEAR, 0, 0, 1, 0);
+        contract.on_get_linear_balance_for_reconcile(
+            Some(SurplusTarget::Treasury),
+            Ok(U128(ONE_NEAR)),
+            Ok(U128(1010 * ONE_NEAR)),
+        );
+    }
+
+    #[test]
+    fn test_linear_in_flight_is_recorded() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 1000 * ONE_NEAR, 0, 0, 1, 0);
+        contract.linear_balance -= 10 * ONE_NEAR;
+        contract.on_linear_out(10 * ONE_NEAR);
+
+        let discrepancy = contract.linear_balance_discrepancy(1000 * ONE_NEAR);
+        assert_eq!(discrepancy.recorded_linear.0, 1000 * ONE_NEAR);
+        assert_eq!(discrepancy.surplus_linear.0, 0);
+
+        contract.on_linear_out_resolved(10 * ONE_NEAR);
+        assert_eq!(contract.linear_in_flight, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot move surplus LiNEAR while LiNEAR transfers are in flight")]
+    fn test_move_surplus_requires_no_linear_in_flight() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 1000 * ONE_NEAR, 0, 0, 1, 0);
+        contract.paused = true;
+        contract.on_linear_out(ONE_NEAR);
+        contract.on_get_linear_balance_for_reconcile(
+            Some(SurplusTarget::Reserve),
+            Ok(U128(ONE_NEAR)),
+            Ok(U128(1010 * ONE_NEAR)),
+        );
+    }
+
+    #[test]
+    fn test_move_surplus_to_treasury() {
+        let mut contract = new_contract(1000 * ONE_NEAR, 1000 * ONE_NEAR, 0, 0, 1, 0);
+        contract.paused = true;
+        let discrepancy = contract.on_get_linear_balance_for_reconcile(
+            Some(SurplusTarget::Treasury),
+            Ok(U128(ONE_NEAR)),
+            Ok(U128(1010 * ONE_NEAR)),
+        );
+        assert_eq!(discrepancy.surplus_linear.0, 10 * ONE_NEAR);
+        assert_eq!(contract.linear_balance, 1010 * ONE_NEAR);
+        assert_eq!(contract.treasury_pool_near_amount, 10 * ONE_NEAR);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::*;
+use near_contract_standards::fungible_token::FungibleToken;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    near_bindgen,
+    store::LookupMap,
+    PanicOnDefault, StorageUsage,
+};
+use phoenix_math::BigDecimal;
+
+const ERR_INVALID_REDEEM_FEE: &str = "Invalid redeem fee";
+
+/// Max bytes of a `held_since` entry: 40 bytes of record overhead,
+/// 1 byte of prefix, 68 bytes of account ID and 8 bytes of timestamp
+const HELD_SINCE_STORAGE_USAGE: StorageUsage = 117;
+
+/// Make the pNEAR storage deposit of an account also pay for its `held_since` entry.
+/// Accounts registered before pay for it by depositing storage again.
+pub(crate) fn with_held_since_storage(mut ft: FungibleToken) -> FungibleToken {
+    ft.account_storage_usage += HELD_SINCE_STORAGE_USAGE;
+    ft
+}
+
+pub(crate) fn held_since_storage_cost() -> Balance {
+    Balance::from(HELD_SINCE_STORAGE_USAGE) * env::storage_byte_cost()
+}
+
+/// Fee charged on redeemed pNEAR, which stays in the reserve pool
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct RedeemFee {
+    fee: BasisPoint,
+    /// if not zero, the fee decreases linearly to zero
+    /// as pNEAR is held for this long
+    decay_period: Duration,
+    /// weighted average time when pNEAR of each account was received.
+    /// Only accounts that paid for the storage have an entry,
+    /// the others are charged the full fee.
+    held_since: LookupMap<AccountId, Timestamp>,
+}
+
+impl RedeemFee {
+    pub fn new() -> Self {
+        Self {
+            fee: 0,
+            decay_period: 0,
+            held_since: LookupMap::new(StorageKey::PnearHeldSince),
+        }
+    }
+
+    pub fn fee(&self) -> BasisPoint {
+        self.fee
+    }
+
+    pub fn decay_period(&self) -> Duration {
+        self.decay_period
+    }
+
+    /// Fee to redeem pNEAR of given account at `ts`
+    pub fn fee_of(&self, account_id: &AccountId, ts: Timestamp) -> BasisPoint {
+        if self.decay_period == 0 {
+            return self.fee;
+        }
+        let held = match self.held_since.get(account_id) {
+            Some(held_since) => ts.saturating_sub(*held_since),
+            None => return self.fee,
+        };
+        if held >= self.decay_period {
+            return 0;
+        }
+        (u64::from(self.fee) * (self.decay_period - held) / self.decay_period) as BasisPoint
+    }
+
+    pub fn is_held_since_tracked(&self, account_id: &AccountId) -> bool {
+        self.held_since.contains_key(account_id)
+    }
+
+    /// Start tracking how long pNEAR of the account is held, whose
+    /// pNEAR is regarded as received at `ts`
+    pub fn track_held_since(&mut self, account_id: &AccountId, ts: Timestamp) {
+        self.held_since.insert(account_id.clone(), ts);
+    }
+
+    /// Stop tracking the account, returns false if it was not tracked
+    pub fn untrack_held_since(&mut self, account_id: &AccountId) -> bool {
+        self.held_since.remove(account_id).is_some()
+    }
+
+    /// Update when pNEAR was held since, after `amount` pNEAR
+    /// is received on top of `balance` at `ts`.
+    /// Accounts that are not tracked are skipped.
+    fn record_received(
+        &mut self,
+        account_id: &AccountId,
+        balance: Balance,
+        amount: Balance,
+        ts: Timestamp,
+    ) {
+        if amount == 0 {
+            return;
+        }
+        let held_since = match self.held_since.get(account_id) {
+            Some(held_since) => *held_since,
+            None => return,
+        };
+        if balance == 0 {
+            self.held_since.insert(account_id.clone(), ts);
+            return;
+        }
+        let held_since = (BigDecimal::from(balance) * held_since.into()
+            + BigDecimal::from(amount) * ts.into())
+            / (balance + amount).into();
+        self.held_since
+            .insert(account_id.clone(), held_since.round_u128() as Timestamp);
+    }
+}
+
+impl PhoenixBonds {
+    /// Should be called before `amount` pNEAR is deposited to the account
+    pub(crate) fn record_pnear_received(&mut self, account_id: &AccountId, amount: Balance) {
+        let balance = self.ft.accounts.get(account_id).unwrap_or(0);
+        self.redeem_fee
+            .record_received(account_id, balance, amount, current_timestamp_ms());
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// Set redeem fee in basis points. If `decay_period` is given, the fee
+    /// decreases linearly to zero as pNEAR is held for that long.
+    #[payable]
+    pub fn set_redeem_fee(&mut self, new_fee: BasisPoint, decay_period: Option<Duration>) {
+        self.assert_owner_with_one_yocto();
+        require!(new_fee < FULL_BASIS_POINT, ERR_INVALID_REDEEM_FEE);
+        let new_decay_period = decay_period.unwrap_or(0);
+
+        Event::SetRedeemFee {
+            old_fee: self.redeem_fee.fee,
+            new_fee,
+            old_decay_period: self.redeem_fee.decay_period,
+            new_decay_period,
+        }
+        .emit();
+
+        self.redeem_fee.fee = new_fee;
+        self.redeem_fee.decay_period = new_decay_period;
+    }
+
+    /// Redeem fee of given account if redeeming now
+    pub fn get_redeem_fee(&self, account_id: AccountId) -> BasisPoint {
+        self.redeem_fee.fee_of(&account_id, current_timestamp_ms())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, ONE_NEAR};
+
+    use crate::{tests::new_contract, utils::tests::ONE_DAY_MS};
+
+    use super::*;
+
+    fn alice() -> AccountId {
+        AccountId::new_unchecked("alice".into())
+    }
+
+    #[test]
+    fn test_flat_fee() {
+        let mut redeem_fee = RedeemFee::new();
+        redeem_fee.fee = 50;
+        assert_eq!(redeem_fee.fee_of(&alice(), 0), 50);
+    }
+
+    #[test]
+    fn test_decaying_fee() {
+        let mut redeem_fee = RedeemFee::new();
+        redeem_fee.fee = 100;
+        redeem_fee.decay_period = 10 * ONE_DAY_MS;
+
+        redeem_fee.track_held_since(&alice(), 0);
+        redeem_fee.record_received(&alice(), 0, 100 * ONE_NEAR, 0);
+        assert_eq!(redeem_fee.fee_of(&alice(), 0), 100);
+        assert_eq!(redeem_fee.fee_of(&alice(), 4 * ONE_DAY_MS), 60);
+        assert_eq!(redeem_fee.fee_of(&alice(), 10 * ONE_DAY_MS), 0);
+
+        // receiving the same amount 4 days later moves holding time to 2 days ago
+        redeem_fee.record_received(&alice(), 100 * ONE_NEAR, 100 * ONE_NEAR, 4 * ONE_DAY_MS);
+        assert_eq!(
+            *redeem_fee.held_since.get(&alice()).unwrap(),
+            2 * ONE_DAY_MS
+        );
+        assert_eq!(redeem_fee.fee_of(&alice(), 4 * ONE_DAY_MS), 80);
+
+        // holding time restarts once all pNEAR was gone
+        redeem_fee.record_received(&alice(), 0, ONE_NEAR, 20 * ONE_DAY_MS);
+        assert_eq!(redeem_fee.fee_of(&alice(), 20 * ONE_DAY_MS), 100);
+    }
+
+    #[test]
+    fn test_held_since_storage_usage() {
+        let mut redeem_fee = RedeemFee::new();
+        let account_id = AccountId::new_unchecked("a".repeat(64));
+
+        let storage_before = env::storage_usage();
+        redeem_fee.track_held_since(&account_id, 0);
+        redeem_fee.held_since.flush();
+        assert_eq!(
+            env::storage_usage() - storage_before,
+            HELD_SINCE_STORAGE_USAGE
+        );
+    }
+
+    #[test]
+    fn test_holder_without_entry() {
+        let mut redeem_fee = RedeemFee::new();
+        redeem_fee.fee = 100;
+        redeem_fee.decay_period = 10 * ONE_DAY_MS;
+
+        // pNEAR of an account that is not tracked is charged the full fee,
+        // no matter how long it was held
+        assert_eq!(redeem_fee.fee_of(&alice(), 100 * ONE_DAY_MS), 100);
+        redeem_fee.record_received(&alice(), 300 * ONE_NEAR, 100 * ONE_NEAR, 20 * ONE_DAY_MS);
+        assert!(!redeem_fee.is_held_since_tracked(&alice()));
+        assert_eq!(redeem_fee.fee_of(&alice(), 100 * ONE_DAY_MS), 100);
+
+        // once tracked, all of its pNEAR is regarded as received then
+        redeem_fee.track_held_since(&alice(), 20 * ONE_DAY_MS);
+        redeem_fee.record_received(&alice(), 400 * ONE_NEAR, 100 * ONE_NEAR, 25 * ONE_DAY_MS);
+        assert_eq!(
+            *redeem_fee.held_since.get(&alice()).unwrap(),
+            21 * ONE_DAY_MS
+        );
+        assert_eq!(redeem_fee.fee_of(&alice(), 25 * ONE_DAY_MS), 60);
+    }
+
+    #[test]
+    fn test_registered_account_pays_held_since_storage() {
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+        // registered before redeem fee was introduced
+        contract.ft.internal_register_account(&alice());
+        contract.mint_pnear(&alice(), ONE_NEAR, None);
+        assert!(!contract.redeem_fee.is_held_since_tracked(&alice()));
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice())
+            .attached_deposit(held_since_storage_cost())
+            .block_timestamp(ONE_DAY_MS * 1_000_000)
+            .build());
+        contract.storage_deposit(None, None);
+        assert_eq!(
+            contract.redeem_fee.held_since.get(&alice()).copied(),
+            Some(ONE_DAY_MS)
+        );
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice())
+            .attached_deposit(1)
+            .build());
+        assert!(contract.storage_unregister(Some(true)));
+        assert!(!contract.redeem_fee.is_held_since_tracked(&alice()));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The attached deposit is less than the storage of pNEAR holding time"
+    )]
+    fn test_held_since_storage_not_paid() {
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+        contract.ft.internal_register_account(&alice());
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice())
+            .attached_deposit(held_since_storage_cost() - 1)
+            .build());
+        contract.storage_deposit(None, None);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::*;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    near_bindgen,
+    serde::Serialize,
+    store::LookupMap,
+    PanicOnDefault,
+};
+
+const ERR_SELF_REFERRAL: &str = "Cannot refer yourself";
+const ERR_INVALID_REFERRAL_REWARD_RATE: &str = "Invalid referral reward rate";
+const ERR_NO_REFERRAL_REWARDS: &str = "No referral rewards to claim";
+const ERR_REFERRER_NOT_REGISTERED: &str = "Referrer is not registered on pNEAR";
+
+/// Stats of notes referred by an account, only committed notes are counted
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReferrerStats {
+    pub committed_notes: u32,
+    #[serde(with = "u128_dec_format")]
+    pub committed_bond_amount: Balance,
+    /// LiNEAR ever rewarded
+    #[serde(with = "u128_dec_format")]
+    pub total_reward_linear: Balance,
+    /// LiNEAR rewarded but not yet claimed
+    #[serde(with = "u128_dec_format")]
+    pub unclaimed_reward_linear: Balance,
+}
+
+/// Referrers get a share of the treasury cut when referred notes are committed.
+/// Rewards are kept in LiNEAR, which is not part of `linear_balance`.
+///
+/// NOTE: Only referring yourself with the same account is rejected, so a bonder
+/// could refer another account of their own. The reward is effectively a
+/// rebate of the treasury cut to bonders, which `reward_rate` should account for.
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Referrals {
+    /// share of the treasury cut paid to the referrer
+    reward_rate: BasisPoint,
+    total_unclaimed_linear: Balance,
+    referrers: LookupMap<AccountId, ReferrerStats>,
+}
+
+impl Referrals {
+    pub fn new() -> Self {
+        Self {
+            reward_rate: 0,
+            total_unclaimed_linear: 0,
+            referrers: LookupMap::new(StorageKey::Referrers),
+        }
+    }
+
+    pub fn reward_rate(&self) -> BasisPoint {
+        self.reward_rate
+    }
+
+    pub fn total_unclaimed_linear(&self) -> Balance {
+        self.total_unclaimed_linear
+    }
+
+    fn add_committed_note(
+        &mut self,
+        referrer_id: &AccountId,
+        bond_amount: Balance,
+        reward_linear: Balance,
+    ) {
+        let stats = self.referrers.entry(referrer_id.clone()).or_default();
+        stats.committed_notes += 1;
+        stats.committed_bond_amount += bond_amount;
+        stats.total_reward_linear += reward_linear;
+        stats.unclaimed_reward_linear += reward_linear;
+        self.total_unclaimed_linear += reward_linear;
+    }
+
+    fn take_unclaimed(&mut self, referrer_id: &AccountId) -> Balance {
+        let amount = match self.referrers.get_mut(referrer_id) {
+            Some(stats) => std::mem::take(&mut stats.unclaimed_reward_linear),
+            None => 0,
+        };
+        self.total_unclaimed_linear -= amount;
+        amount
+    }
+}
+
+impl PhoenixBonds {
+    /// Referrers must be registered on pNEAR, which pays for their stats
+    pub(crate) fn assert_referrer(&self, user_id: &AccountId, referrer_id: Option<&AccountId>) {
+        if let Some(referrer_id) = referrer_id {
+            require!(referrer_id != user_id, ERR_SELF_REFERRAL);
+            require!(
+                self.ft.accounts.contains_key(referrer_id),
+                ERR_REFERRER_NOT_REGISTERED
+            );
+        }
+    }
+
+    /// Move the referrer's share of the treasury cut of a committed note
+    /// out of treasury pool, and credit it to the referrer in LiNEAR.
+    /// Nothing is rewarded if the referrer has unregistered since the bond.
+    pub(crate) fn reward_referrer(
+        &mut self,
+        referrer_id: &AccountId,
+        user_id: &AccountId,
+        note_id: u32,
+        bond_amount: Balance,
+        tau: BasisPoint,
+        linear_price: Balance,
+    ) {
+        if !self.ft.accounts.contains_key(referrer_id) {
+            return;
+        }
+
+        let amount_for_treasury = apply_basis_point(bond_amount, tau);
+        let near_amount = min(
+            apply_basis_point(amount_for_treasury, self.referrals.reward_rate),
+            self.treasury_pool_near_amount,
+        );
+        // Due to precision, the calculated LiNEAR amount can be slightly more than the actual balance,
+        // use `min` here to avoid subtraction overflow
+        let linear_amount = min(near2linear(near_amount, linear_price), self.linear_balance);
+
+        self.treasury_pool_near_amount -= near_amount;
+        self.linear_balance -= linear_amount;
+        self.referrals
+            .add_committed_note(referrer_id, bond_amount, linear_amount);
+
+        if linear_amount > 0 {
+            Event::ReferralReward {
+                referrer_id: referrer_id.clone(),
+                account_id: user_id.clone(),
+                note_id,
+                near_amount: near_amount.into(),
+                linear_amount: linear_amount.into(),
+            }
+            .emit();
+        }
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// Transfer all unclaimed referral rewards of the caller in LiNEAR.
+    /// If transfer failed, these LiNEAR will be moved to lost and found.
+    pub fn claim_referral_rewards(&mut self) -> Promise {
+        // 100 Tgas
+        require!(
+            env::prepaid_gas() >= GAS_CLAIM + GAS_FT_TRANSFER_AND_CALLBACK,
+            ERR_NOT_ENOUGH_GAS
+        );
+        require!(!self.paused, ERR_PAUSED);
+
+        let referrer_id = env::predecessor_account_id();
+        let linear_amount = self.referrals.take_unclaimed(&referrer_id);
+        require!(linear_amount > 0, ERR_NO_REFERRAL_REWARDS);
+
+        Event::ReferralRewardsClaimed {
+            referrer_id: referrer_id.clone(),
+            linear_amount: linear_amount.into(),
+        }
+        .emit();
+
+        self.transfer_linear(&referrer_id, linear_amount, "Claim referral rewards")
+    }
+
+    /// Set the share of the treasury cut paid to referrers
+    #[payable]
+    pub fn set_referral_reward_rate(&mut self, rate: BasisPoint) {
+        self.assert_owner_with_one_yocto();
+        require!(rate <= FULL_BASIS_POINT, ERR_INVALID_REFERRAL_REWARD_RATE);
+
+        Event::SetReferralRewardRate {
+            old_rate: self.referrals.reward_rate,
+            new_rate: rate,
+        }
+        .emit();
+
+        self.referrals.reward_rate = rate;
+    }
+
+    pub fn get_referral_reward_rate(&self) -> BasisPoint {
+        self.referrals.reward_rate()
+    }
+
+    pub fn get_referrer_stats(&self, account_id: AccountId) -> Option<ReferrerStats> {
+        self.referrals.referrers.get(&account_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, ONE_NEAR};
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    fn account(id: &str) -> AccountId {
+        AccountId::new_unchecked(id.into())
+    }
+
+    #[test]
+    fn test_referrer_rewarded_on_commit() {
+        // tau is 3%
+        let mut contract = new_contract(0, 0, 0, 0, 1, 300);
+        contract.referrals.reward_rate = 1000;
+        contract.ft.internal_register_account(&account("bob"));
+        // after bootstrap ends
+        testing_env!(VMContextBuilder::new().block_timestamp(2_000_000).build());
+
+        let note = contract.internal_create_bond(
+            account("alice"),
+            100 * ONE_NEAR,
+            100 * ONE_NEAR,
+            None,
+            Some(account("bob")),
+        );
+        contract.on_get_linear_price_for_commit(account("alice"), note.id(), Ok(U128(ONE_NEAR)));
+
+        // 10% of the treasury cut goes to bob
+        assert_eq!(contract.treasury_pool_near_amount, 27 * ONE_NEAR / 10);
+        assert_eq!(contract.linear_balance, 100 * ONE_NEAR - 3 * ONE_NEAR / 10);
+        let stats = contract.get_referrer_stats(account("bob")).unwrap();
+        assert_eq!(stats.committed_notes, 1);
+        assert_eq!(stats.committed_bond_amount, 100 * ONE_NEAR);
+        assert_eq!(stats.unclaimed_reward_linear, 3 * ONE_NEAR / 10);
+        assert_eq!(
+            contract.referrals.total_unclaimed_linear(),
+            3 * ONE_NEAR / 10
+        );
+
+        assert_eq!(
+            contract.referrals.take_unclaimed(&account("bob")),
+            3 * ONE_NEAR / 10
+        );
+        assert_eq!(contract.referrals.take_unclaimed(&account("bob")), 0);
+        assert_eq!(contract.referrals.total_unclaimed_linear(), 0);
+        let stats = contract.get_referrer_stats(account("bob")).unwrap();
+        assert_eq!(stats.total_reward_linear, 3 * ONE_NEAR / 10);
+    }
+
+    #[test]
+    fn test_unregistered_referrer_not_rewarded() {
+        let mut contract = new_contract(0, 0, 0, 0, 1, 300);
+        contract.referrals.reward_rate = 1000;
+        testing_env!(VMContextBuilder::new().block_timestamp(2_000_000).build());
+
+        // bob is not registered, e.g. unregistered after the bond
+        let note = contract.internal_create_bond(
+            account("alice"),
+            100 * ONE_NEAR,
+            100 * ONE_NEAR,
+            None,
+            Some(account("bob")),
+        );
+        contract.on_get_linear_price_for_commit(account("alice"), note.id(), Ok(U128(ONE_NEAR)));
+
+        assert_eq!(contract.treasury_pool_near_amount, 3 * ONE_NEAR);
+        assert!(contract.get_referrer_stats(account("bob")).is_none());
+        assert_eq!(contract.referrals.total_unclaimed_linear(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot refer yourself")]
+    fn test_self_referral() {
+        let mut contract = new_contract(0, 0, 0, 0, 1, 300);
+        contract.ft.internal_register_account(&account("bob"));
+        contract.assert_referrer(&account("alice"), Some(&account("bob")));
+        contract.assert_referrer(&account("alice"), None);
+        contract.assert_referrer(&account("alice"), Some(&account("alice")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Referrer is not registered on pNEAR")]
+    fn test_unregistered_referrer() {
+        let contract = new_contract(0, 0, 0, 0, 1, 300);
+        contract.assert_referrer(&account("alice"), Some(&account("bob")));
+    }
+}
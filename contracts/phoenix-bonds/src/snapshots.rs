@@ -0,0 +1,297 @@
+use crate::*;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    near_bindgen,
+    serde::Serialize,
+    store::LookupMap,
+    PanicOnDefault,
+};
+use phoenix_math::{annualized_rate, pnear_price_of};
+use std::cmp::{max, min};
+
+/// Max number of snapshots kept, older ones are overwritten
+pub const MAX_SNAPSHOTS: u32 = 365;
+const DEFAULT_SNAPSHOT_INTERVAL: Duration = 24 * 3600 * 1000; // 1 day
+
+const ERR_INVALID_SNAPSHOT_INTERVAL: &str = "Snapshot interval must be positive";
+
+/// Protocol state at a point of time
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Snapshot {
+    timestamp: Timestamp,
+    #[serde(with = "u128_dec_format")]
+    linear_price: Balance,
+    #[serde(with = "u128_dec_format")]
+    reserve_pool_near_amount: Balance,
+    #[serde(with = "u128_dec_format")]
+    pnear_total_supply: Balance,
+    #[serde(with = "u128_dec_format")]
+    pending_pool_near_amount: Balance,
+    #[serde(with = "u128_dec_format")]
+    permanent_pool_near_amount: Balance,
+    #[serde(with = "u128_dec_format")]
+    treasury_pool_near_amount: Balance,
+    alpha: Duration,
+}
+
+impl Snapshot {
+    fn pnear_price(&self) -> Balance {
+        pnear_price_of(self.reserve_pool_near_amount, self.pnear_total_supply)
+    }
+}
+
+/// Ring buffer of the latest `MAX_SNAPSHOTS` snapshots
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Snapshots {
+    /// the n-th snapshot is kept at slot `n % MAX_SNAPSHOTS`
+    slots: LookupMap<u32, Snapshot>,
+    /// number of snapshots ever recorded
+    count: u32,
+    /// min interval between two snapshots
+    interval: Duration,
+}
+
+impl Snapshots {
+    pub fn new() -> Self {
+        Self {
+            slots: LookupMap::new(StorageKey::Snapshots),
+            count: 0,
+            interval: DEFAULT_SNAPSHOT_INTERVAL,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Index of the oldest snapshot that is still kept
+    fn first_index(&self) -> u32 {
+        self.count.saturating_sub(MAX_SNAPSHOTS)
+    }
+
+    fn get(&self, index: u32) -> Option<&Snapshot> {
+        if index < self.first_index() || index >= self.count {
+            return None;
+        }
+        self.slots.get(&(index % MAX_SNAPSHOTS))
+    }
+
+    fn last(&self) -> Option<&Snapshot> {
+        self.count.checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    /// Record a snapshot if at least `interval` has passed since the last one
+    fn record(&mut self, snapshot: Snapshot) -> bool {
+        if let Some(last) = self.last() {
+            if snapshot.timestamp < last.timestamp + self.interval {
+                return false;
+            }
+        }
+        self.slots.insert(self.count % MAX_SNAPSHOTS, snapshot);
+        self.count += 1;
+        true
+    }
+
+    /// Index of the latest snapshot taken no later than `timestamp`,
+    /// or the oldest snapshot if all were taken later
+    fn index_at(&self, timestamp: Timestamp) -> u32 {
+        let (mut low, mut high) = (self.first_index(), self.count - 1);
+        while low < high {
+            let mid = high - (high - low) / 2;
+            if self.get(mid).unwrap().timestamp <= timestamp {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        low
+    }
+}
+
+impl PhoenixBonds {
+    /// Record a snapshot of current protocol state if it's time to
+    pub(crate) fn record_snapshot(&mut self, linear_price: Balance) {
+        let current_ms = current_timestamp_ms();
+        let snapshot = Snapshot {
+            timestamp: current_ms,
+            linear_price,
+            reserve_pool_near_amount: self.reserve_pool_near_amount(linear_price),
+            pnear_total_supply: self.pnear_total_supply(),
+            pending_pool_near_amount: self.pending_pool_near_amount,
+            permanent_pool_near_amount: self.permanent_pool_near_amount,
+            treasury_pool_near_amount: self.treasury_pool_near_amount,
+            alpha: self.accrual_param.current_alpha(current_ms),
+        };
+        self.snapshots.record(snapshot);
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SnapshotInfo {
+    index: u32,
+    #[serde(flatten)]
+    snapshot: Snapshot,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PnearApy {
+    start_timestamp: Timestamp,
+    end_timestamp: Timestamp,
+    start_pnear_price: U128,
+    end_pnear_price: U128,
+    /// annualized, non-compounded growth of pNEAR price
+    apy: BasisPoint,
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// List snapshots from the oldest, starting from snapshot `from_index`.
+    /// Snapshots that were overwritten are skipped.
+    pub fn get_snapshots(&self, from_index: u32, limit: u32) -> Vec<SnapshotInfo> {
+        let from_index = max(from_index, self.snapshots.first_index());
+        (from_index..min(self.snapshots.count, from_index.saturating_add(limit)))
+            .filter_map(|index| {
+                self.snapshots.get(index).map(|snapshot| SnapshotInfo {
+                    index,
+                    snapshot: snapshot.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// APY of pNEAR derived from the latest snapshot and the snapshot taken
+    /// `window` earlier, or the oldest one if history is shorter than `window`.
+    /// Returns `None` if there are less than two snapshots.
+    pub fn get_pnear_apy(&self, window: Duration) -> Option<PnearApy> {
+        let end = self.snapshots.last()?;
+        let start_index = self
+            .snapshots
+            .index_at(end.timestamp.saturating_sub(window));
+        if start_index + 1 >= self.snapshots.count {
+            return None;
+        }
+        let start = self.snapshots.get(start_index)?;
+
+        Some(PnearApy {
+            start_timestamp: start.timestamp,
+            end_timestamp: end.timestamp,
+            start_pnear_price: start.pnear_price().into(),
+            end_pnear_price: end.pnear_price().into(),
+            apy: annualized_rate(
+                start.pnear_price(),
+                end.pnear_price(),
+                end.timestamp - start.timestamp,
+            ),
+        })
+    }
+
+    #[payable]
+    pub fn set_snapshot_interval(&mut self, new_interval: Duration) {
+        self.assert_owner_with_one_yocto();
+        require!(new_interval > 0, ERR_INVALID_SNAPSHOT_INTERVAL);
+
+        Event::SetSnapshotInterval {
+            old_interval: self.snapshots.interval,
+            new_interval,
+        }
+        .emit();
+
+        self.snapshots.interval = new_interval;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, ONE_NEAR};
+
+    use crate::{tests::new_contract, utils::tests::ONE_DAY_MS};
+
+    use super::*;
+
+    fn set_timestamp_ms(ms: Timestamp) {
+        testing_env!(VMContextBuilder::new()
+            .block_timestamp(ms * 1_000_000)
+            .build());
+    }
+
+    fn snapshot(timestamp: Timestamp, reserve: Balance) -> Snapshot {
+        Snapshot {
+            timestamp,
+            linear_price: ONE_NEAR,
+            reserve_pool_near_amount: reserve,
+            pnear_total_supply: 100 * ONE_NEAR,
+            pending_pool_near_amount: 0,
+            permanent_pool_near_amount: 0,
+            treasury_pool_near_amount: 0,
+            alpha: 1,
+        }
+    }
+
+    #[test]
+    fn test_record_once_per_interval() {
+        let mut contract = new_contract(100 * ONE_NEAR, 0, 0, 0, 1, 0);
+        set_timestamp_ms(ONE_DAY_MS);
+
+        contract.record_snapshot(ONE_NEAR);
+        set_timestamp_ms(ONE_DAY_MS + ONE_DAY_MS / 2);
+        contract.record_snapshot(ONE_NEAR);
+        assert_eq!(contract.get_snapshots(0, 10).len(), 1);
+
+        set_timestamp_ms(2 * ONE_DAY_MS);
+        contract.record_snapshot(ONE_NEAR);
+        let snapshots = contract.get_snapshots(0, 10);
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].index, 1);
+        assert_eq!(snapshots[1].snapshot.timestamp, 2 * ONE_DAY_MS);
+        assert_eq!(
+            snapshots[1].snapshot.reserve_pool_near_amount,
+            100 * ONE_NEAR
+        );
+    }
+
+    #[test]
+    fn test_ring_buffer_overwrites_oldest() {
+        let mut snapshots = Snapshots::new();
+        for day in 0..(MAX_SNAPSHOTS + 10) {
+            assert!(snapshots.record(snapshot(day as u64 * ONE_DAY_MS, ONE_NEAR)));
+        }
+
+        assert_eq!(snapshots.first_index(), 10);
+        assert!(snapshots.get(9).is_none());
+        assert_eq!(snapshots.get(10).unwrap().timestamp, 10 * ONE_DAY_MS);
+        assert_eq!(
+            snapshots.last().unwrap().timestamp,
+            (MAX_SNAPSHOTS + 9) as u64 * ONE_DAY_MS
+        );
+        assert_eq!(snapshots.index_at(100 * ONE_DAY_MS + 1), 100);
+        assert_eq!(snapshots.index_at(0), 10);
+    }
+
+    #[test]
+    fn test_pnear_apy() {
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+        assert!(contract.get_pnear_apy(ONE_DAY_MS).is_none());
+
+        // pNEAR price grows from 1 to 1.1 in a year
+        let year = 365 * ONE_DAY_MS;
+        contract.snapshots.record(snapshot(0, 100 * ONE_NEAR));
+        assert!(contract.get_pnear_apy(year).is_none());
+        contract
+            .snapshots
+            .record(snapshot(year / 2, 105 * ONE_NEAR));
+        contract.snapshots.record(snapshot(year, 110 * ONE_NEAR));
+
+        let apy = contract.get_pnear_apy(year).unwrap();
+        assert_eq!(apy.start_timestamp, 0);
+        assert_eq!(apy.end_pnear_price.0, 11 * ONE_NEAR / 10);
+        assert_eq!(apy.apy, 1000);
+
+        // the last half year
+        let apy = contract.get_pnear_apy(year / 2).unwrap();
+        assert_eq!(apy.start_timestamp, year / 2);
+        assert_eq!(apy.apy, 952);
+    }
+}
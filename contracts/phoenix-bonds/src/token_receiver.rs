@@ -4,16 +4,28 @@ use near_sdk::{near_bindgen, serde::Deserialize, PromiseOrValue};
 
 const MINIMUM_BOND_LINEAR_AMOUNT: u128 = ONE_NEAR / 10 + ONE_NEAR / 100; // 0.11 LiNEAR
 
-const ERR_BAD_MSG: &str = "Unrecognized message";
-const ERR_BAD_TOKEN: &str = "Only LiNEAR token can be used to bond";
+const ERR_BAD_TOKEN: &str = "Only LiNEAR or wNEAR can be used to bond";
+const ERR_BAD_REPAY_TOKEN: &str = "Flash loan must be repaid with LiNEAR";
 const ERR_SMALL_BOND_LINEAR_AMOUNT: &str = "Bond amount must be at least 0.11 LiNEAR";
-const ERR_LINEAR_PRICE: &str = "Failed to get LiNEAR price";
 const ERR_MALFORMED_MESSAGE: &str = "Invalid transfer action message";
 
-#[derive(Deserialize, PartialEq)]
+#[derive(Deserialize, Debug, PartialEq)]
 #[serde(crate = "near_sdk::serde")]
 enum Action {
     Bond,
+    FlashLoanRepay,
+    /// `{"Bond": {"referrer_id": "..."}}`, same as `Bond` but referred by `referrer_id`
+    #[serde(untagged)]
+    ReferredBond {
+        #[serde(rename = "Bond")]
+        bond: BondArgs,
+    },
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+struct BondArgs {
+    referrer_id: Option<AccountId>,
 }
 
 #[near_bindgen]
@@ -25,12 +37,27 @@ impl FungibleTokenReceiver for PhoenixBonds {
         amount: U128,
         msg: String,
     ) -> PromiseOrValue<U128> {
-        require!(env::prepaid_gas() >= GAS_FT_ON_TRANSFER, ERR_NOT_ENOUGH_GAS);
-
         let action = serde_json::from_str::<Action>(&msg).expect(ERR_MALFORMED_MESSAGE);
-        require!(action == Action::Bond, ERR_BAD_MSG);
+        let referrer_id = match action {
+            Action::FlashLoanRepay => {
+                require!(
+                    env::predecessor_account_id() == self.linear_address,
+                    ERR_BAD_REPAY_TOKEN
+                );
+                self.internal_repay_flash_loan(&sender_id, amount.0);
+                return PromiseOrValue::Value(U128(0));
+            }
+            Action::Bond => None,
+            Action::ReferredBond { bond } => bond.referrer_id,
+        };
 
+        self.assert_referrer(&sender_id, referrer_id.as_ref());
         let token_address = env::predecessor_account_id();
+        if self.is_wnear(&token_address) {
+            return self.internal_bond_with_wnear(sender_id, amount.0, referrer_id);
+        }
+
+        require!(env::prepaid_gas() >= GAS_FT_ON_TRANSFER, ERR_NOT_ENOUGH_GAS);
         require!(token_address == self.linear_address, ERR_BAD_TOKEN);
         require!(
             amount.0 >= MINIMUM_BOND_LINEAR_AMOUNT,
@@ -41,7 +68,7 @@ impl FungibleTokenReceiver for PhoenixBonds {
             .then(
                 Self::ext(env::current_account_id())
                     .with_static_gas(GAS_LINEAR_BOND_CALLBACK)
-                    .on_get_linear_price_for_linear_bond(sender_id, amount),
+                    .on_get_linear_price_for_linear_bond(sender_id, amount, referrer_id),
             )
             .into()
     }
@@ -54,16 +81,44 @@ impl PhoenixBonds {
         &mut self,
         user_id: AccountId,
         linear_amount: U128,
+        referrer_id: Option<AccountId>,
         #[callback_result] linear_price: Result<U128, PromiseError>,
     ) -> U128 {
-        let linear_price = linear_price.expect(ERR_LINEAR_PRICE).0;
+        let linear_price = self.record_linear_price(linear_price).0;
         let near_amount = linear2near(linear_amount.0, linear_price);
         let bond_amount = near_amount - BOND_STORAGE_DEPOSIT;
 
         // This guarantees the pNEAR redeem price is consistent after bonding,
         // but it will make some LiNEAR left in the contract balance but not in any of the pools.
-        self.internal_create_bond(user_id, bond_amount, near2linear(bond_amount, linear_price));
+        self.internal_create_bond(
+            user_id,
+            bond_amount,
+            near2linear(bond_amount, linear_price),
+            Some(linear_price),
+            referrer_id,
+        );
 
         U128(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_action() {
+        let parse = |msg: &str| serde_json::from_str::<Action>(msg).unwrap();
+        assert_eq!(parse("\"Bond\""), Action::Bond);
+        assert_eq!(parse("\"FlashLoanRepay\""), Action::FlashLoanRepay);
+        assert_eq!(
+            parse(r#"{"Bond": {"referrer_id": "bob"}}"#),
+            Action::ReferredBond {
+                bond: BondArgs {
+                    referrer_id: Some(AccountId::new_unchecked("bob".into()))
+                }
+            }
+        );
+        assert!(serde_json::from_str::<Action>("\"Redeem\"").is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use near_sdk::{
     borsh::{self, BorshSerialize},
-    AccountId, BorshStorageKey, Gas, ONE_NEAR,
+    AccountId, BorshStorageKey, Gas,
 };
 
 #[derive(BorshSerialize, BorshStorageKey)]
@@ -9,24 +9,29 @@ pub(crate) enum StorageKey {
     BondNotes,
     UserLostFound,
     UserNotes(AccountId),
+    UserNearLostFound,
+    UserPnearLostFound,
+    NoteStorage,
+    NoteRegistry,
+    NoteRegistryIndices,
+    NoteRegistryUsers,
+    Snapshots,
+    FlashLoanReceivers,
+    PnearHeldSince,
+    UnstakeTickets,
+    Referrers,
 }
 
-/// Timestamp in milliseconds
-pub type Timestamp = u64;
-/// Time duration in milliseconds
-pub type Duration = u64;
-
-pub type BasisPoint = u32;
-pub const FULL_BASIS_POINT: u32 = 10000;
+pub use phoenix_math::{BasisPoint, Duration, Timestamp, FULL_BASIS_POINT, ONE_PNEAR};
 
 pub const PNEAR_DECIMALS: u8 = 24;
-pub const ONE_PNEAR: u128 = ONE_NEAR;
 
 // -- Gas
 pub const TGAS: u64 = Gas::ONE_TERA.0;
 
 pub const GAS_BOND: Gas = Gas(20 * TGAS);
 pub const GAS_BOND_CALLBACK: Gas = Gas(50 * TGAS);
+pub const GAS_NEAR_TRANSFER_CALLBACK: Gas = Gas(10 * TGAS);
 pub const GAS_CANCEL: Gas = Gas(20 * TGAS);
 /// 120 Tgas
 pub const GAS_CANCEL_CALLBACK: Gas = Gas(40 * TGAS + GAS_FT_TRANSFER_AND_CALLBACK.0);
@@ -39,15 +44,50 @@ pub const GAS_CLAIM: Gas = Gas(20 * TGAS);
 pub const GAS_WITHDRAW: Gas = Gas(20 * TGAS);
 /// 120 Tgas
 pub const GAS_WITHDRAW_CALLBACK: Gas = Gas(40 * TGAS + GAS_FT_TRANSFER_AND_CALLBACK.0);
+pub const GAS_RECONCILE: Gas = Gas(20 * TGAS);
+pub const GAS_RECONCILE_CALLBACK: Gas = Gas(20 * TGAS);
 /// 90 Tgas
 pub const GAS_FT_ON_TRANSFER: Gas =
     Gas(20 * TGAS + GAS_GET_LINEAR_PRICE.0 + GAS_LINEAR_BOND_CALLBACK.0);
 pub const GAS_LINEAR_BOND_CALLBACK: Gas = Gas(50 * TGAS);
+/// 180 Tgas
+pub const GAS_FT_ON_TRANSFER_WNEAR: Gas =
+    Gas(20 * TGAS + GAS_NEAR_WITHDRAW.0 + GAS_WNEAR_UNWRAP_CALLBACK.0);
+/// 150 Tgas
+pub const GAS_WNEAR_UNWRAP_CALLBACK: Gas =
+    Gas(20 * TGAS + GAS_DEPOSIT_AND_STAKE.0 + GAS_WNEAR_BOND_CALLBACK.0);
+/// 80 Tgas
+pub const GAS_WNEAR_BOND_CALLBACK: Gas =
+    Gas(50 * TGAS + GAS_NEAR_DEPOSIT.0 + GAS_WNEAR_REFUND_CALLBACK.0);
+/// 20 Tgas
+pub const GAS_WNEAR_REFUND_CALLBACK: Gas = Gas(10 * TGAS + GAS_NEAR_TRANSFER_CALLBACK.0);
+
+pub const GAS_FLASH_LOAN: Gas = Gas(10 * TGAS);
+pub const GAS_FLASH_LOAN_TRANSFER: Gas = Gas(150 * TGAS);
+pub const GAS_FLASH_LOAN_CALLBACK: Gas = Gas(20 * TGAS);
+/// 180 Tgas
+pub const GAS_FLASH_LOAN_PRICE_CALLBACK: Gas =
+    Gas(10 * TGAS + GAS_FLASH_LOAN_TRANSFER.0 + GAS_FLASH_LOAN_CALLBACK.0);
 
 pub const GAS_DEPOSIT_AND_STAKE: Gas = Gas(50 * TGAS);
 pub const GAS_GET_LINEAR_PRICE: Gas = Gas(20 * TGAS);
+pub const GAS_NEAR_WITHDRAW: Gas = Gas(10 * TGAS);
+pub const GAS_NEAR_DEPOSIT: Gas = Gas(10 * TGAS);
+
+pub const GAS_UNSTAKE: Gas = Gas(50 * TGAS);
+/// 30 Tgas
+pub const GAS_UNSTAKE_CALLBACK: Gas = Gas(20 * TGAS + GAS_NEAR_TRANSFER_CALLBACK.0);
+pub const GAS_WITHDRAW_UNSTAKED: Gas = Gas(20 * TGAS);
+pub const GAS_LINEAR_WITHDRAW: Gas = Gas(50 * TGAS);
+pub const GAS_INSTANT_UNSTAKE: Gas = Gas(50 * TGAS);
+/// 30 Tgas
+pub const GAS_INSTANT_UNSTAKE_CALLBACK: Gas = Gas(20 * TGAS + GAS_NEAR_TRANSFER_CALLBACK.0);
+/// 30 Tgas
+pub const GAS_WITHDRAW_UNSTAKED_CALLBACK: Gas = Gas(20 * TGAS + GAS_NEAR_TRANSFER_CALLBACK.0);
 
 pub const GAS_FT_TRANSFER: Gas = Gas(50 * TGAS);
 pub const GAS_FT_TRANSFER_CALLBACK: Gas = Gas(30 * TGAS);
+pub const GAS_FT_TRANSFER_CALL: Gas = Gas(100 * TGAS);
+pub const GAS_FT_BALANCE_OF: Gas = Gas(20 * TGAS);
 /// 80 Tgas
 pub const GAS_FT_TRANSFER_AND_CALLBACK: Gas = Gas(GAS_FT_TRANSFER.0 + GAS_FT_TRANSFER_CALLBACK.0);
@@ -0,0 +1,419 @@
+use crate::*;
+use near_sdk::{
+    borsh::{self, BorshDeserialize, BorshSerialize},
+    near_bindgen,
+    serde::Serialize,
+    serde_json::json,
+    store::LookupMap,
+    EpochHeight, PanicOnDefault, PromiseOrValue,
+};
+
+/// LiNEAR releases unstaked NEAR after 4 epochs
+const NUM_EPOCHS_TO_UNLOCK: EpochHeight = 4;
+
+/// Deposit for the storage of an unstake ticket, refunded once it's withdrawn
+pub(crate) const UNSTAKE_TICKET_STORAGE_DEPOSIT: Balance = ONE_NEAR / 500; // 0.002 NEAR
+
+const ERR_INVALID_UNSTAKE_AMOUNT: &str = "Amount of NEAR to unstake must not be zero";
+const ERR_NO_UNSTAKED_NEAR: &str = "No unstaked NEAR to withdraw";
+const ERR_UNSTAKED_NEAR_LOCKED: &str = "Unstaked NEAR is not yet available";
+const ERR_UNSTAKED_NEAR_NOT_RELEASED: &str =
+    "NEAR unstaked in an earlier epoch is still locked in LiNEAR";
+
+/// NEAR unstaked from LiNEAR on behalf of a user
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UnstakeTicket {
+    #[serde(with = "u128_dec_format")]
+    amount: Balance,
+    /// epoch since which the NEAR could be withdrawn
+    unlock_epoch: EpochHeight,
+}
+
+/// LiNEAR keeps all unstaked NEAR of this contract in one account, and each
+/// unstake postpones the unlock of the whole account. So unstakes are grouped
+/// by epoch: NEAR is only unstaked in a new epoch once NEAR unstaked in earlier
+/// epochs is unlocked, which is then released from LiNEAR to this contract first,
+/// so a later unstake never postpones an earlier ticket. A user has at most one
+/// ticket, and its unlock epoch is the earliest epoch to withdraw.
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct UnstakeTickets {
+    total_amount: Balance,
+    /// NEAR unstaked in `unstake_epoch` that's not yet released from LiNEAR,
+    /// the rest of `total_amount` is held by this contract
+    unreleased_amount: Balance,
+    /// epoch of the latest unstake on LiNEAR
+    unstake_epoch: EpochHeight,
+    tickets: LookupMap<AccountId, UnstakeTicket>,
+}
+
+impl UnstakeTickets {
+    pub fn new() -> Self {
+        Self {
+            total_amount: 0,
+            unreleased_amount: 0,
+            unstake_epoch: 0,
+            tickets: LookupMap::new(StorageKey::UnstakeTickets),
+        }
+    }
+
+    pub fn total_amount(&self) -> Balance {
+        self.total_amount
+    }
+
+    pub fn get(&self, user_id: &AccountId) -> Option<&UnstakeTicket> {
+        self.tickets.get(user_id)
+    }
+
+    /// NEAR of tickets that's already released from LiNEAR
+    fn released_amount(&self) -> Balance {
+        self.total_amount - self.unreleased_amount
+    }
+
+    /// Epoch since which unreleased NEAR could be withdrawn from LiNEAR
+    fn unlock_epoch(&self) -> EpochHeight {
+        self.unstake_epoch + NUM_EPOCHS_TO_UNLOCK
+    }
+
+    /// Whether unstaking in `epoch` keeps the unlock of all unreleased NEAR,
+    /// given NEAR that's already unlocked is released first
+    fn can_unstake(&self, epoch: EpochHeight) -> bool {
+        self.unreleased_amount == 0 || epoch == self.unstake_epoch || epoch >= self.unlock_epoch()
+    }
+
+    /// Unreleased NEAR that's unlocked in `epoch` and should be released from LiNEAR
+    fn releasable_amount(&self, epoch: EpochHeight) -> Balance {
+        if epoch >= self.unlock_epoch() {
+            self.unreleased_amount
+        } else {
+            0
+        }
+    }
+
+    /// Record NEAR unstaked on LiNEAR in `epoch` for the user,
+    /// returns the epoch since which the NEAR could be withdrawn
+    fn on_unstaked(
+        &mut self,
+        user_id: &AccountId,
+        amount: Balance,
+        epoch: EpochHeight,
+    ) -> EpochHeight {
+        // LiNEAR postpones all unreleased NEAR, even if an unstake crossed
+        // into a new epoch after it was checked
+        self.unstake_epoch = epoch;
+        self.unreleased_amount += amount;
+        let unlock_epoch = self.unlock_epoch();
+        self.insert(user_id, amount, unlock_epoch);
+        unlock_epoch
+    }
+
+    fn on_released(&mut self, amount: Balance) {
+        self.unreleased_amount = self.unreleased_amount.saturating_sub(amount);
+    }
+
+    /// Add NEAR to the user's ticket, which is unlocked at the later of both epochs
+    fn insert(&mut self, user_id: &AccountId, amount: Balance, unlock_epoch: EpochHeight) {
+        self.total_amount += amount;
+        let ticket = match self.tickets.get(user_id) {
+            Some(ticket) => UnstakeTicket {
+                amount: ticket.amount + amount,
+                unlock_epoch: ticket.unlock_epoch.max(unlock_epoch),
+            },
+            None => UnstakeTicket {
+                amount,
+                unlock_epoch,
+            },
+        };
+        self.tickets.insert(user_id.clone(), ticket);
+    }
+
+    fn remove(&mut self, user_id: &AccountId) -> Option<UnstakeTicket> {
+        let ticket = self.tickets.remove(user_id)?;
+        self.total_amount -= ticket.amount;
+        Some(ticket)
+    }
+}
+
+impl PhoenixBonds {
+    pub(crate) fn assert_can_unstake(&self) {
+        require!(
+            self.unstake_tickets.can_unstake(current_epoch_height()),
+            ERR_UNSTAKED_NEAR_NOT_RELEASED
+        );
+    }
+
+    /// Unstake LiNEAR owned by `user_id` and create an unstake ticket for the NEAR,
+    /// NEAR unstaked in an earlier epoch is released first if it's unlocked.
+    /// If unstake failed, these LiNEAR will be moved to lost and found.
+    /// NOTE: Make sure LiNEAR balance is decreased before calling this!
+    pub(crate) fn unstake_linear(
+        &mut self,
+        user_id: &AccountId,
+        linear_amount: Balance,
+        linear_price: Balance,
+    ) -> Promise {
+        self.assert_can_unstake();
+        // NEAR amount is rounded down, so LiNEAR would not burn more than `linear_amount`
+        let near_amount = linear2near_floor(linear_amount, linear_price);
+        require!(near_amount > 0, ERR_INVALID_UNSTAKE_AMOUNT);
+        self.on_linear_out(linear_amount);
+
+        let released_amount = self
+            .unstake_tickets
+            .releasable_amount(current_epoch_height());
+        let unstake = if released_amount > 0 {
+            // withdraw and unstake in one batch, so neither happens if the other failed
+            self.release_unstaked_near().function_call(
+                "unstake".to_string(),
+                json!({ "amount": U128(near_amount) })
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_UNSTAKE,
+            )
+        } else {
+            linear_contract::ext(self.linear_address.clone())
+                .with_static_gas(GAS_UNSTAKE)
+                .unstake(near_amount.into())
+        };
+        unstake.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_UNSTAKE_CALLBACK)
+                .on_linear_unstaked(
+                    user_id.clone(),
+                    linear_amount.into(),
+                    near_amount.into(),
+                    released_amount.into(),
+                ),
+        )
+    }
+
+    /// Withdraw all unreleased NEAR from LiNEAR to this contract
+    fn release_unstaked_near(&self) -> Promise {
+        linear_contract::ext(self.linear_address.clone())
+            .with_static_gas(GAS_LINEAR_WITHDRAW)
+            .withdraw(self.unstake_tickets.unreleased_amount.into())
+    }
+
+    fn on_unstaked_near_released(&mut self, near_amount: Balance) {
+        self.unstake_tickets.on_released(near_amount);
+        Event::UnstakeReleased {
+            near_amount: near_amount.into(),
+        }
+        .emit();
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// If unstake failed, LiNEAR will be moved to the lost and found pool.
+    /// The storage deposit is kept for a new ticket, otherwise it's refunded.
+    /// Returns the amount of NEAR that was unstaked.
+    #[private]
+    pub fn on_linear_unstaked(
+        &mut self,
+        user_id: AccountId,
+        linear_amount: U128,
+        near_amount: U128,
+        released_amount: U128,
+    ) -> U128 {
+        self.on_linear_out_resolved(linear_amount.0);
+        if !is_promise_success() {
+            self.lost_and_found
+                .insert(Asset::Linear, &user_id, linear_amount.0);
+            self.transfer_near(&user_id, UNSTAKE_TICKET_STORAGE_DEPOSIT);
+            return 0.into();
+        }
+
+        if released_amount.0 > 0 {
+            self.on_unstaked_near_released(released_amount.0);
+        }
+        if self.unstake_tickets.get(&user_id).is_some() {
+            self.transfer_near(&user_id, UNSTAKE_TICKET_STORAGE_DEPOSIT);
+        }
+        let unlock_epoch =
+            self.unstake_tickets
+                .on_unstaked(&user_id, near_amount.0, current_epoch_height());
+
+        Event::Unstake {
+            account_id: user_id,
+            linear_amount,
+            near_amount,
+            unlock_epoch,
+        }
+        .emit();
+
+        near_amount
+    }
+
+    /// Withdraw NEAR of the caller's unstake ticket once it's unlocked, together
+    /// with its storage deposit. Unreleased NEAR is released from LiNEAR first
+    /// if it's unlocked or the ticket isn't covered by NEAR already released.
+    /// If release failed, the ticket is kept.
+    pub fn withdraw_unstaked(&mut self) -> Promise {
+        // 100 Tgas
+        require!(
+            env::prepaid_gas()
+                >= GAS_WITHDRAW_UNSTAKED + GAS_LINEAR_WITHDRAW + GAS_WITHDRAW_UNSTAKED_CALLBACK,
+            ERR_NOT_ENOUGH_GAS
+        );
+        require!(!self.paused, ERR_PAUSED);
+
+        let user_id = env::predecessor_account_id();
+        let released_amount = self.unstake_tickets.released_amount();
+        let ticket = self
+            .unstake_tickets
+            .remove(&user_id)
+            .unwrap_or_else(|| env::panic_str(ERR_NO_UNSTAKED_NEAR));
+        require!(
+            current_epoch_height() >= ticket.unlock_epoch,
+            ERR_UNSTAKED_NEAR_LOCKED
+        );
+
+        let releasable_amount = self
+            .unstake_tickets
+            .releasable_amount(current_epoch_height());
+        if ticket.amount <= released_amount && releasable_amount == 0 {
+            Event::UnstakeWithdrawn {
+                account_id: user_id.clone(),
+                near_amount: ticket.amount.into(),
+            }
+            .emit();
+            return self.transfer_near(&user_id, ticket.amount + UNSTAKE_TICKET_STORAGE_DEPOSIT);
+        }
+
+        self.release_unstaked_near().then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_WITHDRAW_UNSTAKED_CALLBACK)
+                .on_unstaked_withdrawn(
+                    user_id,
+                    ticket.amount.into(),
+                    ticket.unlock_epoch,
+                    self.unstake_tickets.unreleased_amount.into(),
+                ),
+        )
+    }
+
+    /// NEAR released from LiNEAR is transferred to the user with the storage
+    /// deposit, or the ticket is restored if release failed.
+    /// Returns the amount of NEAR that was transferred.
+    #[private]
+    pub fn on_unstaked_withdrawn(
+        &mut self,
+        user_id: AccountId,
+        near_amount: U128,
+        unlock_epoch: EpochHeight,
+        released_amount: U128,
+    ) -> PromiseOrValue<U128> {
+        if !is_promise_success() {
+            self.unstake_tickets
+                .insert(&user_id, near_amount.0, unlock_epoch);
+            return PromiseOrValue::Value(0.into());
+        }
+
+        self.on_unstaked_near_released(released_amount.0);
+        Event::UnstakeWithdrawn {
+            account_id: user_id.clone(),
+            near_amount,
+        }
+        .emit();
+
+        // LiNEAR sends NEAR before resolving withdraw, so it's already here
+        self.transfer_near(&user_id, near_amount.0 + UNSTAKE_TICKET_STORAGE_DEPOSIT)
+            .into()
+    }
+
+    pub fn get_unstake_ticket(&self, account_id: AccountId) -> Option<UnstakeTicket> {
+        self.unstake_tickets.get(&account_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::StorageUsage;
+
+    /// Max bytes of an unstake ticket: 40 bytes of record overhead, 1 byte of prefix,
+    /// 68 bytes of account ID, 16 bytes of amount and 8 bytes of unlock epoch
+    const UNSTAKE_TICKET_STORAGE_USAGE: StorageUsage = 133;
+
+    fn alice() -> AccountId {
+        AccountId::new_unchecked("alice".into())
+    }
+
+    #[test]
+    fn test_unstake_tickets_are_merged() {
+        let mut tickets = UnstakeTickets::new();
+
+        tickets.insert(&alice(), 100, 10);
+        tickets.insert(&alice(), 50, 12);
+        assert_eq!(
+            tickets.get(&alice()),
+            Some(&UnstakeTicket {
+                amount: 150,
+                unlock_epoch: 12
+            })
+        );
+
+        // a restored ticket doesn't unlock earlier
+        tickets.insert(&alice(), 20, 8);
+        assert_eq!(tickets.get(&alice()).unwrap().unlock_epoch, 12);
+        assert_eq!(tickets.total_amount(), 170);
+
+        assert_eq!(tickets.remove(&alice()).unwrap().amount, 170);
+        assert_eq!(tickets.total_amount(), 0);
+        assert!(tickets.remove(&alice()).is_none());
+    }
+
+    #[test]
+    fn test_unstakes_grouped_by_epoch() {
+        let mut tickets = UnstakeTickets::new();
+        let bob = AccountId::new_unchecked("bob".into());
+
+        assert!(tickets.can_unstake(10));
+        assert_eq!(tickets.on_unstaked(&alice(), 100, 10), 14);
+        // unstakes in the same epoch share the unlock epoch
+        assert!(tickets.can_unstake(10));
+        assert_eq!(tickets.on_unstaked(&bob, 50, 10), 14);
+        assert_eq!(tickets.released_amount(), 0);
+        assert_eq!(tickets.releasable_amount(10), 0);
+
+        // a new epoch has to wait for the unlock, and NEAR is released first
+        assert!(!tickets.can_unstake(11));
+        assert_eq!(tickets.releasable_amount(13), 0);
+        assert!(tickets.can_unstake(14));
+        assert_eq!(tickets.releasable_amount(14), 150);
+        tickets.on_released(150);
+        assert_eq!(tickets.releasable_amount(14), 0);
+        assert_eq!(tickets.released_amount(), 150);
+
+        assert_eq!(tickets.on_unstaked(&bob, 20, 14), 18);
+        assert_eq!(tickets.released_amount(), 150);
+        assert_eq!(
+            tickets.get(&bob),
+            Some(&UnstakeTicket {
+                amount: 70,
+                unlock_epoch: 18
+            })
+        );
+        assert_eq!(tickets.get(&alice()).unwrap().unlock_epoch, 14);
+    }
+
+    #[test]
+    fn test_unstake_ticket_storage_usage() {
+        let mut tickets = UnstakeTickets::new();
+        let account_id = AccountId::new_unchecked("a".repeat(64));
+
+        let storage_before = env::storage_usage();
+        tickets.insert(&account_id, ONE_NEAR, 10);
+        tickets.tickets.flush();
+        assert_eq!(
+            env::storage_usage() - storage_before,
+            UNSTAKE_TICKET_STORAGE_USAGE
+        );
+        assert!(
+            Balance::from(UNSTAKE_TICKET_STORAGE_USAGE) * env::storage_byte_cost()
+                <= UNSTAKE_TICKET_STORAGE_DEPOSIT
+        );
+    }
+}
@@ -1,16 +1,60 @@
-use crate::*;
+use crate::{legacy::ContractV1_1_0, *};
 
 #[near_bindgen]
 impl PhoenixBonds {
     /// Should only be called by this contract on migration.
-    /// This is NOOP implementation. KEEP IT if you haven't changed contract state.
-    /// If you have changed state, you need to implement migration from old state (keep the old
-    /// struct with different name to deserialize it first).
-    /// After migration goes live, revert back to this implementation for next updates.
+    /// Migrate from v1.1.0 state:
+    /// - support NEAR and pNEAR in lost and found
+    /// - add note storage accounting
+    /// - add global note registry, the contract is paused until existing notes
+    ///   are indexed by `index_bond_notes` and the owner resumes it
+    /// - add snapshots of protocol state
+    /// - add flash loans
+    /// - add redeem fee
+    /// - add dynamic tau
+    /// - add unstake tickets
+    /// - add wNEAR address
+    /// - add referrals
+    /// - track outbound LiNEAR in flight
     #[init(ignore_state)]
     #[private]
     pub fn migrate() -> Self {
-        let contract: PhoenixBonds = env::state_read().expect("ERR_NOT_INITIALIZED");
+        let old: ContractV1_1_0 = env::state_read().expect("ERR_NOT_INITIALIZED");
+        let contract = PhoenixBonds {
+            ft: with_held_since_storage(old.ft),
+            owner_id: old.owner_id,
+            linear_address: old.linear_address,
+            paused: true,
+            linear_balance: old.linear_balance,
+            pending_pool_near_amount: old.pending_pool_near_amount,
+            permanent_pool_near_amount: old.permanent_pool_near_amount,
+            treasury_pool_near_amount: old.treasury_pool_near_amount,
+            tau: old.tau,
+            lost_and_found: LostAndFound::from_linear(old.linear_lost_and_found.into()),
+            bond_notes: old.bond_notes,
+            bootstrap_ends_at: old.bootstrap_ends_at,
+            accrual_param: old.accrual_param,
+            note_storage: NoteStorage::new(),
+            note_registry: NoteRegistry::with_legacy_pending_amount(old.pending_pool_near_amount),
+            snapshots: Snapshots::new(),
+            flash_loans: FlashLoans::new(),
+            redeem_fee: RedeemFee::new(),
+            dynamic_tau: None,
+            unstake_tickets: UnstakeTickets::new(),
+            wnear_address: None,
+            referrals: Referrals::new(),
+            last_linear_price: None,
+            linear_in_flight: 0,
+        };
+
+        Event::Migrate {
+            new_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+        .emit();
+        if !old.paused {
+            Event::Pause {}.emit();
+        }
+
         contract
     }
 }
@@ -29,6 +73,10 @@ mod migration {
     pub const MIN_GAS_FOR_MIGRATE_CALL: Gas = Gas(10 * TGAS);
     /// Gas for calling `get_summary` method
     pub const GAS_FOR_GET_SUMMARY_CALL: Gas = Gas(15 * TGAS);
+    /// Gas for calling `check_invariants` method
+    pub const GAS_FOR_CHECK_INVARIANTS_CALL: Gas = Gas(50 * TGAS);
+    /// Max number of pending notes summed by `check_invariants` on upgrade
+    pub const PENDING_NOTES_LIMIT_FOR_UPGRADE: u32 = 100;
 
     /// Self upgrade and call migrate, optimizes gas by not loading into memory the code.
     /// Takes as input non serialized set of bytes of the code.
@@ -43,10 +91,22 @@ mod migration {
             "Not owner"
         );
 
+        Event::Upgrade {
+            old_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+        .emit();
+
         let current_id = env::current_account_id().as_bytes().to_vec();
         let migrate_method_name = b"migrate".to_vec();
         let get_summary_method_name = b"get_summary".to_vec();
         let get_summary_args = b"{\"linear_price\":\"1000000000000000000000000\"}".to_vec();
+        let check_invariants_method_name = b"check_invariants".to_vec();
+        // LiNEAR price cannot be queried here, so no price is given and the latest
+        // known price is used, or price related checks are skipped if there's none
+        let check_invariants_args = format!(
+            "{{\"pending_notes_limit\":{PENDING_NOTES_LIMIT_FOR_UPGRADE},\"assert_passed\":true}}"
+        )
+        .into_bytes();
         unsafe {
             // Load input (wasm code) into register 0.
             sys::input(0);
@@ -56,8 +116,10 @@ mod migration {
             // 1st batch action in the Tx: "deploy contract" (code is taken from register 0)
             sys::promise_batch_action_deploy_contract(promise_id, u64::MAX as _, 0);
             // 2nd batch action in the Tx: call `migrate()` in the contract with sufficient gas
-            let required_gas =
-                env::used_gas() + GAS_FOR_COMPLETING_UPGRADE_CALL + GAS_FOR_GET_SUMMARY_CALL;
+            let required_gas = env::used_gas()
+                + GAS_FOR_COMPLETING_UPGRADE_CALL
+                + GAS_FOR_GET_SUMMARY_CALL
+                + GAS_FOR_CHECK_INVARIANTS_CALL;
             require!(
                 env::prepaid_gas() >= required_gas + MIN_GAS_FOR_MIGRATE_CALL,
                 "Not enough gas to complete contract state migration"
@@ -85,6 +147,19 @@ mod migration {
                 0_u64,
                 GAS_FOR_GET_SUMMARY_CALL.0,
             );
+            // 4th batch action in the Tx: call `check_invariants()` in the contract, and roll
+            // back the upgrade if any invariant is broken. LiNEAR is valued at the latest price
+            // seen by the contract if any, and at most `PENDING_NOTES_LIMIT_FOR_UPGRADE` pending notes
+            // are summed to keep the gas bounded.
+            sys::promise_batch_action_function_call(
+                promise_id,
+                check_invariants_method_name.len() as _,
+                check_invariants_method_name.as_ptr() as _,
+                check_invariants_args.len() as _,
+                check_invariants_args.as_ptr() as _,
+                0_u64,
+                GAS_FOR_CHECK_INVARIANTS_CALL.0,
+            );
             sys::promise_return(promise_id);
         }
     }
@@ -1,41 +1,13 @@
-use std::convert::{TryFrom, TryInto};
-
-use near_bigdecimal::*;
 #[allow(unused_imports)]
 use near_sdk::borsh::BorshDeserialize;
-use near_sdk::{env, Timestamp};
-use near_sdk::{near_bindgen, Balance};
+use near_sdk::near_bindgen;
+use near_sdk::{env, EpochHeight, Timestamp};
 
-use crate::types::FULL_BASIS_POINT;
 use crate::*;
 
-fn near_like_decimals() -> BigDecimal {
-    BigDecimal::from(10_u64).pow(24)
-}
-
-pub fn near2linear(near_amount: Balance, linear_price: Balance) -> Balance {
-    (BigDecimal::from(near_amount) * near_like_decimals() / linear_price.into()).round_u128()
-}
-
-pub fn linear2near(linear_amount: Balance, linear_price: Balance) -> Balance {
-    (BigDecimal::from(linear_amount) * linear_price.into() / near_like_decimals()).round_u128()
-}
-
-pub fn pnear2near(pnear_amount: Balance, pnear_price: Balance) -> Balance {
-    (BigDecimal::from(pnear_amount) * pnear_price.into() / near_like_decimals()).round_u128()
-}
-
-pub fn near2pnear(near_amount: Balance, pnear_price: Balance) -> Balance {
-    (BigDecimal::from(near_amount) * near_like_decimals() / pnear_price.into()).round_u128()
-}
-
-pub fn apply_basis_point<T>(value: T, point: u32) -> T
-where
-    T: std::ops::Mul<Output = T> + std::ops::Div<Output = T> + TryFrom<u32>,
-    <T as TryFrom<u32>>::Error: std::fmt::Debug,
-{
-    value * point.try_into().unwrap() / FULL_BASIS_POINT.try_into().unwrap()
-}
+pub use phoenix_math::{
+    apply_basis_point, linear2near, linear2near_floor, linear_price_of, near2linear, pnear2near,
+};
 
 #[cfg(not(feature = "test"))]
 pub fn current_timestamp_ms() -> Timestamp {
@@ -54,6 +26,23 @@ pub fn current_timestamp_ms() -> Timestamp {
     }
 }
 
+#[cfg(not(feature = "test"))]
+pub fn current_epoch_height() -> EpochHeight {
+    env::epoch_height()
+}
+
+#[cfg(feature = "test")]
+pub fn current_epoch_height() -> EpochHeight {
+    let test_epoch_key: &[u8] = "_test_epoch_".as_bytes();
+    let raw_epoch_option = env::storage_read(test_epoch_key);
+
+    if let Some(raw_epoch) = raw_epoch_option {
+        u64::try_from_slice(&raw_epoch).unwrap_or(0)
+    } else {
+        0
+    }
+}
+
 #[near_bindgen]
 impl PhoenixBonds {
     #[cfg(feature = "test")]
@@ -61,6 +50,12 @@ impl PhoenixBonds {
         let test_timestamp_key: &[u8] = "_test_ts_".as_bytes();
         env::storage_write(test_timestamp_key, &ms.try_to_vec().unwrap_or_default());
     }
+
+    #[cfg(feature = "test")]
+    pub fn set_current_epoch_height(&mut self, epoch: EpochHeight) {
+        let test_epoch_key: &[u8] = "_test_epoch_".as_bytes();
+        env::storage_write(test_epoch_key, &epoch.try_to_vec().unwrap_or_default());
+    }
 }
 
 pub mod u128_dec_format {
@@ -74,7 +69,6 @@ pub mod u128_dec_format {
         serializer.serialize_str(&num.to_string())
     }
 
-    #[allow(dead_code)]
     pub fn deserialize<'de, D>(deserializer: D) -> Result<u128, D::Error>
     where
         D: Deserializer<'de>,
@@ -88,5 +82,4 @@ pub mod u128_dec_format {
 #[cfg(test)]
 pub mod tests {
     pub const ONE_DAY_MS: u64 = 24 * 3600 * 1000;
-    pub const HALF_DAY_MS: u64 = ONE_DAY_MS / 2;
 }
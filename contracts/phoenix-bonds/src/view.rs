@@ -1,32 +1,75 @@
-use near_sdk::{near_bindgen, serde::Serialize};
+use near_sdk::{
+    near_bindgen,
+    serde::{Deserialize, Serialize},
+};
 
-use crate::*;
+use crate::{bond_note::SettledNotes, *};
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct AccrualInfo {
-    alpha: Duration,
-    min_alpha: Duration,
-    adjust_interval: Duration,
-    adjust_rate: BasisPoint,
-    decreasing: bool,
-    target_mean_length: Duration,
-    current_mean_length: Duration,
+    pub alpha: Duration,
+    pub min_alpha: Duration,
+    pub adjust_interval: Duration,
+    pub adjust_rate: BasisPoint,
+    pub decreasing: bool,
+    pub target_mean_length: Duration,
+    pub current_mean_length: Duration,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Summary {
-    owner_id: AccountId,
-    linear_balance: U128,
-    reserve_pool_near_amount: U128,
-    pending_pool_near_amount: U128,
-    permanent_pool_near_amount: U128,
-    treasury_pool_near_amount: U128,
-    bootstrap_ends_at: Timestamp,
-    tau: BasisPoint,
-    accrual_parameter: AccrualInfo,
-    total_lost_and_found_linear: U128,
+    pub owner_id: AccountId,
+    /// wNEAR contract accepted to bond
+    pub wnear_address: Option<AccountId>,
+    pub linear_balance: U128,
+    pub reserve_pool_near_amount: U128,
+    pub pending_pool_near_amount: U128,
+    pub permanent_pool_near_amount: U128,
+    pub treasury_pool_near_amount: U128,
+    pub bootstrap_ends_at: Timestamp,
+    pub tau: BasisPoint,
+    /// tau applied if a bond note is committed now
+    pub current_tau: BasisPoint,
+    pub accrual_parameter: AccrualInfo,
+    pub total_lost_and_found_linear: U128,
+    pub total_lost_and_found_near: U128,
+    pub total_lost_and_found_pnear: U128,
+    /// NEAR unstaked for users, not yet withdrawn
+    pub total_unstaked_near: U128,
+    pub total_notes_created: u64,
+    pub total_notes_committed: u64,
+    pub total_notes_cancelled: u64,
+    pub bonders_count: u32,
+    pub snapshot_interval: Duration,
+    pub redeem_fee: BasisPoint,
+    pub redeem_fee_decay_period: Duration,
+    /// share of the treasury cut paid to referrers
+    pub referral_reward_rate: BasisPoint,
+    /// LiNEAR rewarded to referrers, not yet claimed
+    pub total_unclaimed_referral_linear: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UserPortfolio {
+    pnear_balance: U128,
+    /// value of pNEAR balance in NEAR
+    pnear_near_value: U128,
+    /// value of pNEAR balance in LiNEAR
+    pnear_linear_value: U128,
+    pending_notes_count: u32,
+    /// total NEAR bonded in pending notes of this page
+    pending_bond_amount: U128,
+    /// total pNEAR pending notes of this page could get if committed now
+    accrued_pnear: U128,
+    /// note id to get the next page of pending notes from, if there could be more
+    next_pending_note_id: Option<u32>,
+    lost_and_found_linear: U128,
+    /// lifetime totals of committed and cancelled notes, legacy notes are
+    /// included once indexed by `index_bond_notes`
+    settled_notes: SettledNotes,
 }
 
 #[near_bindgen]
@@ -35,6 +78,7 @@ impl PhoenixBonds {
         let current_ms = current_timestamp_ms();
         Summary {
             owner_id: self.owner_id.clone(),
+            wnear_address: self.wnear_address.clone(),
             linear_balance: self.linear_balance.into(),
             reserve_pool_near_amount: self.reserve_pool_near_amount(linear_price.0).into(),
             pending_pool_near_amount: self.pending_pool_near_amount.into(),
@@ -42,6 +86,7 @@ impl PhoenixBonds {
             treasury_pool_near_amount: self.treasury_pool_near_amount.into(),
             bootstrap_ends_at: self.bootstrap_ends_at,
             tau: self.tau,
+            current_tau: self.current_tau(),
             accrual_parameter: AccrualInfo {
                 alpha: self.accrual_param.current_alpha(current_ms),
                 min_alpha: self.accrual_param.min_alpha,
@@ -52,7 +97,162 @@ impl PhoenixBonds {
                 target_mean_length: self.accrual_param.target_mean_length,
                 current_mean_length: self.accrual_param.mean_length.mean(current_ms),
             },
-            total_lost_and_found_linear: self.linear_lost_and_found.total_amount().into(),
+            total_lost_and_found_linear: self.lost_and_found.total_amount(Asset::Linear).into(),
+            total_lost_and_found_near: self.lost_and_found.total_amount(Asset::Near).into(),
+            total_lost_and_found_pnear: self.lost_and_found.total_amount(Asset::Pnear).into(),
+            total_unstaked_near: self.unstake_tickets.total_amount().into(),
+            total_notes_created: self.note_registry.created_count(),
+            total_notes_committed: self.note_registry.committed_count(),
+            total_notes_cancelled: self.note_registry.cancelled_count(),
+            bonders_count: self.note_registry.bonders_count(),
+            snapshot_interval: self.snapshots.interval(),
+            redeem_fee: self.redeem_fee.fee(),
+            redeem_fee_decay_period: self.redeem_fee.decay_period(),
+            referral_reward_rate: self.referrals.reward_rate(),
+            total_unclaimed_referral_linear: self.referrals.total_unclaimed_linear().into(),
+        }
+    }
+
+    /// Portfolio of a user. Totals of pending notes only cover at most `limit`
+    /// pending notes starting from note id `from_index`. Users with more pending
+    /// notes should sum up the pages until `next_pending_note_id` is null.
+    pub fn get_user_portfolio(
+        &self,
+        account_id: AccountId,
+        linear_price: U128,
+        from_index: u32,
+        limit: u32,
+    ) -> UserPortfolio {
+        let current_ms = current_timestamp_ms();
+        let pnear_balance = self.ft.accounts.get(&account_id).unwrap_or(0);
+        let pnear_near_value = pnear2near(pnear_balance, self.pnear_price(linear_price.0));
+
+        let pending_note_ids =
+            self.bond_notes
+                .get_user_pending_note_ids(&account_id, from_index, limit);
+        let next_pending_note_id = if pending_note_ids.len() < limit as usize {
+            None
+        } else {
+            pending_note_ids.last().map(|id| id + 1)
+        };
+        let pending_notes: Vec<BondNote> = pending_note_ids
+            .iter()
+            .map(|id| self.bond_notes.get_user_note(&account_id, *id))
+            .collect();
+        let pending_bond_amount: Balance = pending_notes.iter().map(|n| n.bond_amount()).sum();
+        let accrued_pnear: Balance = pending_notes
+            .iter()
+            .map(|n| self.note_accrued_pnear(n, linear_price.0, current_ms))
+            .sum();
+
+        UserPortfolio {
+            pnear_balance: pnear_balance.into(),
+            pnear_near_value: pnear_near_value.into(),
+            pnear_linear_value: near2linear(pnear_near_value, linear_price.0).into(),
+            pending_notes_count: self.bond_notes.user_pending_note_len(&account_id),
+            pending_bond_amount: pending_bond_amount.into(),
+            accrued_pnear: accrued_pnear.into(),
+            next_pending_note_id,
+            lost_and_found_linear: self
+                .lost_and_found
+                .user_amount(Asset::Linear, &account_id)
+                .into(),
+            settled_notes: self.user_settled_notes(&account_id),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, ONE_NEAR};
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    #[test]
+    fn test_user_portfolio() {
+        let alice = AccountId::new_unchecked("alice".into());
+        let linear_price = 2 * ONE_NEAR;
+        let mut contract = new_contract(0, 0, 0, 0, 1, 300);
+        // after bootstrap ends
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice.clone())
+            .block_timestamp(2_000_000)
+            .build());
+
+        let committed =
+            contract.internal_create_bond(alice.clone(), 100 * ONE_NEAR, 50 * ONE_NEAR, None, None);
+        let pending_notes: Vec<u32> = (0..3)
+            .map(|_| {
+                contract
+                    .internal_create_bond(alice.clone(), 10 * ONE_NEAR, 5 * ONE_NEAR, None, None)
+                    .id()
+            })
+            .collect();
+        let note =
+            contract.internal_create_bond(alice.clone(), 20 * ONE_NEAR, 10 * ONE_NEAR, None, None);
+        let mut note = contract.bond_notes.get_user_note(&alice, note.id());
+        note.cancel();
+        contract.save_settled_note(&alice, note.id(), note);
+        contract
+            .lost_and_found
+            .insert(Asset::Linear, &alice, ONE_NEAR);
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(alice.clone())
+            .block_timestamp(4_000_000)
+            .build());
+        contract.on_get_linear_price_for_commit(
+            alice.clone(),
+            committed.id(),
+            Ok(U128(linear_price)),
+        );
+
+        let portfolio = contract.get_user_portfolio(alice.clone(), linear_price.into(), 0, 2);
+        let pnear_balance = contract.ft.accounts.get(&alice).unwrap();
+        assert!(pnear_balance > 0);
+        assert_eq!(portfolio.pnear_balance.0, pnear_balance);
+        // alice holds all pNEAR, which is worth the whole reserve pool
+        let reserve_pool = contract.reserve_pool_near_amount(linear_price);
+        assert!(reserve_pool > 0);
+        assert!(portfolio.pnear_near_value.0.abs_diff(reserve_pool) <= 1);
+        assert!(
+            portfolio
+                .pnear_linear_value
+                .0
+                .abs_diff(portfolio.pnear_near_value.0 / 2)
+                <= 1
+        );
+        assert_eq!(portfolio.pending_notes_count, 3);
+        assert_eq!(portfolio.pending_bond_amount.0, 20 * ONE_NEAR);
+        assert_eq!(portfolio.next_pending_note_id, Some(pending_notes[1] + 1));
+        assert_eq!(portfolio.lost_and_found_linear.0, ONE_NEAR);
+        assert_eq!(portfolio.settled_notes.cancelled_count, 1);
+        assert_eq!(portfolio.settled_notes.cancelled_bond_amount, 20 * ONE_NEAR);
+        assert_eq!(portfolio.settled_notes.committed_count, 1);
+
+        let last_page = contract.get_user_portfolio(
+            alice.clone(),
+            linear_price.into(),
+            portfolio.next_pending_note_id.unwrap(),
+            2,
+        );
+        assert_eq!(last_page.pending_bond_amount.0, 10 * ONE_NEAR);
+        assert_eq!(last_page.next_pending_note_id, None);
+
+        // accrued pNEAR is what a pending note would get if committed now
+        let page = contract.get_user_portfolio(alice.clone(), linear_price.into(), 0, 1);
+        assert!(page.accrued_pnear.0 > 0);
+        assert_eq!(
+            portfolio.accrued_pnear.0 + last_page.accrued_pnear.0,
+            3 * page.accrued_pnear.0
+        );
+        let minted = contract.on_get_linear_price_for_commit(
+            alice.clone(),
+            pending_notes[0],
+            Ok(U128(linear_price)),
+        );
+        assert_eq!(minted, page.accrued_pnear);
+    }
+}
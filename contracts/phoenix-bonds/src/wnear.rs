@@ -0,0 +1,195 @@
+use crate::{interfaces::wnear_contract, *};
+use near_sdk::{near_bindgen, PromiseOrValue};
+
+const MINIMUM_BOND_WNEAR_AMOUNT: u128 = MINIMUM_BOND_AMOUNT + BOND_STORAGE_DEPOSIT; // 0.11 wNEAR
+
+const ERR_SMALL_BOND_WNEAR_AMOUNT: &str = "Bond amount must be at least 0.11 wNEAR";
+const ERR_UNWRAP_FAILED: &str = "Failed to unwrap wNEAR";
+
+impl PhoenixBonds {
+    pub(crate) fn is_wnear(&self, token_address: &AccountId) -> bool {
+        self.wnear_address.as_ref() == Some(token_address)
+    }
+
+    /// Bond with wNEAR received from `user_id`: unwrap wNEAR, stake NEAR on LiNEAR
+    /// and create the bond note. wNEAR is refunded if any step fails.
+    /// Returns the amount of unused wNEAR.
+    pub(crate) fn internal_bond_with_wnear(
+        &mut self,
+        user_id: AccountId,
+        amount: Balance,
+        referrer_id: Option<AccountId>,
+    ) -> PromiseOrValue<U128> {
+        require!(
+            env::prepaid_gas() >= GAS_FT_ON_TRANSFER_WNEAR,
+            ERR_NOT_ENOUGH_GAS
+        );
+        require!(!self.paused, ERR_PAUSED);
+        require!(
+            amount >= MINIMUM_BOND_WNEAR_AMOUNT,
+            ERR_SMALL_BOND_WNEAR_AMOUNT
+        );
+
+        let wnear_address = env::predecessor_account_id();
+        wnear_contract::ext(wnear_address.clone())
+            .with_static_gas(GAS_NEAR_WITHDRAW)
+            .with_attached_deposit(ONE_YOCTO)
+            .near_withdraw(amount.into())
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_WNEAR_UNWRAP_CALLBACK)
+                    .on_wnear_unwrapped(user_id, wnear_address, amount.into(), referrer_id),
+            )
+            .into()
+    }
+
+    fn bond_failed(&self, user_id: &AccountId, amount: U128, reason: &str) {
+        Event::BondFailed {
+            account_id: user_id.clone(),
+            amount,
+            reason: reason.to_string(),
+        }
+        .emit();
+    }
+}
+
+#[near_bindgen]
+impl PhoenixBonds {
+    /// Stake unwrapped NEAR except the storage deposit.
+    /// If unwrap failed, all wNEAR is returned as unused.
+    #[private]
+    pub fn on_wnear_unwrapped(
+        &mut self,
+        user_id: AccountId,
+        wnear_address: AccountId,
+        amount: U128,
+        referrer_id: Option<AccountId>,
+    ) -> PromiseOrValue<U128> {
+        if !is_promise_success() {
+            self.bond_failed(&user_id, amount, ERR_UNWRAP_FAILED);
+            return PromiseOrValue::Value(amount);
+        }
+
+        let bond_amount = amount.0 - BOND_STORAGE_DEPOSIT;
+        linear_contract::ext(self.linear_address.clone())
+            .with_static_gas(GAS_DEPOSIT_AND_STAKE)
+            .with_attached_deposit(bond_amount)
+            .deposit_and_stake()
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_WNEAR_BOND_CALLBACK)
+                    .on_wnear_staked(user_id, wnear_address, amount, referrer_id),
+            )
+            .into()
+    }
+
+    /// Create the bond note as in `on_staked`. If stake failed,
+    /// NEAR is wrapped again to refund wNEAR.
+    #[private]
+    pub fn on_wnear_staked(
+        &mut self,
+        user_id: AccountId,
+        wnear_address: AccountId,
+        amount: U128,
+        referrer_id: Option<AccountId>,
+        #[callback_result] staked_linear_amount: Result<U128, PromiseError>,
+    ) -> PromiseOrValue<U128> {
+        if let Ok(linear_amount) = staked_linear_amount {
+            let bond_amount = amount.0 - BOND_STORAGE_DEPOSIT;
+            self.internal_create_bond(
+                user_id.clone(),
+                bond_amount,
+                linear_amount.0,
+                None,
+                referrer_id,
+            );
+            self.deposit_note_storage(&user_id, BOND_STORAGE_DEPOSIT);
+            return PromiseOrValue::Value(0.into());
+        }
+
+        self.bond_failed(&user_id, amount, ERR_STAKE_FAILED);
+        wnear_contract::ext(wnear_address)
+            .with_static_gas(GAS_NEAR_DEPOSIT)
+            .with_attached_deposit(amount.0)
+            .near_deposit()
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_WNEAR_REFUND_CALLBACK)
+                    .on_wnear_rewrapped(user_id, amount),
+            )
+            .into()
+    }
+
+    /// Rewrapped wNEAR is returned as unused, which would be refunded by
+    /// the wNEAR contract. If rewrap failed, NEAR is refunded instead.
+    #[private]
+    pub fn on_wnear_rewrapped(&mut self, user_id: AccountId, amount: U128) -> U128 {
+        if is_promise_success() {
+            amount
+        } else {
+            self.transfer_near(&user_id, amount.0);
+            0.into()
+        }
+    }
+
+    /// Set wNEAR contract accepted to bond, or disable bonding with wNEAR by `None`
+    #[payable]
+    pub fn set_wnear_address(&mut self, wnear_address: Option<AccountId>) {
+        self.assert_owner_with_one_yocto();
+
+        Event::SetWnearAddress {
+            old_address: self.wnear_address.clone(),
+            new_address: wnear_address.clone(),
+        }
+        .emit();
+
+        self.wnear_address = wnear_address;
+    }
+
+    pub fn get_wnear_address(&self) -> Option<AccountId> {
+        self.wnear_address.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, ONE_NEAR};
+
+    use crate::tests::new_contract;
+
+    use super::*;
+
+    fn alice() -> AccountId {
+        AccountId::new_unchecked("alice".into())
+    }
+
+    fn wnear() -> AccountId {
+        AccountId::new_unchecked("wrap".into())
+    }
+
+    #[test]
+    fn test_bond_note_created_from_wnear() {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(AccountId::new_unchecked("phoenix".into()))
+            .build());
+        let mut contract = new_contract(0, 0, 0, 0, 1, 0);
+        contract.wnear_address = Some(wnear());
+        assert!(contract.is_wnear(&wnear()));
+
+        let unused =
+            contract.on_wnear_staked(alice(), wnear(), U128(ONE_NEAR), None, Ok(U128(ONE_NEAR)));
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+
+        // storage deposit is kept out of the bond
+        let note = contract.bond_notes.get_user_note(&alice(), 0);
+        assert_eq!(note.bond_amount(), ONE_NEAR - BOND_STORAGE_DEPOSIT);
+        assert_eq!(
+            contract.note_storage_balance_of(alice()).deposit.0,
+            BOND_STORAGE_DEPOSIT
+        );
+        assert_eq!(
+            contract.pending_pool_near_amount,
+            ONE_NEAR - BOND_STORAGE_DEPOSIT
+        );
+    }
+}
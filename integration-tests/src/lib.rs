@@ -0,0 +1,725 @@
+//! Fixtures and helpers for integration tests of Phoenix Bonds, which run
+//! the contract together with mock LiNEAR on a local sandbox.
+//!
+//! Contracts must be built before running tests: `make phoenix_test mock_linear mock_wnear`
+use anyhow::Context;
+use near_sdk::json_types::U128;
+use near_workspaces::{
+    network::Sandbox,
+    result::ExecutionFinalResult,
+    types::{Gas, NearToken},
+    Account, AccountId, Contract, Worker,
+};
+use phoenix_bonds::{BondNoteInfo, Summary};
+use phoenix_math::{BasisPoint, Duration, Timestamp};
+use serde_json::{json, Value};
+
+pub use phoenix_math::{Balance, ONE_NEAR};
+
+pub const ONE_DAY_MS: Duration = 24 * 3600 * 1000;
+pub const TAU: BasisPoint = 300; // 3%
+pub const ALPHA: Duration = 3 * ONE_DAY_MS;
+pub const BOOTSTRAP_ENDS: Timestamp = 15 * ONE_DAY_MS;
+pub const BOND_STORAGE_DEPOSIT: Balance = ONE_NEAR / 100;
+pub const UNSTAKE_TICKET_STORAGE_DEPOSIT: Balance = ONE_NEAR / 500;
+
+const PHOENIX_WASM: &str = "phoenix_bonds_test.wasm";
+const MOCK_LINEAR_WASM: &str = "mock_linear.wasm";
+const MOCK_WNEAR_WASM: &str = "mock_wnear.wasm";
+
+pub fn days_to_ms(n: u64) -> Timestamp {
+    n * ONE_DAY_MS
+}
+
+/// `n / 10^decimals` NEAR in yocto
+pub fn near(n: u128, decimals: u32) -> Balance {
+    n * ONE_NEAR / 10u128.pow(decimals)
+}
+
+fn yocto(amount: Balance) -> NearToken {
+    NearToken::from_yoctonear(amount)
+}
+
+/// Deposit and gas of cancel and redeem, `delayed_near` pays for the unstake ticket
+/// and may release NEAR unstaked in an earlier epoch
+fn exit_deposit_and_gas(exit: Option<&str>) -> (NearToken, Gas) {
+    if exit == Some("delayed_near") {
+        (yocto(UNSTAKE_TICKET_STORAGE_DEPOSIT), Gas::from_tgas(210))
+    } else {
+        (yocto(1), Gas::from_tgas(160))
+    }
+}
+
+pub struct Fixtures {
+    // sandbox is stopped once the worker is dropped
+    pub worker: Worker<Sandbox>,
+    pub alice: Account,
+    pub bob: Account,
+    pub owner: Account,
+    pub linear: Contract,
+    pub fake_linear: Contract,
+    pub wnear: Contract,
+    pub phoenix: Contract,
+}
+
+pub async fn init() -> anyhow::Result<Fixtures> {
+    let worker = near_workspaces::sandbox().await?;
+    let root = worker.root_account()?;
+
+    let alice = create_account(&root, "alice").await?;
+    let bob = create_account(&root, "bob").await?;
+
+    let linear = create_and_deploy(&root, "linear", MOCK_LINEAR_WASM, "new", json!({})).await?;
+    let wnear = create_and_deploy(&root, "wrap", MOCK_WNEAR_WASM, "new", json!({})).await?;
+    let owner = root
+        .create_subaccount("owner")
+        .transact()
+        .await?
+        .into_result()?;
+    let phoenix = create_and_deploy(
+        &root,
+        "phoenix",
+        PHOENIX_WASM,
+        "new",
+        json!({
+            "owner_id": owner.id(),
+            "linear_address": linear.id(),
+            "tau": TAU,
+            "bootstrap_ends": BOOTSTRAP_ENDS,
+            "accrual": {
+                "alpha": ALPHA,
+                "min_alpha": 1,
+                "target_mean_length": days_to_ms(15),
+                "adjust_interval": days_to_ms(1),
+                "adjust_rate": 100, // 1%
+            },
+            "wnear_address": wnear.id(),
+        }),
+    )
+    .await?;
+
+    let fake_linear =
+        create_and_deploy(&root, "linear-fake", MOCK_LINEAR_WASM, "new", json!({})).await?;
+    // Phoenix Bonds must be registered to receive wNEAR
+    ft_storage_deposit(&wnear, phoenix.as_account()).await?;
+
+    Ok(Fixtures {
+        worker,
+        alice,
+        bob,
+        owner,
+        linear,
+        fake_linear,
+        wnear,
+        phoenix,
+    })
+}
+
+async fn create_account(root: &Account, account_id: &str) -> anyhow::Result<Account> {
+    Ok(root
+        .create_subaccount(account_id)
+        .initial_balance(NearToken::from_near(1_000_000))
+        .transact()
+        .await?
+        .into_result()?)
+}
+
+async fn create_and_deploy(
+    root: &Account,
+    account_id: &str,
+    wasm_file: &str,
+    method: &str,
+    args: Value,
+) -> anyhow::Result<Contract> {
+    let path = format!("{}/../res/{}", env!("CARGO_MANIFEST_DIR"), wasm_file);
+    let wasm = std::fs::read(&path)
+        .with_context(|| format!("{} not found, please build contracts first", path))?;
+
+    let account = create_account(root, account_id).await?;
+    let contract = account.deploy(&wasm).await?.into_result()?;
+    contract
+        .call(method)
+        .args_json(args)
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(contract)
+}
+
+/// Assert the transaction failed with an error containing `message`
+pub fn assert_failure(result: ExecutionFinalResult, message: &str) {
+    let failure = result.into_result().expect_err("Function call didn't fail");
+    let error = format!("{:?}", failure);
+    assert!(
+        error.contains(message),
+        "Bad error message. expected: \"{}\", actual: \"{}\"",
+        message,
+        error
+    );
+}
+
+// -- mock linear methods
+
+pub async fn set_linear_price(linear: &Contract, price: Balance) -> anyhow::Result<()> {
+    linear
+        .call("set_ft_price")
+        .args_json(json!({ "price": U128(price) }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn get_linear_price(linear: &Contract) -> anyhow::Result<Balance> {
+    Ok(linear.view("ft_price").await?.json::<U128>()?.0)
+}
+
+pub async fn set_linear_panic(linear: &Contract, panic: bool) -> anyhow::Result<()> {
+    linear
+        .call("set_panic")
+        .args_json(json!({ "panic": panic }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn set_ft_transfer_panic(linear: &Contract, panic: bool) -> anyhow::Result<()> {
+    linear
+        .call("set_ft_transfer_panic")
+        .args_json(json!({ "panic": panic }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+/// Make the next `count` LiNEAR transfers fail as if the receiver is not registered
+pub async fn set_unregistered_transfers(linear: &Contract, count: u32) -> anyhow::Result<()> {
+    linear
+        .call("set_unregistered_transfers")
+        .args_json(json!({ "count": count }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn set_ft_price_panic(linear: &Contract, panic: bool) -> anyhow::Result<()> {
+    linear
+        .call("set_ft_price_panic")
+        .args_json(json!({ "panic": panic }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn set_zero_price(linear: &Contract, zero_price: bool) -> anyhow::Result<()> {
+    linear
+        .call("set_zero_price")
+        .args_json(json!({ "zero_price": zero_price }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+/// Set LiNEAR price growth per epoch in parts per million
+pub async fn set_epoch_reward_rate(linear: &Contract, rate: u32) -> anyhow::Result<()> {
+    linear
+        .call("set_epoch_reward_rate")
+        .args_json(json!({ "rate": rate }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+/// Move mock LiNEAR `count` epochs forward, which grows LiNEAR price
+pub async fn advance_epochs(linear: &Contract, count: u64) -> anyhow::Result<()> {
+    linear
+        .call("advance_epochs")
+        .args_json(json!({ "count": count }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+/// Set fee of instant unstake in basis points
+pub async fn set_instant_unstake_fee(linear: &Contract, fee: BasisPoint) -> anyhow::Result<()> {
+    linear
+        .call("set_instant_unstake_fee")
+        .args_json(json!({ "fee": fee }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn get_epoch_height(linear: &Contract) -> anyhow::Result<u64> {
+    Ok(linear.view("get_epoch_height").await?.json()?)
+}
+
+/// Mint LiNEAR by staking NEAR
+pub async fn mint_linear(
+    account: &Account,
+    linear: &Contract,
+    amount: Balance,
+) -> anyhow::Result<()> {
+    account
+        .call(linear.id(), "deposit_and_stake")
+        .deposit(yocto(amount))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+/// Unstake `amount` NEAR from LiNEAR
+pub async fn unstake(
+    account: &Account,
+    linear: &Contract,
+    amount: Balance,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(linear.id(), "unstake")
+        .args_json(json!({ "amount": U128(amount) }))
+        .transact()
+        .await?)
+}
+
+/// Withdraw `amount` unstaked NEAR from LiNEAR
+pub async fn withdraw(
+    account: &Account,
+    linear: &Contract,
+    amount: Balance,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(linear.id(), "withdraw")
+        .args_json(json!({ "amount": U128(amount) }))
+        .transact()
+        .await?)
+}
+
+pub async fn get_account_details(linear: &Contract, account: &Account) -> anyhow::Result<Value> {
+    Ok(linear
+        .view("get_account_details")
+        .args_json(json!({ "account_id": account.id() }))
+        .await?
+        .json()?)
+}
+
+// -- mock wNEAR methods
+
+/// Wrap NEAR of `account` into wNEAR
+pub async fn wrap_near(wnear: &Contract, account: &Account, amount: Balance) -> anyhow::Result<()> {
+    account
+        .call(wnear.id(), "near_deposit")
+        .deposit(yocto(amount))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn set_wnear_withdraw_panic(wnear: &Contract, panic: bool) -> anyhow::Result<()> {
+    wnear
+        .call("set_withdraw_panic")
+        .args_json(json!({ "panic": panic }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn set_wnear_deposit_panic(wnear: &Contract, panic: bool) -> anyhow::Result<()> {
+    wnear
+        .call("set_deposit_panic")
+        .args_json(json!({ "panic": panic }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+// -- fungible token methods
+
+pub async fn ft_storage_deposit(ft: &Contract, account: &Account) -> anyhow::Result<()> {
+    account
+        .call(ft.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(yocto(near(1, 1)))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn get_ft_balance(ft: &Contract, account: &Account) -> anyhow::Result<Balance> {
+    Ok(ft
+        .view("ft_balance_of")
+        .args_json(json!({ "account_id": account.id() }))
+        .await?
+        .json::<U128>()?
+        .0)
+}
+
+pub async fn get_near_balance(account: &Account) -> anyhow::Result<Balance> {
+    Ok(account.view_account().await?.balance.as_yoctonear())
+}
+
+pub async fn ft_transfer(
+    ft: &Contract,
+    from: &Account,
+    to: &Account,
+    amount: Balance,
+) -> anyhow::Result<()> {
+    from.call(ft.id(), "ft_transfer")
+        .args_json(json!({ "receiver_id": to.id(), "amount": U128(amount) }))
+        .deposit(yocto(1))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+// -- phoenix bonds methods
+
+/// Let Phoenix Bonds see the same epoch height as mock LiNEAR
+pub async fn sync_epoch_height(phoenix: &Contract, linear: &Contract) -> anyhow::Result<()> {
+    let epoch = get_epoch_height(linear).await?;
+    phoenix
+        .call("set_current_epoch_height")
+        .args_json(json!({ "epoch": epoch }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn set_timestamp(phoenix: &Contract, ms: Timestamp) -> anyhow::Result<()> {
+    phoenix
+        .call("set_current_timestamp_ms")
+        .args_json(json!({ "ms": ms }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+pub async fn get_summary(phoenix: &Contract, linear_price: Balance) -> anyhow::Result<Summary> {
+    Ok(phoenix
+        .view("get_summary")
+        .args_json(json!({ "linear_price": U128(linear_price) }))
+        .await?
+        .json()?)
+}
+
+pub async fn get_pnear_price(phoenix: &Contract, linear_price: Balance) -> anyhow::Result<Balance> {
+    Ok(phoenix
+        .view("get_pnear_price")
+        .args_json(json!({ "linear_price": U128(linear_price) }))
+        .await?
+        .json::<U128>()?
+        .0)
+}
+
+pub async fn get_bond_note(
+    phoenix: &Contract,
+    account: &Account,
+    note_id: u32,
+    linear_price: Balance,
+) -> anyhow::Result<BondNoteInfo> {
+    Ok(phoenix
+        .view("get_bond_note")
+        .args_json(json!({
+            "account_id": account.id(),
+            "note_id": note_id,
+            "linear_price": U128(linear_price),
+        }))
+        .await?
+        .json()?)
+}
+
+pub async fn notes_count(phoenix: &Contract, account: &Account) -> anyhow::Result<u32> {
+    Ok(phoenix
+        .view("notes_count")
+        .args_json(json!({ "account_id": account.id() }))
+        .await?
+        .json()?)
+}
+
+/// Bond `amount` NEAR, plus the storage deposit
+pub async fn bond(
+    account: &Account,
+    phoenix: &Contract,
+    amount: Balance,
+) -> anyhow::Result<ExecutionFinalResult> {
+    bond_with_referrer(account, phoenix, amount, None).await
+}
+
+/// Bond `amount` NEAR referred by `referrer`, plus the storage deposit
+pub async fn bond_with_referrer(
+    account: &Account,
+    phoenix: &Contract,
+    amount: Balance,
+    referrer: Option<&Account>,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(phoenix.id(), "bond")
+        .args_json(json!({ "referrer_id": referrer.map(|r| r.id()) }))
+        .deposit(yocto(amount + BOND_STORAGE_DEPOSIT))
+        .gas(Gas::from_tgas(120))
+        .transact()
+        .await?)
+}
+
+/// Bond with `amount` LiNEAR via `ft_transfer_call`, returns LiNEAR used
+pub async fn bond_with_linear(
+    account: &Account,
+    phoenix: &Contract,
+    linear: &Contract,
+    amount: Balance,
+) -> anyhow::Result<Balance> {
+    bond_with_linear_and_referrer(account, phoenix, linear, amount, None).await
+}
+
+/// Bond with `amount` LiNEAR referred by `referrer`, returns LiNEAR used
+pub async fn bond_with_linear_and_referrer(
+    account: &Account,
+    phoenix: &Contract,
+    linear: &Contract,
+    amount: Balance,
+    referrer: Option<&Account>,
+) -> anyhow::Result<Balance> {
+    let msg = match referrer {
+        Some(referrer) => json!({ "Bond": { "referrer_id": referrer.id() } }).to_string(),
+        None => "\"Bond\"".to_string(),
+    };
+    Ok(account
+        .call(linear.id(), "ft_transfer_call")
+        .args_json(json!({
+            "receiver_id": phoenix.id(),
+            "amount": U128(amount),
+            "msg": msg,
+        }))
+        .deposit(yocto(1))
+        .gas(Gas::from_tgas(120))
+        .transact()
+        .await?
+        .json::<U128>()?
+        .0)
+}
+
+/// Bond with wNEAR, the result is the amount of wNEAR used
+pub async fn bond_with_wnear(
+    account: &Account,
+    phoenix: &Contract,
+    wnear: &Contract,
+    amount: Balance,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(wnear.id(), "ft_transfer_call")
+        .args_json(json!({
+            "receiver_id": phoenix.id(),
+            "amount": U128(amount),
+            "msg": "\"Bond\"",
+        }))
+        .deposit(yocto(1))
+        .gas(Gas::from_tgas(250))
+        .transact()
+        .await?)
+}
+
+pub async fn set_wnear_address(
+    phoenix: &Contract,
+    owner: &Account,
+    wnear_address: Option<&AccountId>,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(owner
+        .call(phoenix.id(), "set_wnear_address")
+        .args_json(json!({ "wnear_address": wnear_address }))
+        .deposit(yocto(1))
+        .transact()
+        .await?)
+}
+
+pub async fn cancel(
+    phoenix: &Contract,
+    account: &Account,
+    note_id: u32,
+) -> anyhow::Result<ExecutionFinalResult> {
+    cancel_with_exit(phoenix, account, note_id, None, None).await
+}
+
+/// `exit` is one of "linear", "delayed_near" and "instant_near", defaults to LiNEAR
+pub async fn cancel_with_exit(
+    phoenix: &Contract,
+    account: &Account,
+    note_id: u32,
+    exit: Option<&str>,
+    min_amount_out: Option<Balance>,
+) -> anyhow::Result<ExecutionFinalResult> {
+    let (deposit, gas) = exit_deposit_and_gas(exit);
+    Ok(account
+        .call(phoenix.id(), "cancel")
+        .args_json(json!({
+            "note_id": note_id,
+            "exit": exit,
+            "min_amount_out": min_amount_out.map(U128),
+        }))
+        .deposit(deposit)
+        .gas(gas)
+        .transact()
+        .await?)
+}
+
+pub async fn commit(
+    phoenix: &Contract,
+    account: &Account,
+    note_id: u32,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(phoenix.id(), "commit")
+        .args_json(json!({ "note_id": note_id }))
+        .deposit(yocto(1))
+        .gas(Gas::from_tgas(90))
+        .transact()
+        .await?)
+}
+
+pub async fn redeem(
+    phoenix: &Contract,
+    account: &Account,
+    amount: Balance,
+) -> anyhow::Result<ExecutionFinalResult> {
+    redeem_with_exit(phoenix, account, amount, None, None).await
+}
+
+/// `exit` is one of "linear", "delayed_near" and "instant_near", defaults to LiNEAR
+pub async fn redeem_with_exit(
+    phoenix: &Contract,
+    account: &Account,
+    amount: Balance,
+    exit: Option<&str>,
+    min_amount_out: Option<Balance>,
+) -> anyhow::Result<ExecutionFinalResult> {
+    let (deposit, gas) = exit_deposit_and_gas(exit);
+    Ok(account
+        .call(phoenix.id(), "redeem")
+        .args_json(json!({
+            "amount": U128(amount),
+            "exit": exit,
+            "min_amount_out": min_amount_out.map(U128),
+        }))
+        .deposit(deposit)
+        .gas(gas)
+        .transact()
+        .await?)
+}
+
+pub async fn withdraw_unstaked(
+    phoenix: &Contract,
+    account: &Account,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(phoenix.id(), "withdraw_unstaked")
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?)
+}
+
+pub async fn get_unstake_ticket(phoenix: &Contract, account: &Account) -> anyhow::Result<Value> {
+    Ok(phoenix
+        .view("get_unstake_ticket")
+        .args_json(json!({ "account_id": account.id() }))
+        .await?
+        .json()?)
+}
+
+/// `asset` is one of "linear", "near" and "pnear", defaults to LiNEAR
+pub async fn get_user_lost_and_found(
+    phoenix: &Contract,
+    account: &Account,
+    asset: Option<&str>,
+) -> anyhow::Result<Balance> {
+    Ok(phoenix
+        .view("user_lost_and_found")
+        .args_json(json!({ "account_id": account.id(), "asset": asset }))
+        .await?
+        .json::<U128>()?
+        .0)
+}
+
+pub async fn claim_lost_and_found(
+    phoenix: &Contract,
+    account: &Account,
+    asset: Option<&str>,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(phoenix.id(), "claim_lost_and_found")
+        .args_json(json!({ "asset": asset }))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?)
+}
+
+pub async fn claim_lost_and_found_to(
+    phoenix: &Contract,
+    account: &Account,
+    receiver: &Account,
+    msg: Option<&str>,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(phoenix.id(), "claim_lost_and_found_to")
+        .args_json(json!({ "receiver_id": receiver.id(), "msg": msg }))
+        .gas(Gas::from_tgas(150))
+        .transact()
+        .await?)
+}
+
+pub async fn withdraw_treasury(
+    phoenix: &Contract,
+    account: &Account,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(phoenix.id(), "withdraw_treasury")
+        .deposit(yocto(1))
+        .gas(Gas::from_tgas(160))
+        .transact()
+        .await?)
+}
+
+pub async fn set_referral_reward_rate(
+    phoenix: &Contract,
+    owner: &Account,
+    rate: BasisPoint,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(owner
+        .call(phoenix.id(), "set_referral_reward_rate")
+        .args_json(json!({ "rate": rate }))
+        .deposit(yocto(1))
+        .transact()
+        .await?)
+}
+
+pub async fn get_referrer_stats(phoenix: &Contract, account: &Account) -> anyhow::Result<Value> {
+    Ok(phoenix
+        .view("get_referrer_stats")
+        .args_json(json!({ "account_id": account.id() }))
+        .await?
+        .json()?)
+}
+
+pub async fn claim_referral_rewards(
+    phoenix: &Contract,
+    account: &Account,
+) -> anyhow::Result<ExecutionFinalResult> {
+    Ok(account
+        .call(phoenix.id(), "claim_referral_rewards")
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?)
+}
@@ -0,0 +1,221 @@
+use near_workspaces::{
+    types::{Gas, NearToken},
+    Account,
+};
+use phoenix_bonds::{BondNoteInfo, BondStatus};
+use phoenix_bonds_integration_tests::*;
+use phoenix_math::{apply_basis_point, FULL_BASIS_POINT};
+
+fn verify_new_bond_note(note: &BondNoteInfo, account: &Account, amount: Balance) {
+    assert_eq!(note.account_id.as_str(), account.id().as_str());
+    assert_eq!(note.bond_amount, amount);
+    assert_eq!(note.committed_pnear_amount, 0);
+    assert_eq!(note.settled_at, 0);
+    assert!(note.status == BondStatus::Pending);
+    assert_eq!(note.cap, apply_basis_point(amount, FULL_BASIS_POINT - TAU));
+}
+
+// ---- Bond with NEAR
+
+#[tokio::test]
+async fn test_bond_with_small_amount() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    // bond less than 0.01
+    let result = alice
+        .call(phoenix.id(), "bond")
+        .deposit(NearToken::from_yoctonear(near(9, 3)))
+        .gas(Gas::from_tgas(120))
+        .transact()
+        .await?;
+    assert_failure(result, "Bond requires 0.01 NEAR as storage deposit");
+
+    assert_failure(
+        bond(alice, phoenix, near(9, 2)).await?,
+        "Bond amount must be at least 0.1 NEAR",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bond_with_multiple_accounts() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    let linear_price = get_linear_price(linear).await?;
+
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    let alice_note = get_bond_note(phoenix, alice, note_id, linear_price).await?;
+    verify_new_bond_note(&alice_note, alice, 100 * ONE_NEAR);
+
+    let bob_note_id: u32 = bond(bob, phoenix, 30000 * ONE_NEAR).await?.json()?;
+    let bob_note = get_bond_note(phoenix, bob, bob_note_id, linear_price).await?;
+    verify_new_bond_note(&bob_note, bob, 30000 * ONE_NEAR);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bond_multiple_times() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    let linear_price = get_linear_price(linear).await?;
+
+    let note_id_1: u32 = bond(alice, phoenix, 200 * ONE_NEAR).await?.json()?;
+    let note_1 = get_bond_note(phoenix, alice, note_id_1, linear_price).await?;
+    verify_new_bond_note(&note_1, alice, 200 * ONE_NEAR);
+
+    let note_id_2: u32 = bond(alice, phoenix, 9999 * ONE_NEAR).await?.json()?;
+    let note_2 = get_bond_note(phoenix, alice, note_id_2, linear_price).await?;
+    verify_new_bond_note(&note_2, alice, 9999 * ONE_NEAR);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bond_failed_and_deposits_refunded() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+
+    // bond will panic
+    set_linear_panic(linear, true).await?;
+
+    let balance_before = alice.view_account().await?.balance.as_yoctonear();
+    // 900K NEAR + 0.01 NEAR storage fee
+    let note_id: Option<u32> = bond(alice, phoenix, near(90000001, 2)).await?.json()?;
+    assert_eq!(note_id, None);
+
+    let balance_after = alice.view_account().await?.balance.as_yoctonear();
+    // the gas cost should be less than 0.002N
+    assert!(balance_before - balance_after < near(2, 3));
+    Ok(())
+}
+
+// ---- Bond with LiNEAR
+
+#[tokio::test]
+async fn test_wrong_token_transferred() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        fake_linear,
+        ..
+    } = &fixtures;
+    let amount = 1000 * ONE_NEAR;
+
+    // mint some fake linear
+    mint_linear(alice, fake_linear, amount).await?;
+    ft_storage_deposit(fake_linear, phoenix.as_account()).await?;
+
+    bond_with_linear(alice, phoenix, fake_linear, amount).await?;
+
+    // all fake tokens should be refunded
+    assert_eq!(get_ft_balance(fake_linear, alice).await?, amount);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_linear_amount_too_low() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    let amount = ONE_NEAR;
+
+    mint_linear(alice, linear, amount).await?;
+    ft_storage_deposit(linear, phoenix.as_account()).await?;
+
+    bond_with_linear(alice, phoenix, linear, near(1, 1)).await?;
+
+    assert_eq!(get_ft_balance(linear, alice).await?, amount);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_wrong_bond_msg() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    let amount = 100 * ONE_NEAR;
+
+    mint_linear(alice, linear, amount).await?;
+    ft_storage_deposit(linear, phoenix.as_account()).await?;
+
+    alice
+        .call(linear.id(), "ft_transfer_call")
+        .args_json(serde_json::json!({
+            "receiver_id": phoenix.id(),
+            "amount": amount.to_string(),
+            "msg": "",
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(120))
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert_eq!(get_ft_balance(linear, alice).await?, amount);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bond_with_linear() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    let amount = 1000 * ONE_NEAR;
+
+    mint_linear(alice, linear, amount).await?;
+    mint_linear(bob, linear, amount).await?;
+    ft_storage_deposit(linear, phoenix.as_account()).await?;
+
+    let linear_price = get_linear_price(linear).await?;
+    let pnear_price_before_bond = get_pnear_price(phoenix, linear_price).await?;
+
+    // let bob bond and commit some pNEAR first
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    bond(bob, phoenix, amount).await?.into_result()?;
+    set_timestamp(phoenix, days_to_ms(30)).await?;
+    commit(phoenix, bob, 0).await?.into_result()?;
+
+    let used_amount = bond_with_linear(alice, phoenix, linear, amount).await?;
+    assert_eq!(used_amount, amount);
+
+    let note = get_bond_note(phoenix, alice, 0, ONE_NEAR).await?;
+    // 0.01 NEAR as storage deposit
+    assert_eq!(note.bond_amount, amount - BOND_STORAGE_DEPOSIT);
+
+    let pnear_price_after_bond = get_pnear_price(phoenix, linear_price).await?;
+    assert_eq!(
+        pnear_price_after_bond, pnear_price_before_bond,
+        "pNEAR price should not change"
+    );
+    Ok(())
+}
@@ -0,0 +1,222 @@
+use near_sdk::json_types::U128;
+use near_workspaces::{types::NearToken, Account, Contract};
+use phoenix_bonds_integration_tests::*;
+use phoenix_math::{accrued_amount, apply_basis_point, BasisPoint, FULL_BASIS_POINT};
+use serde_json::{json, Value};
+
+fn cap_of(amount: Balance) -> Balance {
+    apply_basis_point(amount, FULL_BASIS_POINT - TAU)
+}
+
+async fn set_dynamic_tau(phoenix: &Contract, owner: &Account, policy: Value) -> anyhow::Result<()> {
+    owner
+        .call(phoenix.id(), "set_dynamic_tau")
+        .args_json(json!({ "policy": policy }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+async fn get_current_tau(phoenix: &Contract) -> anyhow::Result<BasisPoint> {
+    Ok(phoenix.view("get_current_tau").await?.json()?)
+}
+
+#[tokio::test]
+async fn test_cannot_commit_when_bootstrapping() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 1000 * ONE_NEAR).await?.json()?;
+    assert_failure(
+        commit(phoenix, alice, note_id).await?,
+        "Commit and redeem are not allowed now",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_commit_right_after_bond() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+
+    let note_id: u32 = bond(alice, phoenix, 1000 * ONE_NEAR).await?.json()?;
+    let pnear_amount: U128 = commit(phoenix, alice, note_id).await?.json()?;
+
+    // commit right after bond would get nothing
+    assert_eq!(pnear_amount.0, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cannot_commit_twice() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 1000 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+
+    assert_failure(
+        commit(phoenix, alice, note_id).await?,
+        "Bond is not pending",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_commit_before_any_bond() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    assert_failure(commit(phoenix, alice, 0).await?, "Bond note doesn't exist");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_commit_wrong_note_id() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 1000 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    assert_failure(
+        commit(phoenix, alice, note_id + 1).await?,
+        "Bond note doesn't exist",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_commit_at_alpha_gets_half_cap() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    // bond at day 20
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    let note_id: u32 = bond(alice, phoenix, 1000 * ONE_NEAR).await?.json()?;
+
+    // commit at day 20 + alpha
+    set_timestamp(phoenix, days_to_ms(20) + ALPHA).await?;
+    let pnear_amount: U128 = commit(phoenix, alice, note_id).await?.json()?;
+
+    // since it's the first commit, pnear price would be 1
+    // so alice can get (1000 * (1 - tau) / 2) pNEAR
+    assert_eq!(pnear_amount.0, cap_of(1000 * ONE_NEAR) / 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_commit_multiple_bonds() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+
+    // day 0
+    // - alice bond 100 NEAR
+    let alice_note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+
+    // day 10
+    // - linear price increase to 1.01
+    // - bob bond 500 NEAR
+    set_timestamp(phoenix, days_to_ms(10)).await?;
+    set_linear_price(linear, near(101, 2)).await?;
+    let bob_note_id: u32 = bond(bob, phoenix, 500 * ONE_NEAR).await?.json()?;
+
+    // day 15 (bootstrap ends)
+    // - linear price increase to 1.02
+    // - alice commits
+    set_timestamp(phoenix, BOOTSTRAP_ENDS).await?;
+    set_linear_price(linear, near(102, 2)).await?;
+    let pnear_amount: U128 = commit(phoenix, alice, alice_note_id).await?.json()?;
+    // 100 * (1 - tau) * length / (length + alpha), where length is 15 days
+    assert_eq!(
+        pnear_amount.0,
+        accrued_amount(cap_of(100 * ONE_NEAR), BOOTSTRAP_ENDS, ALPHA)
+    );
+
+    // day 17
+    // - linear price increase to 1.03
+    // - bob commits
+    set_timestamp(phoenix, days_to_ms(17)).await?;
+    set_linear_price(linear, near(103, 2)).await?;
+    let pnear_amount: U128 = commit(phoenix, bob, bob_note_id).await?.json()?;
+    // pnear price is about 1.0736, so cap is: 500 * (1 - tau) / pnear_price ~= 451.75
+    // committed pnear would be cap * length / (length + alpha) ~= 316.22, where length is 7 days
+    assert_eq!(pnear_amount.0, 316221549314521496073472772);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_commit_after_10_years() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    // bond at day 20
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    let note_id: u32 = bond(alice, phoenix, 1000 * ONE_NEAR).await?.json()?;
+
+    // commit after 10 years
+    set_timestamp(phoenix, days_to_ms(365 * 10 + 20)).await?;
+    let pnear_amount: U128 = commit(phoenix, alice, note_id).await?.json()?;
+
+    let summary = get_summary(phoenix, near(101, 2)).await?;
+    // current alpha has decreased to the minimum after 10 years
+    let current_alpha = summary.accrual_parameter.alpha;
+    assert_eq!(current_alpha, 1);
+
+    // since it's the first commit, pnear price would be 1
+    assert_eq!(
+        pnear_amount.0,
+        accrued_amount(cap_of(1000 * ONE_NEAR), days_to_ms(365 * 10), current_alpha)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_commit_with_dynamic_tau() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        owner,
+        phoenix,
+        ..
+    } = &fixtures;
+
+    // tau goes from 5% to 1% as treasury pool approaches 1000 NEAR
+    set_dynamic_tau(
+        phoenix,
+        owner,
+        json!({
+            "treasury_target": U128(1000 * ONE_NEAR),
+            "min_tau": 100,
+            "max_tau": 500,
+        }),
+    )
+    .await?;
+    assert_eq!(get_current_tau(phoenix).await?, 500);
+
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    let note_id: u32 = bond(alice, phoenix, 1000 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(365 * 10 + 20)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+
+    // 50 NEAR goes to treasury pool, so tau is lowered
+    let summary = get_summary(phoenix, ONE_NEAR).await?;
+    assert_eq!(summary.treasury_pool_near_amount.0, 50 * ONE_NEAR);
+    assert_eq!(summary.current_tau, 480);
+    assert_eq!(summary.tau, TAU);
+
+    // static tau is used again once the policy is removed
+    set_dynamic_tau(phoenix, owner, Value::Null).await?;
+    assert_eq!(get_current_tau(phoenix).await?, TAU);
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use near_sdk::json_types::U128;
+use phoenix_bonds::BondStatus;
+use phoenix_bonds_integration_tests::*;
+use phoenix_math::{apply_basis_point, pnear2near, FULL_BASIS_POINT};
+
+const INSTANT_NEAR: Option<&str> = Some("instant_near");
+/// default instant unstake fee of mock LiNEAR
+const INSTANT_UNSTAKE_FEE: u32 = 30;
+
+fn after_fee(amount: Balance, fee: u32) -> Balance {
+    apply_basis_point(amount, FULL_BASIS_POINT - fee)
+}
+
+#[tokio::test]
+async fn test_cancel_for_instant_near() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    let near_balance = get_near_balance(alice).await?;
+    // alice doesn't need to register on LiNEAR
+    let received: U128 =
+        cancel_with_exit(phoenix, alice, note_id, INSTANT_NEAR, Some(99 * ONE_NEAR))
+            .await?
+            .json()?;
+    assert_eq!(received.0, after_fee(100 * ONE_NEAR, INSTANT_UNSTAKE_FEE));
+
+    // gas is paid by alice
+    let balance_increase = get_near_balance(alice).await? - near_balance;
+    assert!(balance_increase > received.0 - ONE_NEAR / 10 && balance_increase < received.0);
+
+    let note = get_bond_note(phoenix, alice, note_id, ONE_NEAR).await?;
+    assert!(note.status == BondStatus::Cancelled);
+    assert_eq!(get_summary(phoenix, ONE_NEAR).await?.linear_balance.0, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_redeem_for_instant_near() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    let pnear_amount: U128 = commit(phoenix, alice, note_id).await?.json()?;
+
+    let linear_price = near(11, 1);
+    set_linear_price(linear, linear_price).await?;
+    // 1%
+    set_instant_unstake_fee(linear, 100).await?;
+    let pnear_price = get_pnear_price(phoenix, linear_price).await?;
+    let redeem_amount = pnear_amount.0 / 2;
+    let received: U128 = redeem_with_exit(phoenix, alice, redeem_amount, INSTANT_NEAR, Some(0))
+        .await?
+        .json()?;
+
+    let expected_near = after_fee(pnear2near(redeem_amount, pnear_price), 100);
+    assert!(received.0.abs_diff(expected_near) < 10);
+    assert_eq!(
+        get_ft_balance(phoenix, alice).await?,
+        pnear_amount.0 - redeem_amount
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_min_amount_out_required() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    assert_failure(
+        cancel_with_exit(phoenix, alice, note_id, INSTANT_NEAR, None).await?,
+        "min_amount_out is required for instant NEAR exit",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_instant_unstake_failed() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+
+    // receives less NEAR than min_amount_out
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    let received: U128 =
+        cancel_with_exit(phoenix, alice, note_id, INSTANT_NEAR, Some(100 * ONE_NEAR))
+            .await?
+            .json()?;
+    assert_eq!(received.0, 0);
+
+    // the note is cancelled anyway, and LiNEAR goes to lost and found
+    let note = get_bond_note(phoenix, alice, note_id, ONE_NEAR).await?;
+    assert!(note.status == BondStatus::Cancelled);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        100 * ONE_NEAR
+    );
+
+    // liquidity pool is not available
+    let note_id: u32 = bond(bob, phoenix, 50 * ONE_NEAR).await?.json()?;
+    set_linear_panic(linear, true).await?;
+    let received: U128 = cancel_with_exit(phoenix, bob, note_id, INSTANT_NEAR, Some(0))
+        .await?
+        .json()?;
+    assert_eq!(received.0, 0);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, bob, None).await?,
+        50 * ONE_NEAR
+    );
+    Ok(())
+}
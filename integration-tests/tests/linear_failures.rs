@@ -0,0 +1,389 @@
+use near_sdk::json_types::U128;
+use near_workspaces::{
+    types::{Gas, NearToken},
+    Account, Contract,
+};
+use phoenix_bonds::BondStatus;
+use phoenix_bonds_integration_tests::*;
+use serde_json::{json, Value};
+
+const ERR_GET_LINEAR_PRICE: &str = "Failed to get LiNEAR price";
+
+#[derive(Clone, Copy)]
+enum PriceFailure {
+    Panic,
+    Zero,
+}
+
+const PRICE_FAILURES: [PriceFailure; 2] = [PriceFailure::Panic, PriceFailure::Zero];
+
+async fn set_price_failure(linear: &Contract, failure: Option<PriceFailure>) -> anyhow::Result<()> {
+    set_ft_price_panic(linear, matches!(failure, Some(PriceFailure::Panic))).await?;
+    set_zero_price(linear, matches!(failure, Some(PriceFailure::Zero))).await
+}
+
+async fn reconcile_linear_balance(
+    phoenix: &Contract,
+    owner: &Account,
+) -> anyhow::Result<near_workspaces::result::ExecutionFinalResult> {
+    Ok(owner
+        .call(phoenix.id(), "reconcile_linear_balance")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?)
+}
+
+// ---- ft_price failures
+
+#[tokio::test]
+async fn test_get_linear_price_failed_when_cancel() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, alice).await?;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+
+    for failure in PRICE_FAILURES {
+        set_price_failure(linear, Some(failure)).await?;
+        assert_failure(cancel(phoenix, alice, note_id).await?, ERR_GET_LINEAR_PRICE);
+
+        let note = get_bond_note(phoenix, alice, note_id, ONE_NEAR).await?;
+        assert!(note.status == BondStatus::Pending);
+    }
+
+    set_price_failure(linear, None).await?;
+    let refund_linear: U128 = cancel(phoenix, alice, note_id).await?.json()?;
+    assert_eq!(refund_linear.0, 100 * ONE_NEAR);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_linear_price_failed_when_commit() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+
+    for failure in PRICE_FAILURES {
+        set_price_failure(linear, Some(failure)).await?;
+        assert_failure(commit(phoenix, alice, note_id).await?, ERR_GET_LINEAR_PRICE);
+
+        let note = get_bond_note(phoenix, alice, note_id, ONE_NEAR).await?;
+        assert!(note.status == BondStatus::Pending);
+        assert_eq!(get_ft_balance(phoenix, alice).await?, 0);
+    }
+
+    set_price_failure(linear, None).await?;
+    let pnear_amount: U128 = commit(phoenix, alice, note_id).await?.json()?;
+    assert!(pnear_amount.0 > 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_linear_price_failed_when_redeem() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, alice).await?;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    let pnear_amount: U128 = commit(phoenix, alice, note_id).await?.json()?;
+
+    for failure in PRICE_FAILURES {
+        set_price_failure(linear, Some(failure)).await?;
+        assert_failure(
+            redeem(phoenix, alice, ONE_NEAR).await?,
+            ERR_GET_LINEAR_PRICE,
+        );
+        assert_eq!(get_ft_balance(phoenix, alice).await?, pnear_amount.0);
+    }
+
+    set_price_failure(linear, None).await?;
+    redeem(phoenix, alice, ONE_NEAR).await?.into_result()?;
+    assert_eq!(
+        get_ft_balance(phoenix, alice).await?,
+        pnear_amount.0 - ONE_NEAR
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_linear_price_failed_when_withdraw_treasury() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        owner,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, owner).await?;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+    let treasury = get_summary(phoenix, ONE_NEAR)
+        .await?
+        .treasury_pool_near_amount
+        .0;
+    assert!(treasury > 0);
+
+    for failure in PRICE_FAILURES {
+        set_price_failure(linear, Some(failure)).await?;
+        assert_failure(
+            withdraw_treasury(phoenix, owner).await?,
+            ERR_GET_LINEAR_PRICE,
+        );
+        let summary = get_summary(phoenix, ONE_NEAR).await?;
+        assert_eq!(summary.treasury_pool_near_amount.0, treasury);
+    }
+
+    set_price_failure(linear, None).await?;
+    withdraw_treasury(phoenix, owner).await?.into_result()?;
+    assert_eq!(get_ft_balance(linear, owner).await?, treasury);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_linear_price_failed_when_reconcile() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        owner,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+
+    for failure in PRICE_FAILURES {
+        set_price_failure(linear, Some(failure)).await?;
+        assert_failure(
+            reconcile_linear_balance(phoenix, owner).await?,
+            ERR_GET_LINEAR_PRICE,
+        );
+    }
+
+    set_price_failure(linear, None).await?;
+    let discrepancy: Value = reconcile_linear_balance(phoenix, owner).await?.json()?;
+    assert_eq!(discrepancy["surplus_linear"], json!(U128(0)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_linear_price_failed_when_bond_with_linear() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    let amount = 100 * ONE_NEAR;
+    mint_linear(alice, linear, amount).await?;
+    ft_storage_deposit(linear, phoenix.as_account()).await?;
+
+    for failure in PRICE_FAILURES {
+        set_price_failure(linear, Some(failure)).await?;
+        // all LiNEAR is refunded when the bond failed
+        assert_eq!(bond_with_linear(alice, phoenix, linear, amount).await?, 0);
+        assert_eq!(get_ft_balance(linear, alice).await?, amount);
+        assert_eq!(notes_count(phoenix, alice).await?, 0);
+    }
+
+    set_price_failure(linear, None).await?;
+    assert_eq!(
+        bond_with_linear(alice, phoenix, linear, amount).await?,
+        amount
+    );
+    assert_eq!(notes_count(phoenix, alice).await?, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancel_after_linear_price_decreased() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, alice).await?;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+
+    // 100 NEAR is worth more LiNEAR than the contract holds,
+    // so the refund is capped by the LiNEAR balance
+    set_linear_price(linear, near(9, 1)).await?;
+    let refund_linear: U128 = cancel(phoenix, alice, note_id).await?.json()?;
+    assert_eq!(refund_linear.0, 100 * ONE_NEAR);
+    assert_eq!(get_summary(phoenix, near(9, 1)).await?.linear_balance.0, 0);
+    Ok(())
+}
+
+// ---- ft_transfer failures
+
+#[tokio::test]
+async fn test_ft_transfer_panic_when_cancel() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, alice).await?;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+
+    set_ft_transfer_panic(linear, true).await?;
+    let refund_linear: U128 = cancel(phoenix, alice, note_id).await?.json()?;
+    assert_eq!(refund_linear.0, 0);
+
+    // the note is cancelled anyway, and LiNEAR goes to lost and found
+    let note = get_bond_note(phoenix, alice, note_id, ONE_NEAR).await?;
+    assert!(note.status == BondStatus::Cancelled);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        100 * ONE_NEAR
+    );
+
+    // claim fails as well, and LiNEAR is kept in lost and found
+    let claimed: U128 = claim_lost_and_found(phoenix, alice, None).await?.json()?;
+    assert_eq!(claimed.0, 0);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        100 * ONE_NEAR
+    );
+
+    set_ft_transfer_panic(linear, false).await?;
+    let claimed: U128 = claim_lost_and_found(phoenix, alice, None).await?.json()?;
+    assert_eq!(claimed.0, 100 * ONE_NEAR);
+    assert_eq!(get_ft_balance(linear, alice).await?, 100 * ONE_NEAR);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unregistered_transfers_when_redeem() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, alice).await?;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+
+    // the redeem and the first claim fail
+    set_unregistered_transfers(linear, 2).await?;
+    let redeemed_linear: U128 = redeem(phoenix, alice, ONE_NEAR).await?.json()?;
+    assert_eq!(redeemed_linear.0, 0);
+    let lost_linear = get_user_lost_and_found(phoenix, alice, None).await?;
+    assert!(lost_linear > 0);
+
+    let claimed: U128 = claim_lost_and_found(phoenix, alice, None).await?.json()?;
+    assert_eq!(claimed.0, 0);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        lost_linear
+    );
+
+    let claimed: U128 = claim_lost_and_found(phoenix, alice, None).await?.json()?;
+    assert_eq!(claimed.0, lost_linear);
+    assert_eq!(get_ft_balance(linear, alice).await?, lost_linear);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ft_transfer_call_failed_when_claim_to() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, bob).await?;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    // linear transfer would fail due to no storage deposit
+    cancel(phoenix, alice, note_id).await?.into_result()?;
+
+    // ft_transfer_call panics
+    set_ft_transfer_panic(linear, true).await?;
+    let used: U128 = claim_lost_and_found_to(phoenix, alice, bob, Some(""))
+        .await?
+        .json()?;
+    assert_eq!(used.0, 0);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        100 * ONE_NEAR
+    );
+    set_ft_transfer_panic(linear, false).await?;
+
+    // ft_transfer_call fails as if bob is not registered
+    set_unregistered_transfers(linear, 1).await?;
+    let used: U128 = claim_lost_and_found_to(phoenix, alice, bob, Some(""))
+        .await?
+        .json()?;
+    assert_eq!(used.0, 0);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        100 * ONE_NEAR
+    );
+
+    // bob doesn't implement ft_on_transfer, so all LiNEAR is refunded
+    let used: U128 = claim_lost_and_found_to(phoenix, alice, bob, Some(""))
+        .await?
+        .json()?;
+    assert_eq!(used.0, 0);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        100 * ONE_NEAR
+    );
+    assert_eq!(get_ft_balance(linear, bob).await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ft_transfer_panic_when_withdraw_treasury() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        owner,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, owner).await?;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+    let summary = get_summary(phoenix, ONE_NEAR).await?;
+
+    // treasury and LiNEAR balance are restored if the transfer failed
+    set_ft_transfer_panic(linear, true).await?;
+    withdraw_treasury(phoenix, owner).await?.into_result()?;
+    let summary_after = get_summary(phoenix, ONE_NEAR).await?;
+    assert_eq!(
+        summary_after.treasury_pool_near_amount,
+        summary.treasury_pool_near_amount
+    );
+    assert_eq!(summary_after.linear_balance, summary.linear_balance);
+    assert_eq!(get_ft_balance(linear, owner).await?, 0);
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use near_sdk::json_types::U128;
+use phoenix_bonds_integration_tests::*;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_linear_transfer_failed_when_cancel() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 9999 * ONE_NEAR).await?.json()?;
+    // linear transfer would fail due to no storage deposit
+    let transferred_amount: U128 = cancel(phoenix, alice, note_id).await?.json()?;
+    assert_eq!(transferred_amount.0, 0);
+
+    ft_storage_deposit(linear, alice).await?;
+    let claimed: U128 = claim_lost_and_found(phoenix, alice, None).await?.json()?;
+    assert_eq!(claimed.0, 9999 * ONE_NEAR);
+
+    // try to claim again
+    assert_failure(
+        claim_lost_and_found(phoenix, alice, None).await?,
+        "No lost and found LiNEAR to claim",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_linear_transfer_failed_when_redeem() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, alice).await?;
+
+    let note_id: u32 = bond(alice, phoenix, 9999 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+
+    // transfer pNEAR to bob
+    ft_storage_deposit(phoenix, bob).await?;
+    ft_transfer(phoenix, alice, bob, 10 * ONE_NEAR).await?;
+
+    // bob redeem
+    // linear transfer would fail due to no storage deposit
+    let redeemed_linear: U128 = redeem(phoenix, bob, 10 * ONE_NEAR).await?.json()?;
+    assert_eq!(redeemed_linear.0, 0);
+
+    ft_storage_deposit(linear, bob).await?;
+    let claimed: U128 = claim_lost_and_found(phoenix, bob, None).await?.json()?;
+    assert_eq!(claimed.0, 10 * ONE_NEAR);
+
+    // try to claim again
+    assert_failure(
+        claim_lost_and_found(phoenix, bob, None).await?,
+        "No lost and found LiNEAR to claim",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_linear_transfer_failed_when_claim() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 9999 * ONE_NEAR).await?.json()?;
+    // linear transfer would fail due to no storage deposit
+    let transferred_amount: U128 = cancel(phoenix, alice, note_id).await?.json()?;
+    assert_eq!(transferred_amount.0, 0);
+
+    // linear transfer would fail due to no storage deposit
+    let claimed: U128 = claim_lost_and_found(phoenix, alice, None).await?.json()?;
+    assert_eq!(claimed.0, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_claim_lost_and_found_to_another_account() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 9999 * ONE_NEAR).await?.json()?;
+    // linear transfer would fail due to no storage deposit
+    cancel(phoenix, alice, note_id).await?.into_result()?;
+
+    // bob is not registered either, LiNEAR goes back to lost and found
+    let claimed: U128 = claim_lost_and_found_to(phoenix, alice, bob, None)
+        .await?
+        .json()?;
+    assert_eq!(claimed.0, 0);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        9999 * ONE_NEAR
+    );
+
+    ft_storage_deposit(linear, bob).await?;
+    let claimed: U128 = claim_lost_and_found_to(phoenix, alice, bob, None)
+        .await?
+        .json()?;
+    assert_eq!(claimed.0, 9999 * ONE_NEAR);
+    assert_eq!(get_ft_balance(linear, bob).await?, 9999 * ONE_NEAR);
+    assert_eq!(get_user_lost_and_found(phoenix, alice, None).await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_lost_and_found_entries() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        ..
+    } = &fixtures;
+
+    let alice_note: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    let bob_note: u32 = bond(bob, phoenix, 200 * ONE_NEAR).await?.json()?;
+    cancel(phoenix, alice, alice_note).await?.into_result()?;
+    cancel(phoenix, bob, bob_note).await?.into_result()?;
+
+    let entries: Value = phoenix
+        .view("list_lost_and_found")
+        .args_json(json!({ "asset": "linear", "limit": 10 }))
+        .await?
+        .json()?;
+    assert_eq!(
+        entries,
+        json!([
+            { "account_id": bob.id(), "amount": U128(200 * ONE_NEAR) },
+            { "account_id": alice.id(), "amount": U128(100 * ONE_NEAR) },
+        ])
+    );
+
+    let next_page: Value = phoenix
+        .view("list_lost_and_found")
+        .args_json(json!({ "asset": "linear", "after": entries[0], "limit": 10 }))
+        .await?
+        .json()?;
+    assert_eq!(next_page, json!([entries[1]]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nothing_to_claim_from_near_and_pnear() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    for (asset, name) in [("near", "NEAR"), ("pnear", "pNEAR")] {
+        assert_eq!(
+            get_user_lost_and_found(phoenix, alice, Some(asset)).await?,
+            0
+        );
+
+        assert_failure(
+            claim_lost_and_found(phoenix, alice, Some(asset)).await?,
+            &format!("No lost and found {} to claim", name),
+        );
+    }
+    Ok(())
+}
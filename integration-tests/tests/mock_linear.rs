@@ -0,0 +1,160 @@
+use near_sdk::json_types::U128;
+use phoenix_bonds_integration_tests::*;
+use phoenix_math::{linear2near, pnear2near};
+use serde_json::json;
+
+/// Epochs last about 12 hours
+const EPOCH_MS: u64 = ONE_DAY_MS / 2;
+
+#[tokio::test]
+async fn test_unstake_and_withdraw() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, linear, .. } = &fixtures;
+    mint_linear(alice, linear, 100 * ONE_NEAR).await?;
+
+    unstake(alice, linear, 40 * ONE_NEAR).await?.into_result()?;
+    assert_eq!(get_ft_balance(linear, alice).await?, 60 * ONE_NEAR);
+
+    let epoch_height = get_epoch_height(linear).await?;
+    let details = get_account_details(linear, alice).await?;
+    assert_eq!(details["unstaked_balance"], json!(U128(40 * ONE_NEAR)));
+    assert_eq!(details["staked_balance"], json!(U128(60 * ONE_NEAR)));
+    assert_eq!(
+        details["unstaked_available_epoch_height"],
+        json!(epoch_height + 4)
+    );
+    assert_eq!(details["can_withdraw"], json!(false));
+
+    assert_failure(
+        withdraw(alice, linear, 40 * ONE_NEAR).await?,
+        "The unstaked balance is not yet available due to unstaking delay",
+    );
+
+    advance_epochs(linear, 4).await?;
+    let near_balance = alice.view_account().await?.balance.as_yoctonear();
+    withdraw(alice, linear, 40 * ONE_NEAR)
+        .await?
+        .into_result()?;
+
+    // gas is paid by alice
+    let received = alice.view_account().await?.balance.as_yoctonear() - near_balance;
+    assert!(received > near(399, 1) && received <= 40 * ONE_NEAR);
+    let details = get_account_details(linear, alice).await?;
+    assert_eq!(details["unstaked_balance"], json!(U128(0)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cannot_unstake_or_withdraw_too_much() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, linear, .. } = &fixtures;
+    mint_linear(alice, linear, 100 * ONE_NEAR).await?;
+
+    assert_failure(
+        unstake(alice, linear, 101 * ONE_NEAR).await?,
+        "The account doesn't have enough balance",
+    );
+
+    unstake(alice, linear, 10 * ONE_NEAR).await?.into_result()?;
+    advance_epochs(linear, 4).await?;
+    assert_failure(
+        withdraw(alice, linear, 11 * ONE_NEAR).await?,
+        "Not enough unstaked balance to withdraw",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unstake_postpones_unlock() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, linear, .. } = &fixtures;
+    mint_linear(alice, linear, 100 * ONE_NEAR).await?;
+
+    unstake(alice, linear, 10 * ONE_NEAR).await?.into_result()?;
+    advance_epochs(linear, 3).await?;
+    unstake(alice, linear, 10 * ONE_NEAR).await?.into_result()?;
+
+    // all unstaked NEAR is locked until 4 epochs after the last unstake
+    advance_epochs(linear, 1).await?;
+    assert_failure(
+        withdraw(alice, linear, 10 * ONE_NEAR).await?,
+        "The unstaked balance is not yet available due to unstaking delay",
+    );
+    advance_epochs(linear, 3).await?;
+    withdraw(alice, linear, 20 * ONE_NEAR)
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epoch_rewards() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, linear, .. } = &fixtures;
+    mint_linear(alice, linear, 100 * ONE_NEAR).await?;
+
+    // 0.1% per epoch
+    set_epoch_reward_rate(linear, 1000).await?;
+    advance_epochs(linear, 2).await?;
+    assert_eq!(get_linear_price(linear).await?, near(1_002_001, 6));
+
+    // LiNEAR amount stays the same, while it's worth more NEAR
+    assert_eq!(get_ft_balance(linear, alice).await?, 100 * ONE_NEAR);
+    let details = get_account_details(linear, alice).await?;
+    assert_eq!(details["staked_balance"], json!(U128(near(1_002_001, 4))));
+
+    // newly staked NEAR gets less LiNEAR
+    let bob = &fixtures.bob;
+    mint_linear(bob, linear, near(1_002_001, 4)).await?;
+    assert_eq!(get_ft_balance(linear, bob).await?, 100 * ONE_NEAR);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_phoenix_bonds_over_epochs() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    // about 10% APR
+    set_epoch_reward_rate(linear, 137).await?;
+
+    // alice bonds at the beginning, and bob bonds after 10 days
+    let alice_note: u32 = bond(alice, phoenix, 1000 * ONE_NEAR).await?.json()?;
+    advance_epochs(linear, 20).await?;
+    set_timestamp(phoenix, 20 * EPOCH_MS).await?;
+    let bob_note: u32 = bond(bob, phoenix, 1000 * ONE_NEAR).await?.json()?;
+
+    // both commit at day 20
+    advance_epochs(linear, 20).await?;
+    set_timestamp(phoenix, 40 * EPOCH_MS).await?;
+    let alice_pnear: U128 = commit(phoenix, alice, alice_note).await?.json()?;
+    let bob_pnear: U128 = commit(phoenix, bob, bob_note).await?.json()?;
+    // alice bonded longer, so she gets more pNEAR
+    assert!(alice_pnear.0 > bob_pnear.0);
+
+    let linear_price = get_linear_price(linear).await?;
+    let pnear_price = get_pnear_price(phoenix, linear_price).await?;
+    // staking rewards before the first commit go to treasury
+    assert!(pnear_price.abs_diff(ONE_NEAR) < ONE_NEAR / 1_000_000);
+
+    // staking rewards in the following 30 days go to pNEAR holders
+    advance_epochs(linear, 60).await?;
+    set_timestamp(phoenix, 100 * EPOCH_MS).await?;
+    let new_linear_price = get_linear_price(linear).await?;
+    let new_pnear_price = get_pnear_price(phoenix, new_linear_price).await?;
+    assert!(new_linear_price > linear_price);
+    assert!(new_pnear_price > pnear_price);
+
+    // alice redeems all pNEAR for LiNEAR worth of the same NEAR
+    ft_storage_deposit(linear, alice).await?;
+    let redeemed_linear: U128 = redeem(phoenix, alice, alice_pnear.0).await?.json()?;
+    let redeemed_near = linear2near(redeemed_linear.0, new_linear_price);
+    let expected_near = pnear2near(alice_pnear.0, new_pnear_price);
+    assert!(redeemed_near.abs_diff(expected_near) < ONE_NEAR / 1000);
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use near_sdk::json_types::U128;
+use near_workspaces::{types::NearToken, Account, Contract};
+use phoenix_bonds_integration_tests::*;
+use phoenix_math::{near2linear, BasisPoint};
+use serde_json::json;
+
+async fn set_redeem_fee(
+    phoenix: &Contract,
+    owner: &Account,
+    fee: BasisPoint,
+) -> anyhow::Result<()> {
+    owner
+        .call(phoenix.id(), "set_redeem_fee")
+        .args_json(json!({ "new_fee": fee }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cannot_redeem_when_bootstrapping() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    assert_failure(
+        redeem(phoenix, alice, 0).await?,
+        "Commit and redeem are not allowed now",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cannot_redeem_zero_amount() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+
+    assert_failure(
+        redeem(phoenix, alice, 0).await?,
+        "Redeem amount cannot be 0",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cannot_redeem_with_zero_balance() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+
+    assert_failure(
+        redeem(phoenix, alice, 1).await?,
+        &format!("The account {} is not registered", alice.id()),
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cannot_redeem_more_than_balance() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+
+    let pnear_balance = get_ft_balance(phoenix, alice).await?;
+
+    assert_failure(
+        redeem(phoenix, alice, pnear_balance + 1).await?,
+        "Not enough pNEAR balance",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cannot_burn_all_pnear() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 3000 * ONE_NEAR).await?.json()?;
+
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    let pnear_balance: U128 = commit(phoenix, alice, note_id).await?.json()?;
+
+    assert_failure(
+        redeem(phoenix, alice, pnear_balance.0).await?,
+        "At least one pNEAR must be left",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_redeem_linear() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, alice).await?;
+    ft_storage_deposit(linear, bob).await?;
+
+    // day 0
+    // - alice bond 100 NEAR
+    let alice_note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+
+    // day 10
+    // - linear price increase to 1.01
+    // - bob bond 500 NEAR
+    set_timestamp(phoenix, days_to_ms(10)).await?;
+    set_linear_price(linear, near(101, 2)).await?;
+    let bob_note_id: u32 = bond(bob, phoenix, 500 * ONE_NEAR).await?.json()?;
+
+    // day 15 (bootstrap ends)
+    // - linear price increase to 1.02
+    // - alice commits
+    // - pNEAR price will be 1
+    set_timestamp(phoenix, BOOTSTRAP_ENDS).await?;
+    set_linear_price(linear, near(102, 2)).await?;
+    commit(phoenix, alice, alice_note_id).await?.into_result()?;
+
+    let alice_redeemed_linear: U128 = redeem(phoenix, alice, ONE_NEAR).await?.json()?;
+    // alice should get LiNEAR whose value equals to exact 1 NEAR
+    assert_eq!(alice_redeemed_linear.0, near2linear(ONE_NEAR, near(102, 2)));
+
+    // day 17
+    // - linear price increase to 1.03
+    // - bob commits
+    // - pNEAR price is about 1.0744 now
+    set_timestamp(phoenix, days_to_ms(17)).await?;
+    set_linear_price(linear, near(103, 2)).await?;
+    commit(phoenix, bob, bob_note_id).await?.into_result()?;
+
+    // bob redeem 1 pNEAR
+    let bob_redeemed_linear: U128 = redeem(phoenix, bob, ONE_NEAR).await?.json()?;
+    assert_eq!(bob_redeemed_linear.0, 1043120065605160302135142);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_redeem_fee_stays_in_reserve_pool() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        owner,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, alice).await?;
+
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, BOOTSTRAP_ENDS).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+
+    // 1% redeem fee
+    set_redeem_fee(phoenix, owner, 100).await?;
+    let summary = get_summary(phoenix, ONE_NEAR).await?;
+    assert_eq!(summary.redeem_fee, 100);
+
+    // pNEAR price is 1 and LiNEAR price is 1
+    let redeemed_linear: U128 = redeem(phoenix, alice, 10 * ONE_NEAR).await?.json()?;
+    assert_eq!(redeemed_linear.0, near(99, 1));
+
+    // the fee is left in the reserve pool, so pNEAR price grows
+    assert!(get_pnear_price(phoenix, ONE_NEAR).await? > ONE_NEAR);
+    Ok(())
+}
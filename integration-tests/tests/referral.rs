@@ -0,0 +1,145 @@
+use near_sdk::json_types::U128;
+use phoenix_bonds_integration_tests::*;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_referrer_rewarded_and_claimed() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        owner,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    // 10% of the treasury cut
+    set_referral_reward_rate(phoenix, owner, 1000)
+        .await?
+        .into_result()?;
+    ft_storage_deposit(phoenix, bob).await?;
+
+    let note_id: u32 = bond_with_referrer(alice, phoenix, 100 * ONE_NEAR, Some(bob))
+        .await?
+        .json()?;
+    let note = get_bond_note(phoenix, alice, note_id, ONE_NEAR).await?;
+    assert_eq!(note.referrer_id.unwrap().as_str(), bob.id().as_str());
+    // nothing is rewarded until the note is committed
+    assert_eq!(get_referrer_stats(phoenix, bob).await?, Value::Null);
+
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+
+    // tau is 3%, so the treasury cut is 3 NEAR
+    let reward = near(3, 1);
+    assert_eq!(
+        get_referrer_stats(phoenix, bob).await?,
+        json!({
+            "committed_notes": 1,
+            "committed_bond_amount": U128(100 * ONE_NEAR),
+            "total_reward_linear": U128(reward),
+            "unclaimed_reward_linear": U128(reward),
+        })
+    );
+    let summary = get_summary(phoenix, ONE_NEAR).await?;
+    assert_eq!(summary.treasury_pool_near_amount.0, 3 * ONE_NEAR - reward);
+    assert_eq!(summary.total_unclaimed_referral_linear.0, reward);
+
+    ft_storage_deposit(linear, bob).await?;
+    let claimed: U128 = claim_referral_rewards(phoenix, bob).await?.json()?;
+    assert_eq!(claimed.0, reward);
+    assert_eq!(get_ft_balance(linear, bob).await?, reward);
+    let stats = get_referrer_stats(phoenix, bob).await?;
+    assert_eq!(stats["unclaimed_reward_linear"], json!(U128(0)));
+    assert_eq!(stats["total_reward_linear"], json!(U128(reward)));
+
+    assert_failure(
+        claim_referral_rewards(phoenix, bob).await?,
+        "No referral rewards to claim",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bond_with_linear_and_referrer() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    mint_linear(alice, linear, 100 * ONE_NEAR).await?;
+    ft_storage_deposit(linear, phoenix.as_account()).await?;
+    ft_storage_deposit(phoenix, bob).await?;
+
+    let used =
+        bond_with_linear_and_referrer(alice, phoenix, linear, 100 * ONE_NEAR, Some(bob)).await?;
+    assert_eq!(used, 100 * ONE_NEAR);
+    let note = get_bond_note(phoenix, alice, 0, ONE_NEAR).await?;
+    assert_eq!(note.referrer_id.unwrap().as_str(), bob.id().as_str());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cannot_refer_yourself() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    assert_failure(
+        bond_with_referrer(alice, phoenix, 100 * ONE_NEAR, Some(alice)).await?,
+        "Cannot refer yourself",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_referrer_must_be_registered() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        ..
+    } = &fixtures;
+
+    assert_failure(
+        bond_with_referrer(alice, phoenix, 100 * ONE_NEAR, Some(bob)).await?,
+        "Referrer is not registered on pNEAR",
+    );
+
+    ft_storage_deposit(phoenix, bob).await?;
+    bond_with_referrer(alice, phoenix, 100 * ONE_NEAR, Some(bob))
+        .await?
+        .into_result()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_set_referral_reward_rate() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        owner,
+        phoenix,
+        ..
+    } = &fixtures;
+
+    assert_failure(
+        set_referral_reward_rate(phoenix, alice, 1000).await?,
+        "Not owner",
+    );
+    assert_failure(
+        set_referral_reward_rate(phoenix, owner, 10001).await?,
+        "Invalid referral reward rate",
+    );
+    set_referral_reward_rate(phoenix, owner, 500)
+        .await?
+        .into_result()?;
+    assert_eq!(
+        get_summary(phoenix, ONE_NEAR).await?.referral_reward_rate,
+        500
+    );
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use phoenix_bonds_integration_tests::*;
+use phoenix_math::{apply_basis_point, near2linear};
+
+#[tokio::test]
+async fn test_only_owner_can_withdraw() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures { alice, phoenix, .. } = &fixtures;
+
+    assert_failure(withdraw_treasury(phoenix, alice).await?, "Not owner");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_withdraw() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        owner,
+        ..
+    } = &fixtures;
+    ft_storage_deposit(linear, owner).await?;
+
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    let note_id: u32 = bond(alice, phoenix, 4000 * ONE_NEAR).await?.json()?;
+
+    set_timestamp(phoenix, days_to_ms(30)).await?;
+    commit(phoenix, alice, note_id).await?.into_result()?;
+
+    set_linear_price(linear, near(11, 1)).await?;
+
+    withdraw_treasury(phoenix, owner).await?.into_result()?;
+
+    // tau of the bond goes to treasury, which is withdrawn as LiNEAR
+    assert_eq!(
+        get_ft_balance(linear, owner).await?,
+        near2linear(apply_basis_point(4000 * ONE_NEAR, TAU), near(11, 1))
+    );
+    Ok(())
+}
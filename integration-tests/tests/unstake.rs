@@ -0,0 +1,195 @@
+use near_sdk::json_types::U128;
+use phoenix_bonds::BondStatus;
+use phoenix_bonds_integration_tests::*;
+use phoenix_math::pnear2near;
+use serde_json::{json, Value};
+
+const DELAYED_NEAR: Option<&str> = Some("delayed_near");
+
+async fn next_epochs(fixtures: &Fixtures, count: u64) -> anyhow::Result<()> {
+    advance_epochs(&fixtures.linear, count).await?;
+    sync_epoch_height(&fixtures.phoenix, &fixtures.linear).await
+}
+
+#[tokio::test]
+async fn test_cancel_for_near() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    sync_epoch_height(phoenix, linear).await?;
+    let epoch = get_epoch_height(linear).await?;
+
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    // alice doesn't need to register on LiNEAR
+    let unstaked: U128 = cancel_with_exit(phoenix, alice, note_id, DELAYED_NEAR, None)
+        .await?
+        .json()?;
+    assert_eq!(unstaked.0, 100 * ONE_NEAR);
+
+    let note = get_bond_note(phoenix, alice, note_id, ONE_NEAR).await?;
+    assert!(note.status == BondStatus::Cancelled);
+    assert_eq!(
+        get_unstake_ticket(phoenix, alice).await?,
+        json!({ "amount": U128(100 * ONE_NEAR), "unlock_epoch": epoch + 4 })
+    );
+    let summary = get_summary(phoenix, ONE_NEAR).await?;
+    assert_eq!(summary.linear_balance.0, 0);
+    assert_eq!(summary.total_unstaked_near.0, 100 * ONE_NEAR);
+
+    // NEAR is locked for 4 epochs
+    next_epochs(&fixtures, 3).await?;
+    assert_failure(
+        withdraw_unstaked(phoenix, alice).await?,
+        "Unstaked NEAR is not yet available",
+    );
+
+    next_epochs(&fixtures, 1).await?;
+    let near_balance = get_near_balance(alice).await?;
+    // the storage deposit of the ticket is refunded too
+    let withdrawn: U128 = withdraw_unstaked(phoenix, alice).await?.json()?;
+    assert_eq!(withdrawn.0, 100 * ONE_NEAR + UNSTAKE_TICKET_STORAGE_DEPOSIT);
+    // gas is paid by alice
+    let received = get_near_balance(alice).await? - near_balance;
+    assert!(received > near(999, 1) && received <= withdrawn.0);
+
+    assert_eq!(get_unstake_ticket(phoenix, alice).await?, Value::Null);
+    let summary = get_summary(phoenix, ONE_NEAR).await?;
+    assert_eq!(summary.total_unstaked_near.0, 0);
+
+    assert_failure(
+        withdraw_unstaked(phoenix, alice).await?,
+        "No unstaked NEAR to withdraw",
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_redeem_for_near() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    sync_epoch_height(phoenix, linear).await?;
+
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_timestamp(phoenix, days_to_ms(20)).await?;
+    let pnear_amount: U128 = commit(phoenix, alice, note_id).await?.json()?;
+
+    let linear_price = near(11, 1);
+    set_linear_price(linear, linear_price).await?;
+    let pnear_price = get_pnear_price(phoenix, linear_price).await?;
+    let redeem_amount = pnear_amount.0 / 2;
+    let unstaked: U128 = redeem_with_exit(phoenix, alice, redeem_amount, DELAYED_NEAR, None)
+        .await?
+        .json()?;
+
+    // NEAR amount is rounded down when converted from LiNEAR
+    let expected_near = pnear2near(redeem_amount, pnear_price);
+    assert!(unstaked.0 <= expected_near && expected_near - unstaked.0 < 10);
+    assert_eq!(
+        get_ft_balance(phoenix, alice).await?,
+        pnear_amount.0 - redeem_amount
+    );
+
+    next_epochs(&fixtures, 4).await?;
+    let withdrawn: U128 = withdraw_unstaked(phoenix, alice).await?.json()?;
+    assert_eq!(withdrawn.0, unstaked.0 + UNSTAKE_TICKET_STORAGE_DEPOSIT);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unstake_failed() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+
+    let note_id: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    set_linear_panic(linear, true).await?;
+    let unstaked: U128 = cancel_with_exit(phoenix, alice, note_id, DELAYED_NEAR, None)
+        .await?
+        .json()?;
+    assert_eq!(unstaked.0, 0);
+
+    // the note is cancelled anyway, LiNEAR goes to lost and found
+    // and the storage deposit is refunded
+    let note = get_bond_note(phoenix, alice, note_id, ONE_NEAR).await?;
+    assert!(note.status == BondStatus::Cancelled);
+    assert_eq!(
+        get_user_lost_and_found(phoenix, alice, None).await?,
+        100 * ONE_NEAR
+    );
+    assert_eq!(get_unstake_ticket(phoenix, alice).await?, Value::Null);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unstakes_grouped_by_epoch() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        bob,
+        phoenix,
+        linear,
+        ..
+    } = &fixtures;
+    sync_epoch_height(phoenix, linear).await?;
+    let epoch = get_epoch_height(linear).await?;
+
+    let alice_note: u32 = bond(alice, phoenix, 100 * ONE_NEAR).await?.json()?;
+    let bob_note: u32 = bond(bob, phoenix, 50 * ONE_NEAR).await?.json()?;
+    let bob_second_note: u32 = bond(bob, phoenix, 30 * ONE_NEAR).await?.json()?;
+    cancel_with_exit(phoenix, alice, alice_note, DELAYED_NEAR, None)
+        .await?
+        .into_result()?;
+
+    // bob's unstake would postpone alice's NEAR on LiNEAR
+    next_epochs(&fixtures, 3).await?;
+    assert_failure(
+        cancel_with_exit(phoenix, bob, bob_note, DELAYED_NEAR, None).await?,
+        "NEAR unstaked in an earlier epoch is still locked in LiNEAR",
+    );
+
+    // once unlocked, alice's NEAR is released by bob's unstake
+    next_epochs(&fixtures, 1).await?;
+    cancel_with_exit(phoenix, bob, bob_note, DELAYED_NEAR, None)
+        .await?
+        .into_result()?;
+    let details = get_account_details(linear, phoenix.as_account()).await?;
+    assert_eq!(details["unstaked_balance"], json!(U128(50 * ONE_NEAR)));
+
+    // unstakes in the same epoch are unlocked together
+    cancel_with_exit(phoenix, bob, bob_second_note, DELAYED_NEAR, None)
+        .await?
+        .into_result()?;
+    assert_eq!(
+        get_unstake_ticket(phoenix, bob).await?,
+        json!({ "amount": U128(80 * ONE_NEAR), "unlock_epoch": epoch + 8 })
+    );
+
+    // alice is paid with released NEAR, while bob's NEAR is still locked
+    let withdrawn: U128 = withdraw_unstaked(phoenix, alice).await?.json()?;
+    assert_eq!(withdrawn.0, 100 * ONE_NEAR + UNSTAKE_TICKET_STORAGE_DEPOSIT);
+    assert_failure(
+        withdraw_unstaked(phoenix, bob).await?,
+        "Unstaked NEAR is not yet available",
+    );
+
+    // bob's withdraw releases his NEAR from LiNEAR
+    next_epochs(&fixtures, 4).await?;
+    let withdrawn: U128 = withdraw_unstaked(phoenix, bob).await?.json()?;
+    assert_eq!(withdrawn.0, 80 * ONE_NEAR + UNSTAKE_TICKET_STORAGE_DEPOSIT);
+    let summary = get_summary(phoenix, ONE_NEAR).await?;
+    assert_eq!(summary.total_unstaked_near.0, 0);
+    Ok(())
+}
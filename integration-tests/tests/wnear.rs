@@ -0,0 +1,167 @@
+use near_sdk::json_types::U128;
+use phoenix_bonds_integration_tests::*;
+
+#[tokio::test]
+async fn test_bond_with_wnear() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        wnear,
+        ..
+    } = &fixtures;
+    wrap_near(wnear, alice, 100 * ONE_NEAR).await?;
+
+    let used: U128 = bond_with_wnear(alice, phoenix, wnear, 100 * ONE_NEAR)
+        .await?
+        .json()?;
+    assert_eq!(used.0, 100 * ONE_NEAR);
+    assert_eq!(get_ft_balance(wnear, alice).await?, 0);
+    // all wNEAR is unwrapped
+    assert_eq!(get_ft_balance(wnear, phoenix.as_account()).await?, 0);
+
+    // storage deposit is kept out of the bond, same as bonding with NEAR
+    let bond_amount = 100 * ONE_NEAR - BOND_STORAGE_DEPOSIT;
+    let note = get_bond_note(phoenix, alice, 0, ONE_NEAR).await?;
+    assert_eq!(note.bond_amount, bond_amount);
+    assert_eq!(
+        get_ft_balance(linear, phoenix.as_account()).await?,
+        bond_amount
+    );
+    let summary = get_summary(phoenix, ONE_NEAR).await?;
+    assert_eq!(summary.pending_pool_near_amount.0, bond_amount);
+    assert_eq!(
+        summary.wnear_address.map(|id| id.to_string()),
+        Some(wnear.id().to_string())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_wnear_amount_too_low() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        wnear,
+        ..
+    } = &fixtures;
+    wrap_near(wnear, alice, ONE_NEAR).await?;
+
+    let used: U128 = bond_with_wnear(alice, phoenix, wnear, near(10, 2))
+        .await?
+        .json()?;
+    assert_eq!(used.0, 0);
+    assert_eq!(get_ft_balance(wnear, alice).await?, ONE_NEAR);
+    assert_eq!(notes_count(phoenix, alice).await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unwrap_failed() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        wnear,
+        ..
+    } = &fixtures;
+    wrap_near(wnear, alice, 100 * ONE_NEAR).await?;
+    set_wnear_withdraw_panic(wnear, true).await?;
+
+    let used: U128 = bond_with_wnear(alice, phoenix, wnear, 100 * ONE_NEAR)
+        .await?
+        .json()?;
+    assert_eq!(used.0, 0);
+    assert_eq!(get_ft_balance(wnear, alice).await?, 100 * ONE_NEAR);
+    assert_eq!(notes_count(phoenix, alice).await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stake_failed() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        wnear,
+        ..
+    } = &fixtures;
+    wrap_near(wnear, alice, 100 * ONE_NEAR).await?;
+    set_linear_panic(linear, true).await?;
+
+    // unwrapped NEAR is wrapped again and refunded as wNEAR
+    let used: U128 = bond_with_wnear(alice, phoenix, wnear, 100 * ONE_NEAR)
+        .await?
+        .json()?;
+    assert_eq!(used.0, 0);
+    assert_eq!(get_ft_balance(wnear, alice).await?, 100 * ONE_NEAR);
+    assert_eq!(get_ft_balance(wnear, phoenix.as_account()).await?, 0);
+    assert_eq!(notes_count(phoenix, alice).await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stake_and_rewrap_failed() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        phoenix,
+        linear,
+        wnear,
+        ..
+    } = &fixtures;
+    wrap_near(wnear, alice, 100 * ONE_NEAR).await?;
+    set_linear_panic(linear, true).await?;
+    set_wnear_deposit_panic(wnear, true).await?;
+
+    // NEAR is refunded instead
+    let near_balance = get_near_balance(alice).await?;
+    let used: U128 = bond_with_wnear(alice, phoenix, wnear, 100 * ONE_NEAR)
+        .await?
+        .json()?;
+    assert_eq!(used.0, 100 * ONE_NEAR);
+    assert_eq!(get_ft_balance(wnear, alice).await?, 0);
+    // gas is paid by alice
+    let received = get_near_balance(alice).await? - near_balance;
+    assert!(received > near(999, 1) && received <= 100 * ONE_NEAR);
+    assert_eq!(notes_count(phoenix, alice).await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_set_wnear_address() -> anyhow::Result<()> {
+    let fixtures = init().await?;
+    let Fixtures {
+        alice,
+        owner,
+        phoenix,
+        wnear,
+        ..
+    } = &fixtures;
+    wrap_near(wnear, alice, 100 * ONE_NEAR).await?;
+
+    assert_failure(set_wnear_address(phoenix, alice, None).await?, "Not owner");
+    set_wnear_address(phoenix, owner, None)
+        .await?
+        .into_result()?;
+
+    // wNEAR is refunded once it's no longer accepted
+    let used: U128 = bond_with_wnear(alice, phoenix, wnear, 100 * ONE_NEAR)
+        .await?
+        .json()?;
+    assert_eq!(used.0, 0);
+    assert_eq!(get_ft_balance(wnear, alice).await?, 100 * ONE_NEAR);
+
+    set_wnear_address(phoenix, owner, Some(wnear.id()))
+        .await?
+        .into_result()?;
+    let used: U128 = bond_with_wnear(alice, phoenix, wnear, 100 * ONE_NEAR)
+        .await?
+        .json()?;
+    assert_eq!(used.0, 100 * ONE_NEAR);
+    assert_eq!(notes_count(phoenix, alice).await?, 1);
+    Ok(())
+}
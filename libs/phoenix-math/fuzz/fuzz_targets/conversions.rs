@@ -0,0 +1,35 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use phoenix_math::{
+    checked_linear2near, checked_near2linear, checked_near2pnear, checked_pnear2near, ONE_NEAR,
+};
+
+type Conversion = fn(u128, u128) -> Option<u128>;
+
+fuzz_target!(|input: (u128, u128)| {
+    let (amount, price) = input;
+
+    // converting LiNEAR or pNEAR to NEAR and back
+    let round_trips: [(Conversion, Conversion); 2] = [
+        (checked_linear2near, checked_near2linear),
+        (checked_pnear2near, checked_near2pnear),
+    ];
+    for (to_near, from_near) in round_trips.iter() {
+        let near_amount = to_near(amount, price);
+        let _ = from_near(amount, price);
+
+        // round trip error is within one yocto when the token is worth at least one NEAR
+        if price < ONE_NEAR {
+            continue;
+        }
+        if let Some(back) = near_amount.and_then(|near_amount| from_near(near_amount, price)) {
+            assert!(
+                back.abs_diff(amount) <= 1,
+                "round trip of {} at price {} got {}",
+                amount,
+                price,
+                back
+            );
+        }
+    }
+});
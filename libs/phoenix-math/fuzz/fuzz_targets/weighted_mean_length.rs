@@ -0,0 +1,95 @@
+#![no_main]
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use phoenix_math::{Balance, Duration, Timestamp, WeightedMeanLength};
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Insert {
+        amount: Balance,
+        elapsed: Duration,
+    },
+    Remove {
+        index: usize,
+        elapsed: Duration,
+    },
+    /// query mean at a timestamp that could be in the past
+    Mean {
+        ts: Timestamp,
+    },
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let mut va = WeightedMeanLength::new();
+    let mut ts: Timestamp = 0;
+    let mut updated_at: Timestamp = 0;
+    // upper bound of accumulated rounding error of the weighted sum, in weight * ms,
+    // since mean is rounded on each update, `None` if it's too large to track
+    let mut rounding: Option<Balance> = Some(0);
+    // pending bonds of (amount, created_at)
+    let mut bonds: Vec<(Balance, Timestamp)> = vec![];
+
+    for op in ops {
+        match op {
+            Op::Insert { amount, elapsed } => {
+                ts = ts.saturating_add(elapsed);
+                let total_weight = va.total_weight();
+                if va.checked_insert(amount, ts).is_some() {
+                    // mean is always valid after an update
+                    assert!(va.checked_mean(ts).is_some());
+                    bonds.push((amount, ts));
+                    updated_at = ts;
+                    rounding = rounding.and_then(|r| r.checked_add(total_weight / 2 + 1));
+                }
+            }
+            Op::Remove { index, elapsed } => {
+                ts = ts.saturating_add(elapsed);
+                if bonds.is_empty() {
+                    continue;
+                }
+                let index = index % bonds.len();
+                let (amount, created_at) = bonds[index];
+                let total_weight = va.total_weight();
+                if va.checked_remove(amount, ts - created_at, ts).is_some() {
+                    assert!(va.checked_mean(ts).is_some());
+                    bonds.swap_remove(index);
+                    updated_at = ts;
+                    rounding = rounding.and_then(|r| r.checked_add(total_weight / 2 + 1));
+                }
+            }
+            Op::Mean { ts: query_ts } => {
+                let mean = va.checked_mean(query_ts);
+                if query_ts < updated_at {
+                    assert!(mean.is_none());
+                }
+            }
+        }
+
+        let total_weight: Option<Balance> = bonds
+            .iter()
+            .try_fold(0u128, |sum, (amount, _)| sum.checked_add(*amount));
+        assert_eq!(total_weight, Some(va.total_weight()));
+
+        if bonds.is_empty() {
+            rounding = Some(0);
+            assert_eq!(va.checked_mean(ts), Some(0));
+        }
+
+        // mean never exceeds the oldest bond, except drift by rounding on each update
+        if let (Some(mean), Some(oldest), Some(rounding)) = (
+            va.checked_mean(ts),
+            bonds.iter().map(|(_, created_at)| ts - created_at).max(),
+            rounding,
+        ) {
+            // zero amount bonds have no weight
+            let drift = rounding.checked_div(va.total_weight()).unwrap_or(0) + 1;
+            assert!(
+                u128::from(mean) <= u128::from(oldest) + drift,
+                "mean {} exceeds oldest bond {} with drift {}",
+                mean,
+                oldest,
+                drift
+            );
+        }
+    }
+});
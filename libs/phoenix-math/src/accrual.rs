@@ -0,0 +1,632 @@
+use std::{cmp::max, convert::TryInto};
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    apply_basis_point, Balance, BasisPoint, BigDecimal, Duration, Timestamp, FULL_BASIS_POINT,
+};
+
+const ERR_BAD_TIMESTAMP: &str = "Bad timestamp for computing mean";
+const ERR_MEAN_LENGTH_OVERFLOW: &str = "Weighted mean length overflow";
+
+/// Change of alpha or of when mean length exceeded target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlphaAdjustment {
+    pub old_alpha: Duration,
+    pub new_alpha: Duration,
+    pub old_exceeds_target_at: Timestamp,
+    pub new_exceeds_target_at: Timestamp,
+}
+
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+pub struct AccrualParameter {
+    /// last updated alpha value
+    pub alpha: Duration,
+    /// minimum value that alpha could decrease to
+    pub min_alpha: Duration,
+    /// target weighted mean length of all pending bonds
+    pub target_mean_length: Duration,
+    /// alpha decreases each interval after mean length exceeds target
+    pub adjust_interval: Duration,
+    /// how much should alpha decrease in each interval
+    pub adjust_rate: BasisPoint,
+    /// when did mean length exceed target, 0 means mean length was made below target in last operation
+    pub exceeds_target_at: Timestamp,
+    /// volume weighted mean bonding length
+    pub mean_length: WeightedMeanLength,
+}
+
+impl AccrualParameter {
+    pub fn new(
+        init_alpha: Duration,
+        min_alpha: Duration,
+        target_mean_length: Duration,
+        adjust_interval: Duration,
+        adjust_rate: BasisPoint,
+    ) -> AccrualParameter {
+        Self {
+            alpha: init_alpha,
+            min_alpha,
+            target_mean_length,
+            adjust_interval,
+            adjust_rate,
+            exceeds_target_at: 0,
+            mean_length: WeightedMeanLength::new(),
+        }
+    }
+
+    pub fn current_alpha(&self, ts: Timestamp) -> Duration {
+        let current_mean_length = self.mean_length.mean(ts);
+        if current_mean_length <= self.target_mean_length {
+            self.alpha
+        } else {
+            // how long has the mean length exceeded target value
+            // if the mean length doesn't grow naturally, get exceed_length from the
+            // calculated exceeds_target_at
+            let exceed_length = if self.exceeds_target_at == 0 {
+                current_mean_length - self.target_mean_length
+            } else {
+                ts - self.exceeds_target_at
+            };
+            // how many adjustments shall be made
+            let num_adjustments = exceed_length / self.adjust_interval;
+
+            let mut adjusted = self.alpha;
+            for _ in 0..num_adjustments {
+                adjusted = apply_basis_point(adjusted, FULL_BASIS_POINT - self.adjust_rate);
+            }
+
+            max(self.min_alpha, adjusted)
+        }
+    }
+
+    /// Insert a bond into mean length, returns the adjustment of alpha if any
+    pub fn weighted_mean_insert(
+        &mut self,
+        amount: Balance,
+        ts: Timestamp,
+    ) -> Option<AlphaAdjustment> {
+        let alpha_before_insertion = self.current_alpha(ts);
+
+        let old_mean_length = self.mean_length.mean(ts);
+        self.mean_length.insert(amount, ts); // this could only make mean length shorter
+        let new_mean_length = self.mean_length.mean(ts);
+
+        self.handle_mean_length_update(alpha_before_insertion, old_mean_length, new_mean_length, ts)
+    }
+
+    /// Remove a bond from mean length, returns the adjustment of alpha if any
+    pub fn weighted_mean_remove(
+        &mut self,
+        amount: Balance,
+        length: Duration,
+        ts: Timestamp,
+    ) -> Option<AlphaAdjustment> {
+        let alpha_before_removal = self.current_alpha(ts);
+
+        let old_mean_length = self.mean_length.mean(ts);
+        self.mean_length.remove(amount, length, ts);
+        let new_mean_length = self.mean_length.mean(ts);
+
+        self.handle_mean_length_update(alpha_before_removal, old_mean_length, new_mean_length, ts)
+    }
+
+    fn handle_mean_length_update(
+        &mut self,
+        alpha_before_update: Duration,
+        old_mean_length: Duration,
+        new_mean_length: Duration,
+        ts: Timestamp,
+    ) -> Option<AlphaAdjustment> {
+        let old_alpha = self.alpha;
+        let old_exceeds_target_at = self.exceeds_target_at;
+
+        // if mean length drops below target, reset exceeds_target_at
+        if old_mean_length > self.target_mean_length && new_mean_length <= self.target_mean_length {
+            self.alpha = alpha_before_update;
+            self.exceeds_target_at = 0;
+        } else if self.exceeds_target_at == 0 && new_mean_length > self.target_mean_length {
+            // if this action makes the mean length above target, then exceeds_target_at should be now.
+            if old_mean_length <= self.target_mean_length {
+                self.exceeds_target_at = ts;
+            } else {
+                // else if the mean length grows above target naturally
+                // need to find the correct exceeds_target_at
+                self.exceeds_target_at = ts - (old_mean_length - self.target_mean_length);
+            }
+        }
+
+        if self.alpha != old_alpha || self.exceeds_target_at != old_exceeds_target_at {
+            Some(AlphaAdjustment {
+                old_alpha,
+                new_alpha: self.alpha,
+                old_exceeds_target_at,
+                new_exceeds_target_at: self.exceeds_target_at,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+pub struct WeightedMeanLength {
+    /// sum of (bond amount * bond length) for all pending bonds
+    weighted_sum: BigDecimal,
+    /// sum of bond amount for all pending bonds
+    total_weight: u128,
+    /// last update timestamp
+    updated_at: Timestamp,
+}
+
+impl Default for WeightedMeanLength {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WeightedMeanLength {
+    pub fn new() -> Self {
+        Self {
+            weighted_sum: BigDecimal::from(0u128),
+            total_weight: 0,
+            updated_at: 0,
+        }
+    }
+
+    /// Get volume weighted mean bonding length in ms at given timestamp
+    pub fn mean(&self, ts: Timestamp) -> Duration {
+        assert!(ts >= self.updated_at, "{}", ERR_BAD_TIMESTAMP);
+        self.checked_mean(ts).expect(ERR_MEAN_LENGTH_OVERFLOW)
+    }
+
+    /// Same as `mean`, returns `None` on bad timestamp or overflow
+    pub fn checked_mean(&self, ts: Timestamp) -> Option<Duration> {
+        let time_since_last_update = ts.checked_sub(self.updated_at)?;
+        if self.total_weight == 0 {
+            return Some(0);
+        }
+        self.weighted_sum
+            .checked_div(self.total_weight.into())?
+            .checked_add(time_since_last_update.into())?
+            .checked_round_u128()?
+            .try_into()
+            .ok()
+    }
+
+    /// Sum of bond amount for all pending bonds
+    pub fn total_weight(&self) -> Balance {
+        self.total_weight
+    }
+
+    /// Weighted sum as of `ts`, which is rounded by the mean
+    fn checked_updated_sum(&self, ts: Timestamp) -> Option<BigDecimal> {
+        BigDecimal::from(self.checked_mean(ts)?).checked_mul(self.total_weight.into())
+    }
+
+    /// Insert a bond, returns `None` and keeps the state on bad timestamp or overflow
+    pub fn checked_insert(&mut self, amount: Balance, ts: Timestamp) -> Option<()> {
+        let updated = Self {
+            weighted_sum: self.checked_updated_sum(ts)?,
+            total_weight: self.total_weight.checked_add(amount)?,
+            updated_at: ts,
+        };
+        self.replace_if_valid(updated)
+    }
+
+    /// Part of the weighted sum of a bond that lasted for `length` which the
+    /// weighted sum as of `ts` couldn't cover. Removing such a bond resets the
+    /// weighted sum to zero, so the drift should be reported before that.
+    /// It's expected to be small, since it comes from rounding the mean on each update.
+    /// Returns `None` on bad timestamp or overflow.
+    pub fn checked_remove_drift(
+        &self,
+        amount: Balance,
+        length: Duration,
+        ts: Timestamp,
+    ) -> Option<u128> {
+        let removed_sum = BigDecimal::from(amount).checked_mul(length.into())?;
+        Some(
+            removed_sum
+                .checked_sub(self.checked_updated_sum(ts)?)
+                .map_or(0, |drift| drift.round_u128()),
+        )
+    }
+
+    /// Remove a bond that lasted for `length`,
+    /// returns `None` and keeps the state on bad timestamp or overflow
+    pub fn checked_remove(
+        &mut self,
+        amount: Balance,
+        length: Duration,
+        ts: Timestamp,
+    ) -> Option<()> {
+        let removed_sum = BigDecimal::from(amount).checked_mul(length.into())?;
+        let updated = Self {
+            // weighted sum is rounded on each update, which could make it slightly less than
+            // the removed part, e.g. when removing a bond older than the mean.
+            // Drift beyond that is given by `checked_remove_drift`
+            weighted_sum: self
+                .checked_updated_sum(ts)?
+                .checked_sub(removed_sum)
+                .unwrap_or_else(BigDecimal::zero),
+            total_weight: self.total_weight.checked_sub(amount)?,
+            updated_at: ts,
+        };
+        self.replace_if_valid(updated)
+    }
+
+    /// Replace with the updated state if its mean could be computed
+    fn replace_if_valid(&mut self, updated: Self) -> Option<()> {
+        updated.checked_mean(updated.updated_at)?;
+        *self = updated;
+        Some(())
+    }
+
+    fn insert(&mut self, amount: Balance, ts: Timestamp) {
+        self.checked_insert(amount, ts)
+            .expect(ERR_MEAN_LENGTH_OVERFLOW);
+    }
+
+    fn remove(&mut self, amount: Balance, length: Duration, ts: Timestamp) {
+        self.checked_remove(amount, length, ts)
+            .expect(ERR_MEAN_LENGTH_OVERFLOW);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tests::{HALF_DAY_MS, ONE_DAY_MS},
+        ONE_NEAR,
+    };
+
+    use super::*;
+
+    const INIT_ALPHA: u64 = 3 * ONE_DAY_MS;
+
+    #[test]
+    fn test_weighted_mean_length() {
+        let mut va = WeightedMeanLength::new();
+
+        // ========== Day 1 ==========
+        // - alice bonds 1000
+        // - bob bonds 2000
+        let ts = ONE_DAY_MS;
+        va.insert(1000 * ONE_NEAR, ts);
+        va.insert(2000 * ONE_NEAR, ts);
+
+        // current va should be 0
+        assert_eq!(va.mean(ts), 0);
+
+        // ========== Day 2 ==========
+        // - current va should be 1 day
+        // - charles bonds 1000
+        let ts = 2 * ONE_DAY_MS;
+        assert_eq!(va.mean(ts), ONE_DAY_MS);
+
+        va.insert(1000 * ONE_NEAR, ts);
+
+        // - va should be updated to 3/4 (0.75) day
+        assert_eq!(va.mean(ts), 3 * ONE_DAY_MS / 4);
+
+        // ========== Day 6 ==========
+        // - current va should be 19/4 (4.75) days
+        // - alice bonds 2000
+        let ts = 6 * ONE_DAY_MS;
+        assert_eq!(va.mean(ts), 19 * ONE_DAY_MS / 4);
+
+        va.insert(2000 * ONE_NEAR, ts);
+
+        // - va should be updated to 19/6 (3.167) days
+        assert_eq!(va.mean(ts), 19 * ONE_DAY_MS / 6);
+
+        // ========== Day 11 ==========
+        // - current va should be 49/6 (8.167) days
+        // - bob cancels 2000, length is 10 days
+        let ts = 11 * ONE_DAY_MS;
+        assert_eq!(va.mean(ts), 49 * ONE_DAY_MS / 6);
+
+        va.remove(2000 * ONE_NEAR, 10 * ONE_DAY_MS, ts);
+
+        // - va should be updated to 29/4 (7.25) days
+        assert_eq!(va.mean(ts), 29 * ONE_DAY_MS / 4);
+
+        // ========== Day 16 ==========
+        // - current va should be 49/4 (12.25) days
+        // - alice commits 1000, length 15 days
+        let ts = 16 * ONE_DAY_MS;
+        assert_eq!(va.mean(ts), 49 * ONE_DAY_MS / 4);
+
+        va.remove(1000 * ONE_NEAR, 15 * ONE_DAY_MS, ts);
+
+        // - va should be updated to 34/3 (11.33) days
+        assert_eq!(va.mean(ts), 34 * ONE_DAY_MS / 3);
+
+        // ========== Day 21 ==========
+        // - current va should be 49/3 (16.33) days
+        let ts = 21 * ONE_DAY_MS;
+        assert_eq!(va.mean(ts), 49 * ONE_DAY_MS / 3);
+    }
+
+    fn prepare_accrual_param() -> AccrualParameter {
+        AccrualParameter::new(
+            INIT_ALPHA,
+            0,
+            15 * ONE_DAY_MS,
+            ONE_DAY_MS,
+            100, // 1%
+        )
+    }
+
+    #[test]
+    fn test_weighted_mean_length_remove_after_rounding() {
+        let mut va = WeightedMeanLength::new();
+        va.insert(ONE_NEAR, 0);
+        // mean length 1/3 ms is rounded to 0 on update
+        va.insert(2 * ONE_NEAR, 1);
+        assert_eq!(va.mean(1), 0);
+
+        // the removed part is 1/3 ms of the total weight more than the sum
+        assert_eq!(va.checked_remove_drift(ONE_NEAR, 1, 1), Some(ONE_NEAR));
+        va.remove(ONE_NEAR, 1, 1);
+        assert_eq!(va.mean(1), 0);
+        assert_eq!(va.total_weight(), 2 * ONE_NEAR);
+    }
+
+    #[test]
+    fn test_weighted_mean_length_remove_drift() {
+        let mut va = WeightedMeanLength::new();
+        va.insert(ONE_NEAR, 0);
+        va.insert(ONE_NEAR, 0);
+
+        // no drift while the weighted sum covers the removed bond
+        assert_eq!(va.checked_remove_drift(ONE_NEAR, 10, 10), Some(0));
+        assert_eq!(va.checked_remove_drift(ONE_NEAR, 20, 10), Some(0));
+        assert_eq!(
+            va.checked_remove_drift(ONE_NEAR, 25, 10),
+            Some(5 * ONE_NEAR)
+        );
+        assert_eq!(va.checked_remove_drift(ONE_NEAR, 25, 20), Some(0));
+    }
+
+    #[test]
+    fn test_weighted_mean_length_checked() {
+        let mut va = WeightedMeanLength::new();
+        assert_eq!(va.checked_insert(u128::MAX, 0), Some(()));
+        // total weight overflows
+        assert_eq!(va.checked_insert(1, 0), None);
+        assert_eq!(va.checked_mean(u64::MAX), Some(u64::MAX));
+
+        assert_eq!(
+            va.checked_remove(u128::MAX, ONE_DAY_MS, ONE_DAY_MS),
+            Some(())
+        );
+        assert_eq!(va.checked_mean(ONE_DAY_MS), Some(0));
+        // removing more than inserted
+        assert_eq!(va.checked_remove(1, 0, ONE_DAY_MS), None);
+        // timestamp before last update
+        assert_eq!(va.checked_mean(0), None);
+        assert_eq!(va.checked_insert(1, 0), None);
+    }
+
+    #[test]
+    fn test_weighted_mean_length_overflow_kept_state() {
+        // this used to panic when converting the mean into u64
+        let mut va = WeightedMeanLength::new();
+        va.insert(1, 0);
+        va.insert(1, u64::MAX);
+        // mean is rounded up
+        let mean = u64::MAX / 2 + 1;
+        assert_eq!(va.mean(u64::MAX), mean);
+
+        // the mean after removal is 2 * mean, which exceeds u64
+        assert_eq!(va.checked_remove(1, 0, u64::MAX), None);
+        assert_eq!(va.total_weight(), 2);
+        assert_eq!(va.mean(u64::MAX), mean);
+    }
+
+    #[test]
+    fn test_accrual_param_basic() {
+        let mut accrual = prepare_accrual_param();
+
+        // first insert 100 near at day 0
+        accrual.weighted_mean_insert(100 * ONE_NEAR, 0);
+
+        // day 1
+        let ts = ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+
+        // day 14
+        let ts = 14 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+
+        // day 15
+        let ts = 15 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+
+        // day 16
+        let ts = 16 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA * 99 / 100);
+
+        // day 18
+        let ts = 18 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 251501500); // 3 days * 0.99^3
+        accrual.weighted_mean_insert(100 * ONE_NEAR, ts); // insert another 100 near at day 18, the mean length should be 9 days now
+
+        // day 19
+        let ts = 19 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 251501500);
+
+        // day 20
+        let ts = 20 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 251501500);
+        // remove the second 100 near at day 20, this makes the mean length 20 days
+        accrual.weighted_mean_remove(100 * ONE_NEAR, 2 * ONE_DAY_MS, ts);
+        // alpha won't be affected immediately
+        assert_eq!(accrual.current_alpha(ts), 251501500);
+
+        // day 21
+        let ts = 21 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 251501500 * 99 / 100);
+    }
+
+    #[test]
+    fn test_alpha_when_mean_length_below_target() {
+        let mut accrual = prepare_accrual_param();
+
+        // first insert 100 near at day 0
+        accrual.weighted_mean_insert(100 * ONE_NEAR, 0);
+
+        // day 14
+        let ts = 14 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+        // insert another 100 near
+        accrual.weighted_mean_insert(100 * ONE_NEAR, ts);
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+
+        // day 22
+        let ts = 22 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+        // remove first 100 near, whose length is 22 days
+        accrual.weighted_mean_remove(100 * ONE_NEAR, 22 * ONE_DAY_MS, ts);
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+
+        // day 29
+        let ts = 29 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+    }
+
+    #[test]
+    fn test_alpha_when_mean_length_above_target() {
+        let mut accrual = prepare_accrual_param();
+
+        // first insert 100 near at day 0
+        accrual.weighted_mean_insert(100 * ONE_NEAR, 0);
+
+        // day 16
+        let ts = 16 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 99 * INIT_ALPHA / 100);
+
+        // day 17.5
+        let ts = 17 * ONE_DAY_MS + HALF_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 254041920); // INIT_ALPHA * 0.99^2
+
+        // insert 2 near
+        accrual.weighted_mean_insert(ONE_NEAR, ts);
+        accrual.weighted_mean_insert(ONE_NEAR, ts);
+        assert_eq!(accrual.current_alpha(ts), 254041920); // INIT_ALPHA * 0.99^2
+
+        // day 20.5
+        let ts = 20 * ONE_DAY_MS + HALF_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 246496620); // INIT_ALPHA * 0.99^5
+                                                          // remove second 1 near
+        accrual.weighted_mean_remove(ONE_NEAR, 3 * ONE_DAY_MS, ts);
+        assert_eq!(accrual.current_alpha(ts), 246496620); // INIT_ALPHA * 0.99^5
+
+        // day 21
+        let ts = 21 * ONE_DAY_MS + HALF_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 244031653); // INIT_ALPHA * 0.99^6
+
+        // day 40
+        let ts = 40 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 201611286); // INIT_ALPHA * 0.99^25
+
+        // remove first 100 near, this SHALL NOT affect alpha
+        accrual.weighted_mean_remove(100 * ONE_NEAR, 40 * ONE_DAY_MS, ts);
+        assert_eq!(accrual.current_alpha(ts), 201611286); // INIT_ALPHA * 0.99^25
+
+        // day 41
+        let ts = 41 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 199595173); // INIT_ALPHA * 0.99^26
+    }
+
+    #[test]
+    fn test_alpha_when_mean_length_above_target_2() {
+        let mut accrual = prepare_accrual_param();
+
+        // first insert 100 near at day 0
+        accrual.weighted_mean_insert(100 * ONE_NEAR, 0);
+
+        // day 6
+        // insert another 100 near
+        let ts = 6 * ONE_DAY_MS;
+        accrual.weighted_mean_insert(100 * ONE_NEAR, ts);
+
+        // day 18
+        let ts = 18 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA); // INIT_ALPHA
+
+        // day 19
+        let ts = 19 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 99 * INIT_ALPHA / 100); // INIT_ALPHA * 0.99
+
+        // day 36
+        // insert a third 100 near
+        let ts = 36 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 216305959); // INIT_ALPHA * 0.99^18
+
+        accrual.weighted_mean_insert(100 * ONE_NEAR, ts);
+        assert_eq!(accrual.current_alpha(ts), 216305959); // INIT_ALPHA * 0.99^18
+
+        // day 40
+        let ts = 40 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 207782640); // INIT_ALPHA * 0.99^22
+
+        // remove last 100 near, this SHALL NOT affect alpha
+        accrual.weighted_mean_remove(100 * ONE_NEAR, 4 * ONE_DAY_MS, ts);
+        assert_eq!(accrual.current_alpha(ts), 207782640); // INIT_ALPHA * 0.99^22
+
+        // day 41
+        let ts = 41 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 205704813); // INIT_ALPHA * 0.99^23
+    }
+
+    #[test]
+    fn test_alpha_when_mean_length_increased_above_target() {
+        let mut accrual = prepare_accrual_param();
+
+        // first insert 100 near at day 0
+        accrual.weighted_mean_insert(100 * ONE_NEAR, 0);
+
+        // day 14, insert another 100 near
+        let ts = 14 * ONE_DAY_MS;
+        accrual.weighted_mean_insert(100 * ONE_NEAR, ts);
+
+        // day 16, remove second 100 near
+        let ts = 16 * ONE_DAY_MS;
+        let adjustment = accrual.weighted_mean_remove(100 * ONE_NEAR, 2 * ONE_DAY_MS, ts);
+        assert_eq!(accrual.current_alpha(ts), INIT_ALPHA);
+        assert_eq!(
+            adjustment.map(|a| a.new_exceeds_target_at),
+            Some(16 * ONE_DAY_MS)
+        );
+
+        // day 17
+        let ts = 17 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 99 * INIT_ALPHA / 100); // remember the target was exceeded at day 16
+    }
+
+    #[test]
+    fn test_alpha_when_mean_length_decreased_below_target() {
+        let mut accrual = prepare_accrual_param();
+
+        // first insert 100 near at day 0
+        accrual.weighted_mean_insert(100 * ONE_NEAR, 0);
+
+        // day 12, insert another 1 near
+        let ts = 12 * ONE_DAY_MS;
+        accrual.weighted_mean_insert(ONE_NEAR, ts);
+
+        // day 20.5, current mean length is 20.38 days
+        let ts = 20 * ONE_DAY_MS + HALF_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 246496620); // init_alpha * 0.99^5
+                                                          // remove first 100 near
+        accrual.weighted_mean_remove(100 * ONE_NEAR, 20 * ONE_DAY_MS + HALF_DAY_MS, ts);
+
+        // day 23
+        let ts = 23 * ONE_DAY_MS;
+        assert_eq!(accrual.current_alpha(ts), 246496620); // should be same as day 20.5, when mean length decreased below target
+    }
+}
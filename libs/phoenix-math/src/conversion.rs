@@ -0,0 +1,192 @@
+use std::convert::{TryFrom, TryInto};
+
+use crate::{BasisPoint, BigDecimal, Duration, FULL_BASIS_POINT, ONE_NEAR, ONE_PNEAR, ONE_YEAR_MS};
+
+const ERR_CONVERSION: &str = "Amount conversion overflow or zero price";
+
+fn near_like_decimals() -> BigDecimal {
+    BigDecimal::from(ONE_NEAR)
+}
+
+/// `amount * price / ONE_NEAR`, returns `None` on overflow
+fn checked_mul_price(amount: u128, price: u128) -> Option<u128> {
+    BigDecimal::from(amount)
+        .checked_mul(price.into())?
+        .checked_div(near_like_decimals())?
+        .checked_round_u128()
+}
+
+/// Same as `checked_mul_price`, but rounded down
+fn checked_mul_price_floor(amount: u128, price: u128) -> Option<u128> {
+    BigDecimal::from(amount)
+        .checked_mul(price.into())?
+        .checked_div(near_like_decimals())?
+        .checked_floor_u128()
+}
+
+/// `amount * ONE_NEAR / price`, returns `None` on overflow or zero price
+fn checked_div_price(amount: u128, price: u128) -> Option<u128> {
+    BigDecimal::from(amount)
+        .checked_mul(near_like_decimals())?
+        .checked_div(price.into())?
+        .checked_round_u128()
+}
+
+pub fn checked_near2linear(near_amount: u128, linear_price: u128) -> Option<u128> {
+    checked_div_price(near_amount, linear_price)
+}
+
+pub fn checked_linear2near(linear_amount: u128, linear_price: u128) -> Option<u128> {
+    checked_mul_price(linear_amount, linear_price)
+}
+
+/// Same as `checked_linear2near`, but rounded down, so that the NEAR amount
+/// never costs more than `linear_amount`
+pub fn checked_linear2near_floor(linear_amount: u128, linear_price: u128) -> Option<u128> {
+    checked_mul_price_floor(linear_amount, linear_price)
+}
+
+pub fn checked_pnear2near(pnear_amount: u128, pnear_price: u128) -> Option<u128> {
+    checked_mul_price(pnear_amount, pnear_price)
+}
+
+pub fn checked_near2pnear(near_amount: u128, pnear_price: u128) -> Option<u128> {
+    checked_div_price(near_amount, pnear_price)
+}
+
+pub fn near2linear(near_amount: u128, linear_price: u128) -> u128 {
+    checked_near2linear(near_amount, linear_price).expect(ERR_CONVERSION)
+}
+
+pub fn linear2near(linear_amount: u128, linear_price: u128) -> u128 {
+    checked_linear2near(linear_amount, linear_price).expect(ERR_CONVERSION)
+}
+
+pub fn linear2near_floor(linear_amount: u128, linear_price: u128) -> u128 {
+    checked_linear2near_floor(linear_amount, linear_price).expect(ERR_CONVERSION)
+}
+
+pub fn pnear2near(pnear_amount: u128, pnear_price: u128) -> u128 {
+    checked_pnear2near(pnear_amount, pnear_price).expect(ERR_CONVERSION)
+}
+
+pub fn near2pnear(near_amount: u128, pnear_price: u128) -> u128 {
+    checked_near2pnear(near_amount, pnear_price).expect(ERR_CONVERSION)
+}
+
+pub fn apply_basis_point<T>(value: T, point: u32) -> T
+where
+    T: std::ops::Mul<Output = T> + std::ops::Div<Output = T> + TryFrom<u32>,
+    <T as TryFrom<u32>>::Error: std::fmt::Debug,
+{
+    value * point.try_into().unwrap() / FULL_BASIS_POINT.try_into().unwrap()
+}
+
+/// Price of pNEAR given reserve pool size and pNEAR supply
+pub fn pnear_price_of(reserve_pool_near_amount: u128, pnear_total_supply: u128) -> u128 {
+    if pnear_total_supply == 0 {
+        return ONE_PNEAR;
+    }
+
+    (BigDecimal::from(reserve_pool_near_amount) * ONE_PNEAR.into() / pnear_total_supply.into())
+        .round_u128()
+}
+
+/// LiNEAR price implied by staking `near_amount` for `linear_amount`,
+/// returns `None` on overflow or zero LiNEAR
+pub fn linear_price_of(near_amount: u128, linear_amount: u128) -> Option<u128> {
+    checked_div_price(near_amount, linear_amount)
+}
+
+/// Amount accrued from `value` after `length`, which is `value * length / (length + alpha)`
+pub fn accrued_amount(value: u128, length: Duration, alpha: Duration) -> u128 {
+    (BigDecimal::from(value) * length.into() / (length + alpha).into()).round_u128()
+}
+
+/// Annualized growth rate from `start_price` to `end_price` in basis points,
+/// zero if the price didn't increase
+pub fn annualized_rate(start_price: u128, end_price: u128, duration: Duration) -> BasisPoint {
+    if end_price <= start_price || start_price == 0 || duration == 0 {
+        return 0;
+    }
+
+    let rate =
+        BigDecimal::from(end_price - start_price) * FULL_BASIS_POINT.into() * ONE_YEAR_MS.into()
+            / (BigDecimal::from(start_price) * duration.into());
+    rate.round_u128().try_into().unwrap_or(BasisPoint::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::ONE_DAY_MS;
+
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let price = 6 * ONE_NEAR / 5; // 1.2
+        assert_eq!(near2linear(12 * ONE_NEAR, price), 10 * ONE_NEAR);
+        assert_eq!(linear2near(10 * ONE_NEAR, price), 12 * ONE_NEAR);
+        assert_eq!(near2pnear(12 * ONE_NEAR, price), 10 * ONE_NEAR);
+        assert_eq!(pnear2near(10 * ONE_NEAR, price), 12 * ONE_NEAR);
+        assert_eq!(apply_basis_point(1000_u128, 300), 30);
+    }
+
+    #[test]
+    fn test_linear2near_floor() {
+        let price = 6 * ONE_NEAR / 5; // 1.2
+        assert_eq!(linear2near_floor(10 * ONE_NEAR, price), 12 * ONE_NEAR);
+        // 1.2 and 3.6 yocto
+        assert_eq!(linear2near(1, price), 1);
+        assert_eq!(linear2near_floor(1, price), 1);
+        assert_eq!(linear2near(3, price), 4);
+        assert_eq!(linear2near_floor(3, price), 3);
+        assert_eq!(linear2near_floor(1, ONE_NEAR / 2), 0);
+        assert_eq!(checked_linear2near_floor(u128::MAX, 2 * ONE_NEAR), None);
+    }
+
+    #[test]
+    fn test_checked_conversions() {
+        // 10B NEAR at price of 1000 NEAR is well above real values
+        let max_amount = 10u128.pow(34);
+        let max_price = 1000 * ONE_NEAR;
+        assert!(checked_near2linear(max_amount, 1).is_none());
+        assert!(checked_near2linear(max_amount, ONE_NEAR).is_some());
+        assert!(checked_linear2near(max_amount, max_price).is_some());
+        assert!(checked_pnear2near(max_amount, max_price).is_some());
+        assert!(checked_near2pnear(max_amount, ONE_NEAR).is_some());
+
+        // these used to panic, since intermediate values overflow even if the result fits
+        assert_eq!(checked_near2linear(u128::MAX, ONE_NEAR), None);
+        assert_eq!(checked_linear2near(u128::MAX, 2 * ONE_NEAR), None);
+        assert_eq!(checked_near2pnear(ONE_NEAR, 0), None);
+        assert_eq!(checked_pnear2near(u128::MAX, u128::MAX), None);
+    }
+
+    #[test]
+    fn test_accrued_amount() {
+        // y = x * t / (t + a)
+        let alpha = 30 * ONE_DAY_MS;
+        assert_eq!(accrued_amount(ONE_PNEAR, 0, alpha), 0);
+        assert_eq!(
+            accrued_amount(ONE_PNEAR, 30 * ONE_DAY_MS, alpha),
+            ONE_PNEAR / 2
+        );
+        assert_eq!(
+            accrued_amount(ONE_PNEAR, 60 * ONE_DAY_MS, alpha),
+            666666666666666666666667
+        );
+    }
+
+    #[test]
+    fn test_annualized_rate() {
+        let year = 365 * ONE_DAY_MS;
+        assert_eq!(annualized_rate(ONE_NEAR, 11 * ONE_NEAR / 10, year), 1000);
+        assert_eq!(
+            annualized_rate(ONE_NEAR, 11 * ONE_NEAR / 10, year / 2),
+            2000
+        );
+        assert_eq!(annualized_rate(ONE_NEAR, ONE_NEAR / 2, year), 0);
+        assert_eq!(annualized_rate(0, ONE_NEAR, year), 0);
+    }
+}
@@ -0,0 +1,205 @@
+// lints triggered by the expansion of `construct_uint!`
+#![allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+
+use std::cmp::Ordering;
+use std::convert::TryInto;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, Div, Mul, Sub};
+
+uint::construct_uint!(
+    pub struct U384(6);
+);
+
+const NUM_DECIMALS: u8 = 27;
+const BIG_DIVISOR: u128 = 10u128.pow(NUM_DECIMALS as u32);
+const HALF_DIVISOR: u128 = BIG_DIVISOR / 2;
+
+/// Fixed point decimal with 27 decimals, which has the same arithmetic
+/// and storage layout as `near_bigdecimal::BigDecimal` used before.
+#[derive(Copy, Clone, Default)]
+pub struct BigDecimal(U384);
+
+impl Display for BigDecimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let a = self.0 / U384::from(BIG_DIVISOR);
+        let b = (self.0 - a * U384::from(BIG_DIVISOR)).as_u128();
+        if b > 0 {
+            write!(f, "{}", format!("{}.{:027}", a, b).trim_end_matches('0'))
+        } else {
+            write!(f, "{}.0", a)
+        }
+    }
+}
+
+impl std::fmt::Debug for BigDecimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl From<u128> for BigDecimal {
+    fn from(a: u128) -> Self {
+        Self(U384::from(a) * U384::from(BIG_DIVISOR))
+    }
+}
+
+impl From<u64> for BigDecimal {
+    fn from(a: u64) -> Self {
+        Self(U384::from(a) * U384::from(BIG_DIVISOR))
+    }
+}
+
+impl From<u32> for BigDecimal {
+    fn from(a: u32) -> Self {
+        Self(U384::from(a) * U384::from(BIG_DIVISOR))
+    }
+}
+
+impl Add for BigDecimal {
+    type Output = Self;
+
+    fn add(self, rhs: BigDecimal) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for BigDecimal {
+    type Output = Self;
+
+    fn sub(self, rhs: BigDecimal) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Mul for BigDecimal {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self((self.0 * rhs.0 + U384::from(HALF_DIVISOR)) / U384::from(BIG_DIVISOR))
+    }
+}
+
+impl Div for BigDecimal {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        Self((self.0 * U384::from(BIG_DIVISOR) + U384::from(HALF_DIVISOR)) / rhs.0)
+    }
+}
+
+impl BigDecimal {
+    pub fn zero() -> Self {
+        Self(U384::zero())
+    }
+
+    pub fn one() -> Self {
+        Self(U384::from(BIG_DIVISOR))
+    }
+
+    pub fn round_u128(&self) -> u128 {
+        ((self.0 + U384::from(HALF_DIVISOR)) / U384::from(BIG_DIVISOR)).as_u128()
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let product = self
+            .0
+            .checked_mul(rhs.0)?
+            .checked_add(U384::from(HALF_DIVISOR))?;
+        Some(Self(product / U384::from(BIG_DIVISOR)))
+    }
+
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.0.is_zero() {
+            return None;
+        }
+        let dividend = self
+            .0
+            .checked_mul(U384::from(BIG_DIVISOR))?
+            .checked_add(U384::from(HALF_DIVISOR))?;
+        Some(Self(dividend / rhs.0))
+    }
+
+    /// Same as `round_u128`, returns `None` if the value doesn't fit in u128
+    pub fn checked_round_u128(&self) -> Option<u128> {
+        (self.0.checked_add(U384::from(HALF_DIVISOR))? / U384::from(BIG_DIVISOR))
+            .try_into()
+            .ok()
+    }
+
+    /// Integer part of the value, returns `None` if it doesn't fit in u128
+    pub fn checked_floor_u128(&self) -> Option<u128> {
+        (self.0 / U384::from(BIG_DIVISOR)).try_into().ok()
+    }
+}
+
+impl PartialEq<Self> for BigDecimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl PartialOrd for BigDecimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.0.partial_cmp(&other.0)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl borsh::BorshSerialize for BigDecimal {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        borsh::BorshSerialize::serialize(&self.0 .0, writer)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl borsh::BorshDeserialize for BigDecimal {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self(U384(borsh::BorshDeserialize::deserialize(buf)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn b(a: u128) -> BigDecimal {
+        BigDecimal::from(a)
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!((b(5) + b(2)).round_u128(), 7);
+        assert_eq!((b(5) - b(2)).round_u128(), 3);
+        assert_eq!((b(17) / b(5)).round_u128(), 3);
+        assert_eq!((b(18) / b(5)).round_u128(), 4);
+        assert_eq!((b(3) / b(5)).round_u128(), 1);
+        assert_eq!((b(3) / b(5) * b(5)).round_u128(), 3);
+        assert_eq!(BigDecimal::one(), b(1));
+        assert_eq!((b(1) / b(3)).to_string(), "0.333333333333333333333333333");
+    }
+
+    #[test]
+    fn test_checked() {
+        assert_eq!(b(17).checked_div(b(5)).unwrap().round_u128(), 3);
+        assert_eq!(b(3).checked_mul(b(5)).unwrap(), b(15));
+        assert_eq!(b(3).checked_sub(b(5)), None);
+        assert_eq!(b(1).checked_div(BigDecimal::zero()), None);
+
+        let max = b(u128::MAX);
+        assert_eq!(max.checked_round_u128(), Some(u128::MAX));
+        assert_eq!((max + b(1)).checked_round_u128(), None);
+        assert_eq!(max.checked_mul(max).and_then(|v| v.checked_mul(max)), None);
+
+        assert_eq!((b(18) / b(5)).checked_floor_u128(), Some(3));
+        assert_eq!(max.checked_floor_u128(), Some(u128::MAX));
+        assert_eq!((max + b(1)).checked_floor_u128(), None);
+    }
+}
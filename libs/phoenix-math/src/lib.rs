@@ -0,0 +1,30 @@
+//! Pure math of Phoenix Bonds, which is shared by the contract
+//! and off-chain tools such as the simulator.
+pub mod accrual;
+pub mod conversion;
+pub mod decimal;
+pub mod pool;
+
+pub use accrual::{AccrualParameter, AlphaAdjustment, WeightedMeanLength};
+pub use conversion::*;
+pub use decimal::BigDecimal;
+pub use pool::{Pools, RedeemOutcome};
+
+pub type Balance = u128;
+/// Timestamp in milliseconds
+pub type Timestamp = u64;
+/// Time duration in milliseconds
+pub type Duration = u64;
+pub const ONE_YEAR_MS: Duration = 365 * 24 * 3600 * 1000;
+
+pub type BasisPoint = u32;
+pub const FULL_BASIS_POINT: u32 = 10000;
+
+pub const ONE_NEAR: Balance = 10u128.pow(24);
+pub const ONE_PNEAR: Balance = ONE_NEAR;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    pub const ONE_DAY_MS: u64 = 24 * 3600 * 1000;
+    pub const HALF_DAY_MS: u64 = ONE_DAY_MS / 2;
+}
@@ -0,0 +1,233 @@
+use std::cmp::min;
+
+use crate::{
+    accrued_amount, apply_basis_point, linear2near, near2linear, near2pnear, pnear2near,
+    pnear_price_of, Balance, BasisPoint, Duration,
+};
+
+/// Pool accounting of the protocol. pNEAR total supply is kept by the token,
+/// it's updated here so that pNEAR price could be derived.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Pools {
+    /// amount of LiNEAR held by the protocol
+    pub linear_balance: Balance,
+    /// amount of NEAR in pending bonds
+    pub pending_pool_near_amount: Balance,
+    /// amount of NEAR that the protocol owns
+    pub permanent_pool_near_amount: Balance,
+    /// amount of NEAR to reward AMM liquidity provider
+    pub treasury_pool_near_amount: Balance,
+    pub pnear_total_supply: Balance,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RedeemOutcome {
+    /// LiNEAR that should be sent to the user
+    pub redeemed_linear: Balance,
+    /// redeem fee that stays in the reserve pool
+    pub fee_linear: Balance,
+}
+
+impl Pools {
+    pub fn reserve_pool_near_amount(&self, linear_price: Balance) -> Balance {
+        let protocol_owned_near_amount = linear2near(self.linear_balance, linear_price);
+        protocol_owned_near_amount.saturating_sub(
+            self.pending_pool_near_amount
+                + self.permanent_pool_near_amount
+                + self.treasury_pool_near_amount,
+        )
+    }
+
+    pub fn pnear_price(&self, linear_price: Balance) -> Balance {
+        pnear_price_of(
+            self.reserve_pool_near_amount(linear_price),
+            self.pnear_total_supply,
+        )
+    }
+
+    /// Cap of pNEAR that a pending bond of `bond_amount` is worth
+    pub fn note_cap(
+        &self,
+        bond_amount: Balance,
+        tau: BasisPoint,
+        linear_price: Balance,
+    ) -> Balance {
+        let amount_to_treasury = apply_basis_point(bond_amount, tau);
+        near2pnear(
+            bond_amount - amount_to_treasury,
+            self.pnear_price(linear_price),
+        )
+    }
+
+    /// Add a bond of `bond_amount` NEAR, which was staked for `linear_amount` LiNEAR
+    pub fn bond(&mut self, bond_amount: Balance, linear_amount: Balance) {
+        self.pending_pool_near_amount += bond_amount;
+        self.linear_balance += linear_amount;
+    }
+
+    /// Cancel a pending bond, returns the amount of LiNEAR to refund
+    pub fn cancel(&mut self, bond_amount: Balance, linear_price: Balance) -> Balance {
+        // Due to precision, the calculated refund amount can be slightly more than the actual balance,
+        // use `min` here to avoid subtraction overflow
+        let refund_linear = min(near2linear(bond_amount, linear_price), self.linear_balance);
+
+        self.pending_pool_near_amount -= bond_amount;
+        self.linear_balance -= refund_linear;
+        refund_linear
+    }
+
+    /// Commit a pending bond that has lasted for `length`,
+    /// returns the amount of pNEAR to mint
+    pub fn commit(
+        &mut self,
+        bond_amount: Balance,
+        length: Duration,
+        tau: BasisPoint,
+        alpha: Duration,
+        linear_price: Balance,
+    ) -> Balance {
+        let is_first_commit = self.pnear_total_supply == 0;
+
+        let amount_for_treasury = apply_basis_point(bond_amount, tau);
+        let mut treasury_gained_near_amount = amount_for_treasury;
+        if is_first_commit {
+            // assign all staking profits before the first commit to treasury.
+            // only the unassigned part is taken, since commits that minted no pNEAR
+            // leave the supply at zero and have assigned profits already
+            treasury_gained_near_amount += self.reserve_pool_near_amount(linear_price);
+        }
+
+        let reserve_should_gain_near_amount =
+            accrued_amount(bond_amount - amount_for_treasury, length, alpha);
+
+        // this should be equal to: near2pnear(reserve_should_gain_near_amount, self.pnear_price(linear_price))
+        let pnear_to_mint =
+            accrued_amount(self.note_cap(bond_amount, tau, linear_price), length, alpha);
+
+        let permanent_gained_near_amount =
+            bond_amount - amount_for_treasury - reserve_should_gain_near_amount;
+
+        self.treasury_pool_near_amount += treasury_gained_near_amount;
+        self.permanent_pool_near_amount += permanent_gained_near_amount;
+        self.pending_pool_near_amount -= bond_amount;
+        self.pnear_total_supply += pnear_to_mint;
+
+        pnear_to_mint
+    }
+
+    /// Redeem pNEAR for LiNEAR, `fee` of the redeemed value stays in the reserve pool
+    pub fn redeem(
+        &mut self,
+        pnear_amount: Balance,
+        fee: BasisPoint,
+        linear_price: Balance,
+    ) -> RedeemOutcome {
+        let redeemed_near = pnear2near(pnear_amount, self.pnear_price(linear_price));
+        let fee_near = apply_basis_point(redeemed_near, fee);
+
+        // Due to precision, the calculated redeemed amount can be slightly more than the actual balance,
+        // use `min` here to avoid subtraction overflow
+        let redeemed_linear = min(
+            near2linear(redeemed_near - fee_near, linear_price),
+            self.linear_balance,
+        );
+
+        self.linear_balance -= redeemed_linear;
+        self.pnear_total_supply -= pnear_amount;
+
+        RedeemOutcome {
+            redeemed_linear,
+            fee_linear: near2linear(fee_near, linear_price),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::ONE_DAY_MS, ONE_NEAR, ONE_PNEAR};
+
+    use super::*;
+
+    fn pools() -> Pools {
+        Pools {
+            linear_balance: 1_000_000 * ONE_NEAR,
+            pending_pool_near_amount: 900_000 * ONE_NEAR,
+            permanent_pool_near_amount: 30_000 * ONE_NEAR,
+            treasury_pool_near_amount: 10_000 * ONE_NEAR,
+            pnear_total_supply: 0,
+        }
+    }
+
+    #[test]
+    fn test_reserve_pool_and_pnear_price() {
+        let mut pools = pools();
+        let linear_price = 6 * ONE_NEAR / 5; // 1.2
+
+        assert_eq!(
+            pools.reserve_pool_near_amount(linear_price),
+            260_000 * ONE_NEAR
+        );
+        assert_eq!(pools.pnear_price(linear_price), ONE_PNEAR);
+
+        pools.pnear_total_supply = 130_000 * ONE_PNEAR;
+        assert_eq!(pools.pnear_price(linear_price), 2 * ONE_NEAR);
+
+        // reserve pool is empty when pools exceed LiNEAR value
+        assert_eq!(pools.reserve_pool_near_amount(ONE_NEAR / 2), 0);
+    }
+
+    #[test]
+    fn test_bond_and_cancel() {
+        let mut pools = Pools::default();
+        pools.bond(100 * ONE_NEAR, 100 * ONE_NEAR);
+
+        // LiNEAR price increases, and there's no reserve pool to refund more
+        let refund = pools.cancel(100 * ONE_NEAR, 2 * ONE_NEAR);
+        assert_eq!(refund, 50 * ONE_NEAR);
+        assert_eq!(pools.pending_pool_near_amount, 0);
+        assert_eq!(pools.linear_balance, 50 * ONE_NEAR);
+    }
+
+    #[test]
+    fn test_commit_and_redeem() {
+        let mut pools = Pools::default();
+        pools.bond(1000 * ONE_NEAR, 1000 * ONE_NEAR);
+
+        // first commit at length equal to alpha gets half of the cap, tau is 3%
+        let pnear = pools.commit(1000 * ONE_NEAR, ONE_DAY_MS, 300, ONE_DAY_MS, ONE_NEAR);
+        assert_eq!(pnear, 485 * ONE_PNEAR);
+        assert_eq!(pools.treasury_pool_near_amount, 30 * ONE_NEAR);
+        assert_eq!(pools.permanent_pool_near_amount, 485 * ONE_NEAR);
+        assert_eq!(pools.pending_pool_near_amount, 0);
+        assert_eq!(pools.pnear_price(ONE_NEAR), ONE_NEAR);
+
+        // 1% redeem fee stays in the reserve pool
+        let outcome = pools.redeem(100 * ONE_PNEAR, 100, ONE_NEAR);
+        assert_eq!(outcome.redeemed_linear, 99 * ONE_NEAR);
+        assert_eq!(outcome.fee_linear, ONE_NEAR);
+        assert_eq!(pools.pnear_total_supply, 385 * ONE_PNEAR);
+        assert!(pools.pnear_price(ONE_NEAR) > ONE_NEAR);
+    }
+
+    #[test]
+    fn test_commit_without_pnear_minted() {
+        let mut pools = Pools::default();
+        pools.bond(100 * ONE_NEAR, 100 * ONE_NEAR);
+        pools.bond(100 * ONE_NEAR, 100 * ONE_NEAR);
+
+        // committed right after bonding mints nothing, staking profits go to treasury
+        let linear_price = 2 * ONE_NEAR;
+        let pnear = pools.commit(100 * ONE_NEAR, 0, 300, ONE_DAY_MS, linear_price);
+        assert_eq!(pnear, 0);
+        assert_eq!(pools.treasury_pool_near_amount, 203 * ONE_NEAR);
+
+        // next commit is still the first to mint pNEAR, but profits were assigned already
+        let pnear = pools.commit(100 * ONE_NEAR, ONE_DAY_MS, 300, ONE_DAY_MS, linear_price);
+        assert_eq!(pnear, 97 * ONE_PNEAR / 2);
+        assert_eq!(pools.treasury_pool_near_amount, 206 * ONE_NEAR);
+        assert_eq!(
+            pools.reserve_pool_near_amount(linear_price),
+            97 * ONE_NEAR / 2
+        );
+    }
+}
@@ -9,6 +9,7 @@ import {
   getBondNote,
   getFtBalance,
   getLinearPrice,
+  mintLinear,
   setLinearPanic,
   getPnearPrice,
   commit,
@@ -114,21 +115,6 @@ test("Bond failed and all deposits are refunded", async (test) => {
 
 // ---- Bond with LiNEAR
 
-async function mintLinear(
-  account: NearAccount,
-  linear: NearAccount,
-  amount: string
-) {
-  await account.call(
-    linear,
-    "deposit_and_stake",
-    {},
-    {
-      attachedDeposit: amount,
-    }
-  );
-}
-
 test("Wrong token transferred", async (test) => {
   const { alice, phoenix, fakeLinear } = test.context.accounts;
   const amount = NEAR.parse("1000");
@@ -10,6 +10,10 @@ import {
   daysToMs,
   commit,
   listPendingNotes,
+  listAllNotes,
+  listAllPendingNotes,
+  bondersCount,
+  getUserPortfolio,
 } from "./common";
 import { init } from "./init";
 
@@ -123,3 +127,67 @@ test("list pending notes", async (test) => {
     await listPendingNotes(phoenix, alice, linearPrice, 0, 100)
   );
 });
+
+test("list notes of all users", async (test) => {
+  const { alice, bob, phoenix, linear } = test.context.accounts;
+
+  test.deepEqual(await bondersCount(phoenix), 0);
+
+  const linearPrice = await getLinearPrice(linear);
+  await bond(alice, phoenix, NEAR.parse("100"));
+  await bond(bob, phoenix, NEAR.parse("100"));
+  await bond(alice, phoenix, NEAR.parse("100"));
+  test.deepEqual(await bondersCount(phoenix), 2);
+
+  const all = await listAllNotes(phoenix, linearPrice, 0, 100);
+  test.deepEqual(
+    all.map((n) => [n.global_index, n.account_id, n.id]),
+    [
+      [0, alice.accountId, 0],
+      [1, bob.accountId, 0],
+      [2, alice.accountId, 1],
+    ]
+  );
+
+  await cancel(phoenix, bob, 0);
+  const pending = await listAllPendingNotes(phoenix, linearPrice, 0, 100);
+  test.deepEqual(
+    pending.map((n) => n.global_index),
+    [0, 2]
+  );
+  test.deepEqual(
+    (await listAllPendingNotes(phoenix, linearPrice, 1, 1)).map(
+      (n) => n.global_index
+    ),
+    [2]
+  );
+
+  const summary: any = await phoenix.view("get_summary", {
+    linear_price: linearPrice,
+  });
+  test.is(summary.total_notes_created, 3);
+  test.is(summary.total_notes_cancelled, 1);
+  test.is(summary.total_notes_committed, 0);
+  test.is(summary.bonders_count, 2);
+});
+
+test("user portfolio", async (test) => {
+  const { alice, phoenix, linear } = test.context.accounts;
+
+  const linearPrice = await getLinearPrice(linear);
+  await bond(alice, phoenix, NEAR.parse("100"));
+  await bond(alice, phoenix, NEAR.parse("50"));
+  await cancel(phoenix, alice, 1);
+
+  const portfolio = await getUserPortfolio(phoenix, alice, linearPrice);
+  test.is(portfolio.pnear_balance, "0");
+  test.is(portfolio.pending_notes_count, 1);
+  test.is(portfolio.pending_bond_amount, NEAR.parse("100").toString());
+  test.is(portfolio.next_pending_note_id, null);
+  test.is(portfolio.lost_and_found_linear, "0");
+  test.is(portfolio.settled_notes.cancelled_count, 1);
+  test.is(
+    portfolio.settled_notes.cancelled_bond_amount,
+    NEAR.parse("50").toString()
+  );
+});
@@ -5,6 +5,7 @@ import {
   bond,
   commit,
   daysToMs,
+  setDynamicTau,
   setLinearPrice,
   setTimestamp,
 } from "./common";
@@ -167,3 +168,33 @@ test("Commit after 10 years", async (test) => {
       .toFixed(0)
   );
 });
+
+test("Commit with dynamic tau", async (test) => {
+  const { alice, owner, phoenix } = test.context.accounts;
+  const linearPrice = NEAR.parse("1").toString();
+
+  // tau goes from 5% to 1% as treasury pool approaches 1000 NEAR
+  await setDynamicTau(phoenix, owner, {
+    treasury_target: NEAR.parse("1000").toString(),
+    min_tau: 100,
+    max_tau: 500,
+  });
+  test.is(await phoenix.view("get_current_tau", {}), 500);
+
+  await setTimestamp(phoenix, daysToMs(20));
+  const noteId = await bond(alice, phoenix, NEAR.parse("1000"));
+  await setTimestamp(phoenix, daysToMs(365 * 10 + 20));
+  await commit(phoenix, alice, noteId);
+
+  // 50 NEAR goes to treasury pool, so tau is lowered
+  const summary: any = await phoenix.view("get_summary", {
+    linear_price: linearPrice,
+  });
+  test.is(summary.treasury_pool_near_amount, NEAR.parse("50").toString());
+  test.is(summary.current_tau, 480);
+  test.is(summary.tau, tau * 100 * 100);
+
+  // static tau is used again once the policy is removed
+  await setDynamicTau(phoenix, owner, null);
+  test.is(await phoenix.view("get_current_tau", {}), tau * 100 * 100);
+});
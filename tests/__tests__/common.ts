@@ -13,6 +13,10 @@ export interface BondNote {
   accrued_pnear: string;
 }
 
+export interface GlobalBondNote extends BondNote {
+  global_index: number;
+}
+
 export function daysToMs(n: number) {
   return n * 24 * 3600 * 1000;
 }
@@ -201,17 +205,77 @@ export async function listPendingNotes(
   phoenix: NearAccount,
   account: NearAccount,
   linearPrice: string,
-  offset: number,
+  fromIndex: number,
   limit: number
 ): Promise<BondNote> {
   return phoenix.view("list_pending_notes", {
     account_id: account.accountId,
     linear_price: linearPrice,
-    offset,
+    from_index: fromIndex,
+    limit,
+  });
+}
+
+export async function listAllNotes(
+  phoenix: NearAccount,
+  linearPrice: string,
+  fromIndex: number,
+  limit: number
+): Promise<GlobalBondNote[]> {
+  return phoenix.view("list_all_notes", {
+    linear_price: linearPrice,
+    from_index: fromIndex,
     limit,
   });
 }
 
+export async function listAllPendingNotes(
+  phoenix: NearAccount,
+  linearPrice: string,
+  fromIndex: number,
+  limit: number
+): Promise<GlobalBondNote[]> {
+  return phoenix.view("list_all_pending_notes", {
+    linear_price: linearPrice,
+    from_index: fromIndex,
+    limit,
+  });
+}
+
+export async function bondersCount(phoenix: NearAccount): Promise<number> {
+  return phoenix.view("bonders_count", {});
+}
+
+export async function getUserPortfolio(
+  phoenix: NearAccount,
+  account: NearAccount,
+  linearPrice: string,
+  fromIndex = 0,
+  limit = 100
+): Promise<any> {
+  return phoenix.view("get_user_portfolio", {
+    account_id: account.accountId,
+    linear_price: linearPrice,
+    from_index: fromIndex,
+    limit,
+  });
+}
+
+export async function getSnapshots(
+  phoenix: NearAccount,
+  fromIndex: number,
+  limit: number
+): Promise<any[]> {
+  return phoenix.view("get_snapshots", { from_index: fromIndex, limit });
+}
+
+export async function getPnearApy(
+  phoenix: NearAccount,
+  window: number
+): Promise<any> {
+  return phoenix.view("get_pnear_apy", { window });
+}
+
 export async function cancel(
   phoenix: NearAccount,
   account: NearAccount,
@@ -266,27 +330,66 @@ export async function redeem(
   );
 }
 
+export type Asset = "linear" | "near" | "pnear";
+
 export async function getUserLostAndFound(
   phoenix: NearAccount,
-  account: NearAccount
+  account: NearAccount,
+  asset?: Asset
 ): Promise<string> {
-  return phoenix.view("user_lost_and_found", { account_id: account.accountId });
+  return phoenix.view("user_lost_and_found", {
+    account_id: account.accountId,
+    asset,
+  });
 }
 
 export async function claimLostAndFound(
   phoenix: NearAccount,
-  account: NearAccount
+  account: NearAccount,
+  asset?: Asset
 ): Promise<string> {
   return account.call(
     phoenix,
     "claim_lost_and_found",
-    {},
+    { asset },
     {
       gas: Gas.parse("100 Tgas"),
     }
   );
 }
 
+
+export async function claimLostAndFoundTo(
+  phoenix: NearAccount,
+  account: NearAccount,
+  receiver: NearAccount,
+  msg?: string
+): Promise<string> {
+  return account.call(
+    phoenix,
+    "claim_lost_and_found_to",
+    { receiver_id: receiver.accountId, msg },
+    {
+      gas: Gas.parse("150 Tgas"),
+    }
+  );
+}
+
+export async function mintLinear(
+  account: NearAccount,
+  linear: NearAccount,
+  amount: string
+) {
+  await account.call(
+    linear,
+    "deposit_and_stake",
+    {},
+    {
+      attachedDeposit: amount,
+    }
+  );
+}
+
 export async function bondWithLinear(
   account: NearAccount,
   phoenix: NearAccount,
@@ -307,3 +410,29 @@ export async function bondWithLinear(
     }
   );
 }
+
+export async function setRedeemFee(
+  phoenix: NearAccount,
+  owner: NearAccount,
+  fee: number
+) {
+  return owner.call(
+    phoenix,
+    "set_redeem_fee",
+    { new_fee: fee },
+    { attachedDeposit: NEAR.from("1") }
+  );
+}
+
+export async function setDynamicTau(
+  phoenix: NearAccount,
+  owner: NearAccount,
+  policy: { treasury_target: string; min_tau: number; max_tau: number } | null
+) {
+  return owner.call(
+    phoenix,
+    "set_dynamic_tau",
+    { policy },
+    { attachedDeposit: NEAR.from("1") }
+  );
+}
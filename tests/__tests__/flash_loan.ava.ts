@@ -0,0 +1,164 @@
+import { Gas, NEAR, NearAccount } from "near-workspaces";
+import {
+  assertFailure,
+  bond,
+  commit,
+  daysToMs,
+  getFtBalance,
+  setTimestamp,
+} from "./common";
+import { init } from "./init";
+
+const test = init();
+
+async function addReceiver(
+  phoenix: NearAccount,
+  owner: NearAccount,
+  receiver: NearAccount
+) {
+  return owner.call(
+    phoenix,
+    "add_flash_loan_receiver",
+    { account_id: receiver.accountId },
+    { attachedDeposit: NEAR.from("1") }
+  );
+}
+
+async function setMode(borrower: NearAccount, mode: string) {
+  return borrower.call(borrower, "set_mode", { mode });
+}
+
+async function borrow(borrower: NearAccount, amount: string) {
+  return borrower.call(
+    borrower,
+    "borrow",
+    { amount },
+    { gas: Gas.parse("300 Tgas") }
+  );
+}
+
+async function getLinearBalance(phoenix: NearAccount): Promise<string> {
+  const summary: any = await phoenix.view("get_summary", {
+    linear_price: NEAR.parse("1").toString(),
+  });
+  return summary.linear_balance;
+}
+
+// only LiNEAR of the reserve pool can be borrowed,
+// which gets LiNEAR when bonds are committed
+async function fundReservePool(alice: NearAccount, phoenix: NearAccount) {
+  const noteId = await bond(alice, phoenix, NEAR.parse("1000"));
+  await setTimestamp(phoenix, daysToMs(20));
+  await commit(phoenix, alice, noteId);
+}
+
+// borrower needs some LiNEAR to pay the fee
+async function fundBorrower(borrower: NearAccount, linear: NearAccount) {
+  await borrower.call(
+    linear,
+    "deposit_and_stake",
+    {},
+    { attachedDeposit: NEAR.parse("10").toString() }
+  );
+}
+
+test("Only approved receivers can borrow", async (test) => {
+  const { alice, phoenix } = test.context.accounts;
+
+  await bond(alice, phoenix, NEAR.parse("1000"));
+  await assertFailure(
+    test,
+    alice.call(
+      phoenix,
+      "flash_loan",
+      { amount: NEAR.parse("100").toString(), msg: "" },
+      { gas: Gas.parse("250 Tgas") }
+    ),
+    "Not an approved flash loan receiver"
+  );
+});
+
+test("Flash loan fee goes to reserve pool", async (test) => {
+  const { alice, owner, phoenix, linear, borrower } = test.context.accounts;
+
+  await fundReservePool(alice, phoenix);
+  await fundBorrower(borrower, linear);
+  await addReceiver(phoenix, owner, borrower);
+
+  const linearBalance = await getLinearBalance(phoenix);
+  await borrow(borrower, NEAR.parse("10").toString());
+
+  // 0.1% fee
+  const fee = NEAR.parse("0.01");
+  test.is(
+    await getLinearBalance(phoenix),
+    NEAR.from(linearBalance).add(fee).toString()
+  );
+  test.is(
+    await getFtBalance(linear, phoenix),
+    NEAR.from(linearBalance).add(fee).toString()
+  );
+  test.is(await phoenix.view("get_current_flash_loan", {}), null);
+});
+
+test("Unpaid flash loan fee removes the receiver", async (test) => {
+  const { alice, owner, phoenix, linear, borrower } = test.context.accounts;
+
+  await fundReservePool(alice, phoenix);
+  await fundBorrower(borrower, linear);
+  await addReceiver(phoenix, owner, borrower);
+  await setMode(borrower, "repay_principal");
+
+  const linearBalance = await getLinearBalance(phoenix);
+  await borrow(borrower, NEAR.parse("10").toString());
+
+  test.is(await getLinearBalance(phoenix), linearBalance);
+  test.is(await phoenix.view("get_current_flash_loan", {}), null);
+  test.deepEqual(await phoenix.view("get_flash_loan_receivers", {}), []);
+  // the contract is not paused
+  await bond(alice, phoenix, NEAR.parse("100"));
+  await assertFailure(
+    test,
+    borrow(borrower, NEAR.parse("10").toString()),
+    "Not an approved flash loan receiver"
+  );
+});
+
+test("Unreturned flash loan is written off", async (test) => {
+  const { alice, owner, phoenix, linear, borrower } = test.context.accounts;
+
+  await fundReservePool(alice, phoenix);
+  await addReceiver(phoenix, owner, borrower);
+  await setMode(borrower, "keep");
+
+  const linearBalance = await getLinearBalance(phoenix);
+  await borrow(borrower, NEAR.parse("10").toString());
+
+  test.is(
+    await getLinearBalance(phoenix),
+    NEAR.from(linearBalance).sub(NEAR.parse("10")).toString()
+  );
+  test.is(
+    await getFtBalance(linear, phoenix),
+    await getLinearBalance(phoenix)
+  );
+  await assertFailure(
+    test,
+    bond(alice, phoenix, NEAR.parse("100")),
+    "Contract paused"
+  );
+});
+
+test("Cannot borrow LiNEAR of other pools", async (test) => {
+  const { alice, owner, phoenix, borrower } = test.context.accounts;
+
+  // all LiNEAR is in the pending pool
+  await bond(alice, phoenix, NEAR.parse("1000"));
+  await addReceiver(phoenix, owner, borrower);
+
+  await assertFailure(
+    test,
+    borrow(borrower, NEAR.parse("10").toString()),
+    "Invalid flash loan amount"
+  );
+});
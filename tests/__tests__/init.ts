@@ -44,6 +44,7 @@ async function initFixtures(root: NearAccount) {
   const { owner, phoenix } = await initPhoenixBonds(root, linear);
 
   const fakeLinear = await initFakeLinear(root);
+  const borrower = await initMockBorrower(root, linear, phoenix);
 
   return {
     alice,
@@ -52,6 +53,7 @@ async function initFixtures(root: NearAccount) {
     fakeLinear,
     owner,
     phoenix,
+    borrower,
   };
 }
 
@@ -79,6 +81,25 @@ async function initFakeLinear(root: NearAccount) {
   );
 }
 
+async function initMockBorrower(
+  root: NearAccount,
+  linear: NearAccount,
+  phoenix: NearAccount
+) {
+  return createAndDeploy(
+    root,
+    "borrower",
+    "tests/compiled-contracts/mock_borrower.wasm",
+    {
+      method: "new",
+      args: {
+        linear_address: linear.accountId,
+        phoenix_address: phoenix.accountId,
+      },
+    }
+  );
+}
+
 async function initPhoenixBonds(root: NearAccount, linear: NearAccount) {
   const owner = await root.createSubAccount("owner");
 
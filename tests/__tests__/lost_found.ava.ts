@@ -4,10 +4,13 @@ import {
   bond,
   cancel,
   claimLostAndFound,
+  claimLostAndFoundTo,
   commit,
   daysToMs,
   ftStorageDeposit,
   ftTransfer,
+  getFtBalance,
+  getUserLostAndFound,
   redeem,
   setTimestamp,
 } from "./common";
@@ -80,3 +83,65 @@ test("LiNEAR transfer failed when claim lost&found", async (test) => {
   const claimedLostAndFound = await claimLostAndFound(phoenix, alice);
   test.is(claimedLostAndFound, "0");
 });
+
+test("Claim lost&found LiNEAR to another account", async (test) => {
+  const { alice, bob, phoenix, linear } = test.context.accounts;
+
+  const noteId = await bond(alice, phoenix, NEAR.parse("9999"));
+  // linear transfer would fail due to no storage deposit
+  await cancel(phoenix, alice, noteId);
+
+  // bob is not registered either, LiNEAR goes back to lost and found
+  test.is(await claimLostAndFoundTo(phoenix, alice, bob), "0");
+  test.is(
+    await getUserLostAndFound(phoenix, alice),
+    NEAR.parse("9999").toString()
+  );
+
+  await ftStorageDeposit(linear, bob);
+  test.is(
+    await claimLostAndFoundTo(phoenix, alice, bob),
+    NEAR.parse("9999").toString()
+  );
+  test.is(await getFtBalance(linear, bob), NEAR.parse("9999").toString());
+  test.is(await getUserLostAndFound(phoenix, alice), "0");
+});
+
+test("List lost&found entries", async (test) => {
+  const { alice, bob, phoenix } = test.context.accounts;
+
+  const aliceNote = await bond(alice, phoenix, NEAR.parse("100"));
+  const bobNote = await bond(bob, phoenix, NEAR.parse("200"));
+  await cancel(phoenix, alice, aliceNote);
+  await cancel(phoenix, bob, bobNote);
+
+  const entries: any[] = await phoenix.view("list_lost_and_found", {
+    asset: "linear",
+    limit: 10,
+  });
+  test.deepEqual(entries, [
+    { account_id: bob.accountId, amount: NEAR.parse("200").toString() },
+    { account_id: alice.accountId, amount: NEAR.parse("100").toString() },
+  ]);
+
+  const nextPage: any[] = await phoenix.view("list_lost_and_found", {
+    asset: "linear",
+    after: entries[0],
+    limit: 10,
+  });
+  test.deepEqual(nextPage, [entries[1]]);
+});
+
+test("Nothing to claim from NEAR and pNEAR lost&found", async (test) => {
+  const { alice, phoenix } = test.context.accounts;
+
+  for (const asset of ["near", "pnear"] as const) {
+    test.is(await getUserLostAndFound(phoenix, alice, asset), "0");
+
+    await assertFailure(
+      test,
+      claimLostAndFound(phoenix, alice, asset),
+      `No lost and found ${asset === "near" ? "NEAR" : "pNEAR"} to claim`
+    );
+  }
+});
@@ -0,0 +1,109 @@
+import Big from "big.js";
+import { Gas, NEAR, NearAccount } from "near-workspaces";
+import {
+  assertFailure,
+  bond,
+  bondWithLinear,
+  cancel,
+  ftStorageDeposit,
+  getBondNote,
+  mintLinear,
+  notesCount,
+} from "./common";
+import { init } from "./init";
+
+const test = init();
+
+async function pruneNotes(
+  phoenix: NearAccount,
+  account: NearAccount,
+  noteIds: number[]
+) {
+  return account.call(phoenix, "prune_notes", { note_ids: noteIds });
+}
+
+async function noteStorageBalanceOf(
+  phoenix: NearAccount,
+  account: NearAccount
+): Promise<any> {
+  return phoenix.view("note_storage_balance_of", {
+    account_id: account.accountId,
+  });
+}
+
+async function noteStorageWithdraw(phoenix: NearAccount, account: NearAccount) {
+  return account.call(
+    phoenix,
+    "note_storage_withdraw",
+    {},
+    {
+      attachedDeposit: NEAR.from("1"),
+      gas: Gas.parse("30 Tgas"),
+    }
+  );
+}
+
+test("Pending notes cannot be pruned", async (test) => {
+  const { alice, phoenix } = test.context.accounts;
+
+  const noteId = await bond(alice, phoenix, NEAR.parse("10"));
+  await assertFailure(
+    test,
+    pruneNotes(phoenix, alice, [noteId]),
+    "Only committed or cancelled notes can be pruned"
+  );
+});
+
+test("Prune cancelled note and withdraw storage deposit", async (test) => {
+  const { alice, phoenix, linear } = test.context.accounts;
+  await ftStorageDeposit(linear, alice);
+
+  const noteId = await bond(alice, phoenix, NEAR.parse("10"));
+  const storageAfterBond = await noteStorageBalanceOf(phoenix, alice);
+  test.is(storageAfterBond.deposit, NEAR.parse("0.01").toString());
+  test.true(Big(storageAfterBond.available).lt(NEAR.parse("0.01").toString()));
+
+  await cancel(phoenix, alice, noteId);
+  await pruneNotes(phoenix, alice, [noteId]);
+
+  // pruned note is gone, but note ids are kept
+  await assertFailure(
+    test,
+    getBondNote(phoenix, alice, noteId, NEAR.parse("1").toString()),
+    "Bond note doesn't exist"
+  );
+  test.deepEqual(await notesCount(phoenix, alice), 1);
+  const archived: any = await phoenix.view("archived_notes", {
+    account_id: alice.accountId,
+  });
+  test.is(archived.cancelled_count, 1);
+
+  const storageAfterPrune = await noteStorageBalanceOf(phoenix, alice);
+  test.true(
+    Big(storageAfterPrune.available).gt(storageAfterBond.available)
+  );
+
+  await noteStorageWithdraw(phoenix, alice);
+  const storageAfterWithdraw = await noteStorageBalanceOf(phoenix, alice);
+  test.is(storageAfterWithdraw.available, "0");
+});
+
+test("LiNEAR bond has no withdrawable storage deposit", async (test) => {
+  const { alice, phoenix, linear } = test.context.accounts;
+  await mintLinear(alice, linear, NEAR.parse("10").toString());
+  await ftStorageDeposit(linear, phoenix);
+
+  await bondWithLinear(alice, phoenix, linear, NEAR.parse("10").toString());
+  const storageAfterBond = await noteStorageBalanceOf(phoenix, alice);
+  test.is(storageAfterBond.deposit, "0");
+
+  await cancel(phoenix, alice, 0);
+  await pruneNotes(phoenix, alice, [0]);
+  const storageAfterPrune = await noteStorageBalanceOf(phoenix, alice);
+  test.is(storageAfterPrune.available, "0");
+  await assertFailure(
+    test,
+    noteStorageWithdraw(phoenix, alice),
+    "No storage deposit to withdraw"
+  );
+});
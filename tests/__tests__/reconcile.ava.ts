@@ -0,0 +1,72 @@
+import { Gas, NEAR, NearAccount } from "near-workspaces";
+import { assertFailure, bond, ftTransfer } from "./common";
+import { init } from "./init";
+
+const test = init();
+
+async function reconcile(
+  phoenix: NearAccount,
+  signer: NearAccount,
+  target?: "treasury" | "reserve"
+): Promise<any> {
+  return signer.call(
+    phoenix,
+    "reconcile_linear_balance",
+    { target },
+    {
+      attachedDeposit: NEAR.from("1"),
+      gas: Gas.parse("80 Tgas"),
+    }
+  );
+}
+
+test("Only owner can reconcile", async (test) => {
+  const { alice, phoenix } = test.context.accounts;
+
+  await assertFailure(test, reconcile(phoenix, alice), "Not owner");
+});
+
+test("Move donated LiNEAR into treasury", async (test) => {
+  const { alice, owner, phoenix, linear } = test.context.accounts;
+
+  await bond(alice, phoenix, NEAR.parse("100"));
+
+  // donate 10 LiNEAR
+  await alice.call(
+    linear,
+    "deposit_and_stake",
+    {},
+    { attachedDeposit: NEAR.parse("10").toString() }
+  );
+  await ftTransfer(linear, alice, phoenix, NEAR.parse("10").toString());
+
+  const report = await reconcile(phoenix, owner);
+  test.is(report.surplus_linear, NEAR.parse("10").toString());
+  test.is(report.deficit_linear, "0");
+
+  // reporting alone doesn't change pools
+  const summaryBefore: any = await phoenix.view("get_summary", {
+    linear_price: NEAR.parse("1").toString(),
+  });
+  test.is(summaryBefore.treasury_pool_near_amount, "0");
+
+  // surplus can only be moved while paused
+  await assertFailure(
+    test,
+    reconcile(phoenix, owner, "treasury"),
+    "Contract must be paused to move surplus LiNEAR"
+  );
+  await owner.call(phoenix, "pause", {}, { attachedDeposit: NEAR.from("1") });
+  await reconcile(phoenix, owner, "treasury");
+  const summaryAfter: any = await phoenix.view("get_summary", {
+    linear_price: NEAR.parse("1").toString(),
+  });
+  test.is(summaryAfter.treasury_pool_near_amount, NEAR.parse("10").toString());
+  test.is(summaryAfter.linear_balance, NEAR.parse("110").toString());
+
+  const discrepancy: any = await phoenix.view(
+    "get_linear_balance_discrepancy",
+    { actual_linear_balance: NEAR.parse("110").toString() }
+  );
+  test.is(discrepancy.surplus_linear, "0");
+});
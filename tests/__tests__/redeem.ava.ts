@@ -11,6 +11,7 @@ import {
   getPnearPrice,
   redeem,
   setLinearPrice,
+  setRedeemFee,
   setTimestamp,
 } from "./common";
 import { bootstrapEnds, init } from "./init";
@@ -128,3 +129,34 @@ test("Redeem LiNEAR", async (test) => {
   );
   test.is(bobRedeemedLinear, "1043120065605160302135142");
 });
+
+test("Redeem fee stays in the reserve pool", async (test) => {
+  const { alice, owner, phoenix, linear } = test.context.accounts;
+  await ftStorageDeposit(linear, alice);
+
+  const noteId = await bond(alice, phoenix, NEAR.parse("100"));
+  await setTimestamp(phoenix, bootstrapEnds);
+  await commit(phoenix, alice, noteId);
+
+  // 1% redeem fee
+  await setRedeemFee(phoenix, owner, 100);
+  const summary: any = await phoenix.view("get_summary", {
+    linear_price: NEAR.parse("1").toString(),
+  });
+  test.is(summary.redeem_fee, 100);
+
+  // pNEAR price is 1 and LiNEAR price is 1
+  const redeemedLinear = await redeem(
+    phoenix,
+    alice,
+    NEAR.parse("10").toString()
+  );
+  test.is(redeemedLinear, NEAR.parse("9.9").toString());
+
+  // the fee is left in the reserve pool, so pNEAR price grows
+  const pnearPrice = await getPnearPrice(
+    phoenix,
+    NEAR.parse("1").toString()
+  );
+  test.true(Big(pnearPrice).gt(NEAR.parse("1").toString()));
+});
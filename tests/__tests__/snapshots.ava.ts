@@ -0,0 +1,68 @@
+import { NEAR } from "near-workspaces";
+import {
+  bond,
+  commit,
+  daysToMs,
+  getPnearApy,
+  getSnapshots,
+  setTimestamp,
+} from "./common";
+import { init } from "./init";
+
+const test = init();
+
+test("Snapshots are recorded at most once per interval", async (test) => {
+  const { alice, phoenix } = test.context.accounts;
+
+  await setTimestamp(phoenix, daysToMs(20));
+  const noteId0 = await bond(alice, phoenix, NEAR.parse("100"));
+  const noteId1 = await bond(alice, phoenix, NEAR.parse("100"));
+  const noteId2 = await bond(alice, phoenix, NEAR.parse("100"));
+
+  await setTimestamp(phoenix, daysToMs(30));
+  await commit(phoenix, alice, noteId0);
+  await commit(phoenix, alice, noteId1);
+
+  let snapshots = await getSnapshots(phoenix, 0, 10);
+  test.is(snapshots.length, 1);
+  test.is(snapshots[0].index, 0);
+  test.is(snapshots[0].timestamp, daysToMs(30));
+
+  // no APY with a single snapshot
+  test.is(await getPnearApy(phoenix, daysToMs(365)), null);
+
+  await setTimestamp(phoenix, daysToMs(31));
+  await commit(phoenix, alice, noteId2);
+
+  snapshots = await getSnapshots(phoenix, 0, 10);
+  test.is(snapshots.length, 2);
+  test.is(snapshots[1].timestamp, daysToMs(31));
+
+  const apy = await getPnearApy(phoenix, daysToMs(365));
+  test.is(apy.start_timestamp, daysToMs(30));
+  test.is(apy.end_timestamp, daysToMs(31));
+});
+
+test("Only owner can set snapshot interval", async (test) => {
+  const { alice, owner, phoenix } = test.context.accounts;
+
+  await test.throwsAsync(
+    alice.call(
+      phoenix,
+      "set_snapshot_interval",
+      { new_interval: daysToMs(7) },
+      { attachedDeposit: "1" }
+    )
+  );
+
+  await owner.call(
+    phoenix,
+    "set_snapshot_interval",
+    { new_interval: daysToMs(7) },
+    { attachedDeposit: "1" }
+  );
+  const summary: any = await phoenix.view("get_summary", {
+    linear_price: NEAR.parse("1").toString(),
+  });
+  test.is(summary.snapshot_interval, daysToMs(7));
+});
@@ -0,0 +1,37 @@
+//! Off-chain simulator of Phoenix Bonds, which replays a scenario of
+//! bond, commit, cancel and redeem under a LiNEAR price curve, and outputs
+//! pNEAR price, pool sizes and alpha over time as CSV.
+//!
+//! Usage: `cargo run -p phoenix-simulator -- <scenario.json> > output.csv`
+mod scenario;
+mod simulator;
+
+use std::{fs, io, process};
+
+use scenario::Scenario;
+use simulator::Simulator;
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: phoenix-simulator <scenario.json>");
+            process::exit(1);
+        }
+    };
+
+    let scenario: Scenario = fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|content| serde_json::from_str(&content).map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load scenario {path}: {err}");
+            process::exit(1);
+        });
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if let Err(err) = Simulator::new(scenario).run(&mut out) {
+        eprintln!("Failed to write output: {err}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,213 @@
+use phoenix_math::{Balance, BasisPoint, Duration, Timestamp, ONE_NEAR};
+use serde::{Deserialize, Deserializer};
+
+/// A simulation scenario, which is loaded from a JSON file.
+/// NEAR, LiNEAR and pNEAR amounts and prices are decimal strings, e.g. `"1.5"`.
+#[derive(Deserialize)]
+pub struct Scenario {
+    pub tau: BasisPoint,
+    #[serde(default)]
+    pub redeem_fee: BasisPoint,
+    pub accrual: AccrualConfig,
+    pub bootstrap_ends: Timestamp,
+    /// how long the simulation lasts
+    pub duration: Duration,
+    /// interval between two output rows
+    pub step: Duration,
+    pub linear_price: PriceCurve,
+    /// scripted actions
+    #[serde(default)]
+    pub actions: Vec<ScriptedAction>,
+    /// randomized flow that runs on each step
+    pub random: Option<RandomFlow>,
+}
+
+#[derive(Deserialize)]
+pub struct AccrualConfig {
+    pub alpha: Duration,
+    pub min_alpha: Duration,
+    pub target_mean_length: Duration,
+    pub adjust_interval: Duration,
+    pub adjust_rate: BasisPoint,
+}
+
+/// LiNEAR price over time
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PriceCurve {
+    /// price grows linearly from `start` at `apy`
+    Apy {
+        #[serde(deserialize_with = "near_amount")]
+        start: Balance,
+        apy: BasisPoint,
+    },
+    /// price is interpolated between `(timestamp, price)` points
+    Points { points: Vec<PricePoint> },
+}
+
+#[derive(Deserialize)]
+pub struct PricePoint {
+    pub at: Timestamp,
+    #[serde(deserialize_with = "near_amount")]
+    pub price: Balance,
+}
+
+#[derive(Deserialize)]
+pub struct ScriptedAction {
+    pub at: Timestamp,
+    pub user: String,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    Bond {
+        #[serde(deserialize_with = "near_amount")]
+        amount: Balance,
+    },
+    /// `note` is the index of the user's notes
+    Commit {
+        note: usize,
+    },
+    Cancel {
+        note: usize,
+    },
+    Redeem {
+        #[serde(deserialize_with = "near_amount")]
+        amount: Balance,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct RandomFlow {
+    pub seed: u64,
+    /// max number of new bonds on each step
+    pub max_bonds_per_step: u32,
+    #[serde(deserialize_with = "near_amount")]
+    pub max_bond_amount: Balance,
+    /// each note is settled once it lasts for a random length up to this
+    pub max_note_length: Duration,
+    /// chance that a note is cancelled instead of committed
+    pub cancel_rate: BasisPoint,
+    /// chance that a pNEAR holder redeems part of the balance on each step
+    pub redeem_rate: BasisPoint,
+}
+
+impl PriceCurve {
+    pub fn price_at(&self, ts: Timestamp) -> Balance {
+        match self {
+            PriceCurve::Apy { start, apy } => {
+                start
+                    + start * u128::from(*apy) * u128::from(ts)
+                        / (u128::from(phoenix_math::FULL_BASIS_POINT)
+                            * u128::from(phoenix_math::ONE_YEAR_MS))
+            }
+            PriceCurve::Points { points } => {
+                let next = points.iter().position(|p| p.at > ts);
+                match next {
+                    Some(0) => points[0].price,
+                    Some(i) => {
+                        let (a, b) = (&points[i - 1], &points[i]);
+                        let elapsed = u128::from(ts - a.at);
+                        let span = u128::from(b.at - a.at);
+                        if b.price >= a.price {
+                            a.price + (b.price - a.price) * elapsed / span
+                        } else {
+                            a.price - (a.price - b.price) * elapsed / span
+                        }
+                    }
+                    None => points.last().map(|p| p.price).unwrap_or(ONE_NEAR),
+                }
+            }
+        }
+    }
+}
+
+/// Parse a decimal string in NEAR into yoctoNEAR
+pub fn parse_near(s: &str) -> Result<Balance, String> {
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    if frac.len() > 24 {
+        return Err(format!("Too many decimals: {s}"));
+    }
+    let int: Balance = if int.is_empty() {
+        0
+    } else {
+        int.parse().map_err(|_| format!("Invalid amount: {s}"))?
+    };
+    let frac: Balance = if frac.is_empty() {
+        0
+    } else {
+        format!("{frac:0<24}")
+            .parse()
+            .map_err(|_| format!("Invalid amount: {s}"))?
+    };
+    int.checked_mul(ONE_NEAR)
+        .and_then(|v| v.checked_add(frac))
+        .ok_or_else(|| format!("Amount too large: {s}"))
+}
+
+/// Format yoctoNEAR as a decimal string in NEAR
+pub fn format_near(amount: Balance) -> String {
+    let frac = amount % ONE_NEAR;
+    if frac == 0 {
+        return (amount / ONE_NEAR).to_string();
+    }
+    let frac = format!("{frac:024}");
+    format!("{}.{}", amount / ONE_NEAR, frac.trim_end_matches('0'))
+}
+
+fn near_amount<'de, D>(deserializer: D) -> Result<Balance, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    parse_near(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_near() {
+        assert_eq!(parse_near("1").unwrap(), ONE_NEAR);
+        assert_eq!(parse_near("1.5").unwrap(), 3 * ONE_NEAR / 2);
+        assert_eq!(parse_near(".01").unwrap(), ONE_NEAR / 100);
+        assert!(parse_near("1.0000000000000000000000001").is_err());
+        assert!(parse_near("abc").is_err());
+
+        assert_eq!(format_near(3 * ONE_NEAR / 2), "1.5");
+        assert_eq!(format_near(100 * ONE_NEAR), "100");
+        assert_eq!(format_near(1), "0.000000000000000000000001");
+    }
+
+    #[test]
+    fn test_price_curve() {
+        let curve = PriceCurve::Apy {
+            start: ONE_NEAR,
+            apy: 1000,
+        };
+        assert_eq!(curve.price_at(0), ONE_NEAR);
+        assert_eq!(
+            curve.price_at(phoenix_math::ONE_YEAR_MS),
+            11 * ONE_NEAR / 10
+        );
+
+        let curve = PriceCurve::Points {
+            points: vec![
+                PricePoint {
+                    at: 100,
+                    price: ONE_NEAR,
+                },
+                PricePoint {
+                    at: 200,
+                    price: 2 * ONE_NEAR,
+                },
+            ],
+        };
+        assert_eq!(curve.price_at(0), ONE_NEAR);
+        assert_eq!(curve.price_at(150), 3 * ONE_NEAR / 2);
+        assert_eq!(curve.price_at(300), 2 * ONE_NEAR);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+};
+
+use phoenix_math::{
+    apply_basis_point, near2linear, AccrualParameter, Balance, Duration, Pools, Timestamp,
+    FULL_BASIS_POINT, ONE_PNEAR,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::scenario::{format_near, Action, Scenario};
+
+const CSV_HEADER: &str = "timestamp,linear_price,pnear_price,pnear_total_supply,\
+reserve_pool_near_amount,pending_pool_near_amount,permanent_pool_near_amount,\
+treasury_pool_near_amount,linear_balance,alpha,mean_length,pending_notes";
+
+struct Note {
+    user: String,
+    bond_amount: Balance,
+    created_at: Timestamp,
+    pending: bool,
+    /// when a note of the randomized flow should be settled
+    settle_after: Option<Duration>,
+}
+
+/// Replays a scenario with the same math as the contract
+pub struct Simulator {
+    scenario: Scenario,
+    pools: Pools,
+    accrual: AccrualParameter,
+    notes: Vec<Note>,
+    user_notes: HashMap<String, Vec<usize>>,
+    pnear_balances: BTreeMap<String, Balance>,
+    rng: StdRng,
+    users_count: u32,
+}
+
+impl Simulator {
+    pub fn new(scenario: Scenario) -> Self {
+        let accrual = AccrualParameter::new(
+            scenario.accrual.alpha,
+            scenario.accrual.min_alpha,
+            scenario.accrual.target_mean_length,
+            scenario.accrual.adjust_interval,
+            scenario.accrual.adjust_rate,
+        );
+        let seed = scenario.random.as_ref().map(|r| r.seed).unwrap_or(0);
+        Self {
+            scenario,
+            pools: Pools::default(),
+            accrual,
+            notes: vec![],
+            user_notes: HashMap::new(),
+            pnear_balances: BTreeMap::new(),
+            rng: StdRng::seed_from_u64(seed),
+            users_count: 0,
+        }
+    }
+
+    /// Run the scenario and write a CSV row of protocol state on each step
+    pub fn run<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{CSV_HEADER}")?;
+
+        let mut actions = std::mem::take(&mut self.scenario.actions);
+        actions.sort_by_key(|a| a.at);
+        let mut actions = actions.into_iter().peekable();
+
+        let mut ts = 0;
+        while ts <= self.scenario.duration {
+            while let Some(scripted) = actions.next_if(|a| a.at <= ts) {
+                let result = match scripted.action {
+                    Action::Bond { amount } => {
+                        self.bond(&scripted.user, amount, scripted.at);
+                        Ok(())
+                    }
+                    Action::Commit { note } => self
+                        .user_note_index(&scripted.user, note)
+                        .and_then(|index| self.commit(index, scripted.at)),
+                    Action::Cancel { note } => self
+                        .user_note_index(&scripted.user, note)
+                        .and_then(|index| self.cancel(index, scripted.at)),
+                    Action::Redeem { amount } => self.redeem(&scripted.user, amount, scripted.at),
+                };
+                if let Err(err) = result {
+                    eprintln!("{} at {}: {err}", scripted.user, scripted.at);
+                }
+            }
+
+            if self.scenario.random.is_some() {
+                self.run_random_step(ts);
+            }
+
+            self.write_row(out, ts)?;
+            ts += self.scenario.step;
+        }
+        Ok(())
+    }
+
+    fn write_row<W: Write>(&self, out: &mut W, ts: Timestamp) -> io::Result<()> {
+        let linear_price = self.scenario.linear_price.price_at(ts);
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            ts,
+            format_near(linear_price),
+            format_near(self.pools.pnear_price(linear_price)),
+            format_near(self.pools.pnear_total_supply),
+            format_near(self.pools.reserve_pool_near_amount(linear_price)),
+            format_near(self.pools.pending_pool_near_amount),
+            format_near(self.pools.permanent_pool_near_amount),
+            format_near(self.pools.treasury_pool_near_amount),
+            format_near(self.pools.linear_balance),
+            self.accrual.current_alpha(ts),
+            self.accrual.mean_length.mean(ts),
+            self.notes.iter().filter(|n| n.pending).count(),
+        )
+    }
+
+    fn user_note_index(&self, user: &str, note: usize) -> Result<usize, String> {
+        self.user_notes
+            .get(user)
+            .and_then(|notes| notes.get(note).copied())
+            .ok_or_else(|| format!("note {note} doesn't exist"))
+    }
+
+    fn bond(&mut self, user: &str, amount: Balance, ts: Timestamp) -> usize {
+        let linear_amount = near2linear(amount, self.scenario.linear_price.price_at(ts));
+        self.pools.bond(amount, linear_amount);
+        self.accrual.weighted_mean_insert(amount, ts);
+
+        self.notes.push(Note {
+            user: user.to_string(),
+            bond_amount: amount,
+            created_at: ts,
+            pending: true,
+            settle_after: None,
+        });
+        let index = self.notes.len() - 1;
+        self.user_notes
+            .entry(user.to_string())
+            .or_default()
+            .push(index);
+        index
+    }
+
+    fn settle_note(&mut self, index: usize, ts: Timestamp) -> Result<(Balance, Duration), String> {
+        let note = &mut self.notes[index];
+        if !note.pending {
+            return Err("note is not pending".to_string());
+        }
+        note.pending = false;
+        let length = ts - note.created_at;
+        let bond_amount = note.bond_amount;
+        Ok((bond_amount, length))
+    }
+
+    fn commit(&mut self, index: usize, ts: Timestamp) -> Result<(), String> {
+        if ts < self.scenario.bootstrap_ends {
+            return Err("cannot commit when bootstrapping".to_string());
+        }
+        let (bond_amount, length) = self.settle_note(index, ts)?;
+        let pnear_amount = self.pools.commit(
+            bond_amount,
+            length,
+            self.scenario.tau,
+            self.accrual.current_alpha(ts),
+            self.scenario.linear_price.price_at(ts),
+        );
+        self.accrual.weighted_mean_remove(bond_amount, length, ts);
+
+        let user = self.notes[index].user.clone();
+        *self.pnear_balances.entry(user).or_default() += pnear_amount;
+        Ok(())
+    }
+
+    fn cancel(&mut self, index: usize, ts: Timestamp) -> Result<(), String> {
+        let (bond_amount, length) = self.settle_note(index, ts)?;
+        self.pools
+            .cancel(bond_amount, self.scenario.linear_price.price_at(ts));
+        self.accrual.weighted_mean_remove(bond_amount, length, ts);
+        Ok(())
+    }
+
+    fn redeem(&mut self, user: &str, amount: Balance, ts: Timestamp) -> Result<(), String> {
+        if ts < self.scenario.bootstrap_ends {
+            return Err("cannot redeem when bootstrapping".to_string());
+        }
+        let balance = self.pnear_balances.get(user).copied().unwrap_or(0);
+        if amount == 0 || amount > balance {
+            return Err("not enough pNEAR balance".to_string());
+        }
+        if self.pools.pnear_total_supply - amount <= ONE_PNEAR {
+            return Err("at least one pNEAR must be left".to_string());
+        }
+        self.pools.redeem(
+            amount,
+            self.scenario.redeem_fee,
+            self.scenario.linear_price.price_at(ts),
+        );
+        self.pnear_balances
+            .insert(user.to_string(), balance - amount);
+        Ok(())
+    }
+
+    fn chance(&mut self, rate: u32) -> bool {
+        self.rng.gen_range(0..FULL_BASIS_POINT) < rate
+    }
+
+    /// New bonds, settling notes that lasted long enough, and redeeming pNEAR
+    fn run_random_step(&mut self, ts: Timestamp) {
+        let flow = match self.scenario.random {
+            Some(flow) => flow,
+            None => return,
+        };
+
+        for _ in 0..self.rng.gen_range(0..=flow.max_bonds_per_step) {
+            self.users_count += 1;
+            let user = format!("user{}", self.users_count);
+            let amount = self.rng.gen_range(ONE_PNEAR / 10..=flow.max_bond_amount);
+            let index = self.bond(&user, amount, ts);
+            self.notes[index].settle_after = Some(self.rng.gen_range(0..=flow.max_note_length));
+        }
+
+        if ts >= self.scenario.bootstrap_ends {
+            let due: Vec<usize> = (0..self.notes.len())
+                .filter(|i| {
+                    let note = &self.notes[*i];
+                    note.pending
+                        && note
+                            .settle_after
+                            .map(|length| ts - note.created_at >= length)
+                            .unwrap_or(false)
+                })
+                .collect();
+            for index in due {
+                let result = if self.chance(flow.cancel_rate) {
+                    self.cancel(index, ts)
+                } else {
+                    self.commit(index, ts)
+                };
+                result.expect("failed to settle a due note");
+            }
+
+            let holders: Vec<(String, Balance)> = self
+                .pnear_balances
+                .iter()
+                .filter(|(_, balance)| **balance > 0)
+                .map(|(user, balance)| (user.clone(), *balance))
+                .collect();
+            for (user, balance) in holders {
+                if self.chance(flow.redeem_rate) {
+                    let amount =
+                        apply_basis_point(balance, self.rng.gen_range(1..=FULL_BASIS_POINT));
+                    // redeem could be rejected to keep one pNEAR in supply
+                    let _ = self.redeem(&user, amount, ts);
+                }
+            }
+        }
+    }
+}